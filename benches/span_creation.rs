@@ -1,5 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use opentelemetry_tracing::opentelemetry_sdk;
+use opentelemetry_tracing::sampler::AlwaysOff;
 use tracing::{span, Level};
 use tracing_subscriber::prelude::*;
 
@@ -24,6 +25,102 @@ pub fn span_creation_benchmark(c: &mut Criterion) {
             let _guard = span.enter();
         });
     });
+
+    c.bench_function("bare_span_creation", |b| {
+        b.iter(|| {
+            let span = span!(Level::TRACE, "x");
+            let _guard = span.enter();
+        });
+    });
+
+    c.bench_function("span_creation_20_attributes", |b| {
+        b.iter(|| {
+            let span = span!(
+                Level::TRACE,
+                "Main Span",
+                attribute1 = "v1",
+                attribute2 = "v2",
+                attribute3 = "v3",
+                attribute4 = "v4",
+                attribute5 = "v5",
+                attribute6 = "v6",
+                attribute7 = "v7",
+                attribute8 = "v8",
+                attribute9 = "v9",
+                attribute10 = "v10",
+                attribute11 = "v11",
+                attribute12 = "v12",
+                attribute13 = "v13",
+                attribute14 = "v14",
+                attribute15 = "v15",
+                attribute16 = "v16",
+                attribute17 = "v17",
+                attribute18 = "v18",
+                attribute19 = "v19",
+                attribute20 = "v20",
+            );
+            let _guard = span.enter();
+        });
+    });
+
+    c.bench_function("child_span_creation", |b| {
+        let parent = span!(Level::TRACE, "Parent Span");
+        let _parent_guard = parent.enter();
+        b.iter(|| {
+            let span = span!(
+                Level::TRACE,
+                "Child Span",
+                attribute1 = "v1",
+                attribute2 = "v2",
+                attribute3 = "v3",
+                attribute4 = "v4",
+                attribute5 = "v5",
+            );
+            let _guard = span.enter();
+        });
+    });
+
+    let unsampled = tracing_subscriber::registry().with(
+        opentelemetry_sdk::OpenTelemetrySdk::builder()
+            .with_sampler(AlwaysOff)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(unsampled, || {
+        c.bench_function("unsampled_span_creation", |b| {
+            b.iter(|| {
+                let span = span!(
+                    Level::TRACE,
+                    "Main Span",
+                    attribute1 = "v1",
+                    attribute2 = "v2",
+                    attribute3 = "v3",
+                    attribute4 = "v4",
+                    attribute5 = "v5",
+                );
+                let _guard = span.enter();
+            });
+        });
+    });
+
+    // The floor: the registry alone, with the SDK swapped for `DisabledSdk`.
+    let disabled = tracing_subscriber::registry().with(opentelemetry_sdk::DisabledSdk::new());
+    tracing::subscriber::with_default(disabled, || {
+        c.bench_function("disabled_span_creation", |b| {
+            b.iter(|| {
+                let span = span!(
+                    Level::TRACE,
+                    "Main Span",
+                    attribute1 = "v1",
+                    attribute2 = "v2",
+                    attribute3 = "v3",
+                    attribute4 = "v4",
+                    attribute5 = "v5",
+                );
+                let _guard = span.enter();
+            });
+        });
+    });
 }
 
 criterion_group!(benches, span_creation_benchmark);
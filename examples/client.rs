@@ -10,7 +10,10 @@ use tracing::{Level, span};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use opentelemetry_tracing::opentelemetry_sdk;
+use opentelemetry_tracing::exporter::StdoutExporter;
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
 use opentelemetry_tracing::opentelemetry_sdk::OtelSpanExt;
+use opentelemetry_tracing::propagator::{BaggagePropagator, JaegerPropagator};
 
 // A simple type alias so as to DRY.
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -18,7 +21,11 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>
 #[tokio::main]
 async fn main() -> Result<()> {
     // setup local telemetry
-    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::new();
+    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::builder()
+        .with_service_name("client")
+        .with_propagator(JaegerPropagator::new())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(StdoutExporter::new())))
+        .build()?;
     tracing_subscriber::registry()
         // .with(fmt::layer()) // Uncomment this line to see the fmt layer in action
         .with(otel_sdk_layer)
@@ -50,17 +57,19 @@ async fn fetch_url(url: hyper::Uri) -> Result<()> {
     let span = span!(
         Level::TRACE,
         "Main Span",
+        otel.kind = "client",
         attribute1 = "v1",
         attribute2 = "v2"
     );
     let _guard = span.enter();
+    span.set_baggage("user.tier", "gold");
 
     let path = url.path();
-    let req = Request::builder()
+    let request = Request::builder()
         .uri(path)
-        .header(hyper::header::HOST, authority.as_str())
-        .header("uber-trace-id", span.extract_jaeger_propagation().as_str())
-        .body(Empty::<Bytes>::new())?;
+        .header(hyper::header::HOST, authority.as_str());
+    let mut req = span.inject_into_request(request).body(Empty::<Bytes>::new())?;
+    BaggagePropagator::new().inject(&span, req.headers_mut());
 
 
     let mut res = sender.send_request(req).await?;
@@ -73,7 +82,7 @@ async fn fetch_url(url: hyper::Uri) -> Result<()> {
     while let Some(next) = res.frame().await {
         let frame = next?;
         if let Some(chunk) = frame.data_ref() {
-            io::stdout().write_all(&chunk).await?;
+            io::stdout().write_all(chunk).await?;
         }
     }
 
@@ -3,40 +3,26 @@ use std::net::SocketAddr;
 
 use bytes::Bytes;
 use http_body_util::Full;
+use hyper::body::Incoming;
 use hyper::server::conn::http1;
-use hyper::service::service_fn;
 use hyper::{Request, Response};
 use hyper_util::rt::{TokioIo, TokioTimer};
+use hyper_util::service::TowerToHyperService;
 use tokio::net::TcpListener;
-use tracing::{field, Level, span, warn};
+use tower::ServiceBuilder;
+use tracing::{Level, span, warn};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use opentelemetry_tracing::{opentelemetry_sdk, span_with_remote_parent};
-use opentelemetry_tracing::opentelemetry_sdk::OtelSpanExt;
+use opentelemetry_tracing::opentelemetry_sdk;
+use opentelemetry_tracing::exporter::StdoutExporter;
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::middleware::OtelLayer;
+use opentelemetry_tracing::propagator::JaegerPropagator;
 
 
 // An async function that consumes a request, does nothing with it and returns a
-// response.
-async fn hello(req: Request<impl hyper::body::Body>) -> Result<Response<Full<Bytes>>, Infallible> {
-    let span = span_with_remote_parent!(
-        req.headers().get("uber-trace-id").unwrap().to_str().unwrap().to_string(),
-        Level::TRACE,
-        "Main Span",
-        attribute1 = "v1",
-        attribute2 = "v2"
-    );
-
-    // NOTE(tommycpp): The reason why we need this function to change parent post span creation is
-    // there is no way in tracing to create a "fake span"(a span that doesn't really in Registry or
-    // localhost). But in distributed tracing, we need to create a span that doesn't exist in the
-    // localhost.
-    //
-    // To support "fake span" we need:
-    // 1. Add some information in Regitry to represent the "fake span", assign a tracing span Id for it
-    // 2. Fake span cannot be entered or exited, users cannot add events onto it because it doesnt' exist in localhost
-    // 3. Fake span can be used as parent for new spans.
-
-    let _guard = span.enter();
+// response. `OtelLayer` has already opened the server span continuing the client's trace.
+async fn hello(_req: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
     warn!(name: "my-event-name-inside-outer-span", event_id = 10, user_name = "otel");
     let span_inner = span!(
         Level::TRACE,
@@ -54,7 +40,10 @@ async fn hello(req: Request<impl hyper::body::Body>) -> Result<Response<Full<Byt
 
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::new();
+    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::builder()
+        .with_service_name("server")
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(StdoutExporter::new())))
+        .build()?;
     tracing_subscriber::registry()
         // .with(fmt::layer()) // Uncomment this line to see the fmt layer in action
         .with(otel_sdk_layer)
@@ -84,12 +73,15 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Spin up a new task in Tokio so we can continue to listen for new TCP connection on the
         // current task without waiting for the processing of the HTTP1 connection we just received
         // to finish
+        let service = ServiceBuilder::new()
+            .layer(OtelLayer::new(JaegerPropagator::new()))
+            .service_fn(hello);
         tokio::task::spawn(async move {
             // Handle the connection from the client using HTTP1 and pass any
             // HTTP requests received on that connection to the `hello` function
             if let Err(err) = http1::Builder::new()
                 .timer(TokioTimer::new())
-                .serve_connection(io, service_fn(hello))
+                .serve_connection(io, TowerToHyperService::new(service))
                 .await
             {
                 println!("Error serving connection: {:?}", err);
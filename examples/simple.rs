@@ -1,11 +1,21 @@
+use std::collections::HashMap;
+
 use opentelemetry_tracing::opentelemetry_sdk;
+use opentelemetry_tracing::exporter::StdoutExporter;
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
 use tracing::{field, span, warn, Level};
 use tracing_subscriber::prelude::*;
 use opentelemetry_tracing::opentelemetry_sdk::OtelSpanExt;
+use opentelemetry_tracing::propagator::JaegerPropagator;
 
 // cargo run --example simple
 fn main() {
-    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::new();
+    let otel_sdk_layer = opentelemetry_sdk::OpenTelemetrySdk::builder()
+        .with_service_name("simple")
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(StdoutExporter::new())))
+        .with_propagator(JaegerPropagator::new())
+        .build()
+        .expect("valid SDK configuration");
     tracing_subscriber::registry()
         // .with(fmt::layer()) // Uncomment this line to see the fmt layer in action
         .with(otel_sdk_layer)
@@ -17,7 +27,12 @@ fn main() {
         attribute1 = "v1",
         attribute2 = "v2"
     );
-    span.set_parent("262603779606908057216172753575155927278:4855502779463763640:0:1".to_string());
+    // Headers of an incoming request, say.
+    let headers = HashMap::from([(
+        "uber-trace-id".to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736:00f067aa0ba902b7:0:1".to_string(),
+    )]);
+    span.set_parent_from(&headers);
     let _guard = span.enter();
     warn!(name: "my-event-name-inside-outer-span", event_id = 10, user_name = "otel");
     let span_inner = span!(
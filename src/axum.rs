@@ -0,0 +1,37 @@
+//! axum integration, enabled by the `axum` feature.
+
+use ::axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use tracing::Span;
+
+use crate::{
+    middleware::OtelLayer,
+    opentelemetry_sdk::{OtelSpanExt, SpanContext},
+};
+
+/// Opens a `Server` span per request, see [`OtelService`](crate::middleware::OtelService).
+///
+/// Added with `Router::layer`, it runs after routing, so `http.route` and the span name use the
+/// matched route pattern, e.g. `GET /users/:id`, rather than the request's path.
+pub type Trace = OtelLayer;
+
+/// Extracts the context of the span the handler runs in, e.g. to log the trace id or pass it
+/// on by hand. Rejects the request with a 500 if there is no such span, i.e. the router isn't
+/// wrapped in [`Trace`] or no `OpenTelemetrySdk` is installed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OtelSpanContext(pub SpanContext);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for OtelSpanContext {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(_parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Span::current().span_context().map(OtelSpanContext).ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "no OpenTelemetry span context for this request",
+        ))
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Source of the timestamps recorded on spans and events.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+
+    /// Monotonic time that span durations are measured with, so they stay correct when the
+    /// wall clock is adjusted while a span is open.
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Reads the system's wall clock. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic timestamps in tests.
+///
+/// Clones share the same time, so keep a clone around after handing one to the SDK.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<(SystemTime, Instant)>>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new((start, Instant::now()))),
+        }
+    }
+
+    /// Moves both the wall clock and the monotonic clock forward.
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += by;
+        now.1 += by;
+    }
+
+    /// Sets the wall clock only, possibly backwards, like an NTP adjustment would.
+    pub fn set(&self, now: SystemTime) {
+        self.now.lock().unwrap().0 = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.now.lock().unwrap().0
+    }
+
+    fn instant(&self) -> Instant {
+        self.now.lock().unwrap().1
+    }
+}
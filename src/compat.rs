@@ -0,0 +1,241 @@
+//! Conversions to and from the `opentelemetry` crate's types, enabled by the
+//! `opentelemetry-compat` feature.
+
+use std::{fmt, sync::Mutex};
+
+use ::opentelemetry::{trace as otel, InstrumentationScope, KeyValue, Value};
+use ::opentelemetry_sdk::export::trace::{SpanData, SpanExporter as OtelSpanExporter};
+use ::opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+use ::opentelemetry_sdk::Resource as OtelResource;
+
+use crate::exporter::{ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{
+    AttributeValue, OTelSpanData, Resource, SpanContext, SpanId, SpanKind, SpanStatus, TraceFlags,
+    TraceId, TraceState,
+};
+
+impl From<TraceId> for otel::TraceId {
+    fn from(value: TraceId) -> Self {
+        otel::TraceId::from_bytes(value.0.to_be_bytes())
+    }
+}
+
+impl From<otel::TraceId> for TraceId {
+    fn from(value: otel::TraceId) -> Self {
+        TraceId(u128::from_be_bytes(value.to_bytes()))
+    }
+}
+
+impl From<SpanId> for otel::SpanId {
+    fn from(value: SpanId) -> Self {
+        otel::SpanId::from_bytes(value.0.to_be_bytes())
+    }
+}
+
+impl From<otel::SpanId> for SpanId {
+    fn from(value: otel::SpanId) -> Self {
+        SpanId(u64::from_be_bytes(value.to_bytes()))
+    }
+}
+
+impl From<TraceFlags> for otel::TraceFlags {
+    fn from(value: TraceFlags) -> Self {
+        otel::TraceFlags::new(value.to_u8())
+    }
+}
+
+impl From<otel::TraceFlags> for TraceFlags {
+    fn from(value: otel::TraceFlags) -> Self {
+        TraceFlags::new(value.to_u8())
+    }
+}
+
+impl From<&TraceState> for otel::TraceState {
+    fn from(value: &TraceState) -> Self {
+        // Our members are already validated, so this only fails on rules the two disagree on.
+        otel::TraceState::from_key_value(value.iter()).unwrap_or_default()
+    }
+}
+
+impl From<&otel::TraceState> for TraceState {
+    fn from(value: &otel::TraceState) -> Self {
+        TraceState::from_header(&value.header())
+    }
+}
+
+/// Our `SpanContext` doesn't carry a trace state, so the converted one is empty.
+impl From<SpanContext> for otel::SpanContext {
+    fn from(value: SpanContext) -> Self {
+        otel::SpanContext::new(
+            value.trace_id.into(),
+            value.span_id.into(),
+            value.flags.into(),
+            value.is_remote,
+            otel::TraceState::default(),
+        )
+    }
+}
+
+/// The `opentelemetry` span context had an all-zero trace or span id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSpanContext;
+
+impl fmt::Display for InvalidSpanContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid span context")
+    }
+}
+
+impl std::error::Error for InvalidSpanContext {}
+
+impl TryFrom<&otel::SpanContext> for SpanContext {
+    type Error = InvalidSpanContext;
+
+    fn try_from(value: &otel::SpanContext) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(InvalidSpanContext);
+        }
+        Ok(SpanContext {
+            is_remote: value.is_remote(),
+            ..SpanContext::new(
+                value.trace_id().into(),
+                value.span_id().into(),
+                value.trace_flags().into(),
+            )
+        })
+    }
+}
+
+impl From<&OTelSpanData> for SpanData {
+    fn from(span: &OTelSpanData) -> Self {
+        let span_context = otel::SpanContext::new(
+            span.trace_id.into(),
+            span.span_id.into(),
+            span.flags.into(),
+            false,
+            (&span.trace_state).into(),
+        );
+        let mut scope = InstrumentationScope::builder(span.scope_name.clone());
+        if let Some(version) = &span.scope_version {
+            scope = scope.with_version(version.clone());
+        }
+        // Both are non-exhaustive, so they can't be built with a struct expression.
+        let mut events = SpanEvents::default();
+        events.events = span
+            .events
+            .iter()
+            .map(|event| {
+                otel::Event::new(
+                    event.name.clone(),
+                    event.timestamp,
+                    key_values(event.attributes.iter()),
+                    event.dropped_attributes_count,
+                )
+            })
+            .collect();
+        events.dropped_count = span.dropped_events_count;
+        let mut links = SpanLinks::default();
+        links.links = span
+            .links
+            .iter()
+            .map(|link| {
+                let context = SpanContext::new(link.trace_id, link.span_id, TraceFlags::default());
+                otel::Link::new(context.into(), key_values(link.attributes.iter()), 0)
+            })
+            .collect();
+        links.dropped_count = span.dropped_links_count;
+        SpanData {
+            span_context,
+            parent_span_id: span.parent_span_id.unwrap_or_default().into(),
+            span_kind: otel_span_kind(span.kind),
+            name: span.name.clone().into(),
+            start_time: span.start_time,
+            // Spans reach exporters once closed; fall back to the start for any that aren't.
+            end_time: span.end_time.unwrap_or(span.start_time),
+            attributes: key_values(span.attributes.iter()),
+            dropped_attributes_count: span.dropped_attributes_count,
+            events,
+            links,
+            status: match &span.status {
+                SpanStatus::Unset => otel::Status::Unset,
+                SpanStatus::Ok => otel::Status::Ok,
+                SpanStatus::Error { description } => otel::Status::error(description.clone()),
+            },
+            instrumentation_scope: scope.build(),
+        }
+    }
+}
+
+impl From<&Resource> for OtelResource {
+    fn from(value: &Resource) -> Self {
+        OtelResource::new(key_values(value.iter()))
+    }
+}
+
+/// Exports through an exporter written for the `opentelemetry` SDK, e.g. one of the upstream
+/// `opentelemetry-*` exporter crates. Spans are converted to `SpanData` and each export is
+/// blocked on.
+pub struct CompatSpanExporter<E> {
+    exporter: Mutex<E>,
+}
+
+impl<E: OtelSpanExporter> CompatSpanExporter<E> {
+    pub fn new(exporter: E) -> CompatSpanExporter<E> {
+        CompatSpanExporter {
+            exporter: Mutex::new(exporter),
+        }
+    }
+}
+
+impl<E: OtelSpanExporter> SpanExporter for CompatSpanExporter<E> {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        let batch = spans.iter().map(SpanData::from).collect();
+        let export = self.exporter.lock().unwrap().export(batch);
+        futures_executor::block_on(export).map_err(|err| ExportError::Failed(err.to_string()))
+    }
+
+    fn force_flush(&self) {
+        let flush = self.exporter.lock().unwrap().force_flush();
+        if let Err(err) = futures_executor::block_on(flush) {
+            eprintln!("CompatSpanExporter: {err}");
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporter
+            .get_mut()
+            .unwrap()
+            .set_resource(&resource.into());
+    }
+
+    fn shutdown(&self) {
+        self.exporter.lock().unwrap().shutdown();
+    }
+}
+
+fn otel_span_kind(kind: SpanKind) -> otel::SpanKind {
+    match kind {
+        SpanKind::Client => otel::SpanKind::Client,
+        SpanKind::Server => otel::SpanKind::Server,
+        SpanKind::Producer => otel::SpanKind::Producer,
+        SpanKind::Consumer => otel::SpanKind::Consumer,
+        SpanKind::Internal => otel::SpanKind::Internal,
+    }
+}
+
+fn key_values<'a, K>(attributes: impl Iterator<Item = (K, &'a AttributeValue)>) -> Vec<KeyValue>
+where
+    K: AsRef<str>,
+{
+    attributes
+        .map(|(key, value)| {
+            let value = match value {
+                AttributeValue::Bool(value) => Value::Bool(*value),
+                AttributeValue::I64(value) => Value::I64(*value),
+                AttributeValue::F64(value) => Value::F64(*value),
+                AttributeValue::String(value) => Value::String(value.clone().into()),
+            };
+            KeyValue::new(key.as_ref().to_string(), value)
+        })
+        .collect()
+}
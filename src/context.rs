@@ -0,0 +1,98 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tracing::{span::Entered, Span};
+
+use crate::opentelemetry_sdk::{current_remote_context, RemoteContextGuard, RemoteSpanContext};
+
+/// A snapshot of the OTel context on this thread: the current span and the remote context
+/// attached with a `RemoteContextGuard`, if any.
+///
+/// Capture it before handing work to another task or thread (e.g. `tokio::spawn`) and attach
+/// it there, so spans created by that work continue the same trace instead of starting new ones.
+#[derive(Clone, Debug)]
+pub struct OtelContext {
+    span: Span,
+    remote: Option<RemoteSpanContext>,
+}
+
+impl OtelContext {
+    pub fn current() -> OtelContext {
+        OtelContext {
+            span: Span::current(),
+            remote: current_remote_context(),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Makes this context current on this thread until the guard is dropped.
+    pub fn attach(&self) -> OtelContextGuard<'_> {
+        OtelContextGuard {
+            _remote: self.remote.map(RemoteContextGuard::new),
+            _entered: self.span.enter(),
+        }
+    }
+}
+
+/// Returned by [`OtelContext::attach`]; restores the previous context when dropped. Like
+/// `RemoteContextGuard`, don't hold it across an `.await`.
+#[must_use = "the context is detached as soon as the guard is dropped"]
+pub struct OtelContextGuard<'a> {
+    // Dropped in declaration order: the remote context is restored before the span is exited.
+    _remote: Option<RemoteContextGuard>,
+    _entered: Entered<'a>,
+}
+
+pin_project! {
+    /// A future that runs with an [`OtelContext`] attached each time it is polled.
+    #[derive(Debug)]
+    pub struct WithOtelContext<F> {
+        #[pin]
+        inner: F,
+        context: OtelContext,
+    }
+}
+
+impl<F: Future> Future for WithOtelContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = this.context.attach();
+        this.inner.poll(cx)
+    }
+}
+
+/// Carries an [`OtelContext`] into futures, e.g. ones passed to `tokio::spawn`.
+pub trait OtelFutureExt: Future + Sized {
+    fn with_otel_context(self, context: OtelContext) -> WithOtelContext<Self> {
+        WithOtelContext {
+            inner: self,
+            context,
+        }
+    }
+
+    /// Shorthand for `with_otel_context(OtelContext::current())`.
+    fn with_current_otel_context(self) -> WithOtelContext<Self> {
+        self.with_otel_context(OtelContext::current())
+    }
+
+    /// Like `tracing::Instrument::instrument`, entering `span` around each poll, but also
+    /// carrying the remote context attached to this thread right now. Spans created while
+    /// polling, including after an `.await`, are children of `span` and inherit its baggage.
+    fn otel_instrument(self, span: Span) -> WithOtelContext<Self> {
+        self.with_otel_context(OtelContext {
+            span,
+            remote: current_remote_context(),
+        })
+    }
+}
+
+impl<F: Future> OtelFutureExt for F {}
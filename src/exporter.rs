@@ -0,0 +1,67 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "otlp")]
+mod file;
+mod in_memory;
+#[cfg(feature = "otlp")]
+mod otlp;
+#[cfg(feature = "otlp")]
+mod otlp_http;
+mod stdout;
+mod zipkin;
+
+#[cfg(feature = "otlp")]
+pub use file::FileExporter;
+pub use in_memory::InMemorySpanExporter;
+#[cfg(feature = "otlp")]
+pub use otlp::{OtlpGrpcExporter, DEFAULT_OTLP_GRPC_ENDPOINT, DEFAULT_OTLP_MAX_REQUEST_SIZE};
+#[cfg(feature = "otlp")]
+pub use otlp_http::{OtlpHttpEncoding, OtlpHttpExporter, DEFAULT_OTLP_HTTP_ENDPOINT};
+pub use stdout::{JsonFormatter, LogfmtFormatter, SpanFormatter, StdoutExporter};
+pub use zipkin::{ZipkinExporter, DEFAULT_ZIPKIN_ENDPOINT};
+
+use crate::opentelemetry_sdk::{OTelSpanData, Resource};
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// The exporter was already shut down.
+    Shutdown,
+    /// The spans could not be exported.
+    Failed(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Shutdown => write!(f, "exporter is shut down"),
+            ExportError::Failed(reason) => write!(f, "export failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Ships finished spans to a backend.
+pub trait SpanExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError>;
+
+    /// Flushes anything the exporter buffers internally.
+    fn force_flush(&self) {}
+
+    /// Called with the SDK's resource before any spans are exported.
+    fn set_resource(&mut self, _resource: &Resource) {}
+
+    fn shutdown(&self);
+}
+
+/// Nanoseconds since the Unix epoch, as exporters send timestamps: 0 for times before the
+/// epoch and `u64::MAX` for times past 2554.
+pub fn system_time_to_unix_nanos(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX),
+        Err(_) => 0,
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use prost::Message;
+
+use super::{otlp::export_request, ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{OTelSpanData, Resource};
+
+/// Appends each batch to a file as a length-delimited OTLP `ExportTraceServiceRequest`, for
+/// environments without a collector. Read it back with
+/// `ExportTraceServiceRequest::decode_length_delimited`.
+///
+/// With a maximum file size, a batch that would grow the file past it goes to a new file
+/// instead, named after the path with `.1`, `.2` and so on appended.
+pub struct FileExporter {
+    path: PathBuf,
+    max_file_size: Option<u64>,
+    file: Mutex<OpenFile>,
+    resource: Resource,
+    is_shutdown: AtomicBool,
+}
+
+struct OpenFile {
+    writer: BufWriter<File>,
+    // Bytes in the file, including ones still buffered.
+    len: u64,
+    // 0 for `path` itself, n for `path.n`.
+    index: u32,
+}
+
+impl OpenFile {
+    fn open(path: &Path, index: u32) -> Result<OpenFile, ExportError> {
+        let path = numbered_path(path, index);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        let len = file
+            .metadata()
+            .map_err(|err| ExportError::Failed(err.to_string()))?
+            .len();
+        Ok(OpenFile {
+            writer: BufWriter::new(file),
+            len,
+            index,
+        })
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()
+    }
+}
+
+fn numbered_path(path: &Path, index: u32) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let mut numbered = path.as_os_str().to_owned();
+    numbered.push(format!(".{index}"));
+    PathBuf::from(numbered)
+}
+
+impl FileExporter {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Result<FileExporter, ExportError> {
+        let path = path.into();
+        let file = OpenFile::open(&path, 0)?;
+        Ok(FileExporter {
+            path,
+            max_file_size: None,
+            file: Mutex::new(file),
+            resource: Resource::default(),
+            is_shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Roll over to a new numbered file rather than grow a file past `bytes`. A single batch
+    /// larger than that still gets a file of its own.
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+}
+
+impl SpanExporter for FileExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(ExportError::Shutdown);
+        }
+        let batch = export_request(&spans, &self.resource).encode_length_delimited_to_vec();
+        let mut file = self.file.lock().unwrap();
+        let full = self
+            .max_file_size
+            .is_some_and(|max| file.len > 0 && file.len + batch.len() as u64 > max);
+        if full {
+            let index = file.index + 1;
+            file.sync()
+                .map_err(|err| ExportError::Failed(err.to_string()))?;
+            *file = OpenFile::open(&self.path, index)?;
+        }
+        file.writer
+            .write_all(&batch)
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        file.len += batch.len() as u64;
+        Ok(())
+    }
+
+    fn force_flush(&self) {
+        let _ = self.file.lock().unwrap().writer.flush();
+    }
+
+    fn shutdown(&self) {
+        self.is_shutdown.store(true, Ordering::Relaxed);
+        if let Err(err) = self.file.lock().unwrap().sync() {
+            eprintln!(
+                "FileExporter: failed to sync {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+
+use super::{ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{AttributeValue, OTelSpanData, Resource, TraceId};
+
+/// Keeps exported spans in memory so tests can assert on them.
+///
+/// Clones share the same storage, so keep a clone around after handing one to a processor.
+#[derive(Clone, Debug, Default)]
+pub struct InMemorySpanExporter {
+    spans: Arc<Mutex<Vec<OTelSpanData>>>,
+    resource: Arc<Mutex<Option<Resource>>>,
+}
+
+impl InMemorySpanExporter {
+    pub fn new() -> InMemorySpanExporter {
+        InMemorySpanExporter::default()
+    }
+
+    /// Spans exported so far, in the order they finished.
+    pub fn get_finished_spans(&self) -> Vec<OTelSpanData> {
+        self.spans.lock().unwrap().clone()
+    }
+
+    /// Finished spans called `name`, in the order they finished.
+    pub fn spans_named(&self, name: &str) -> Vec<OTelSpanData> {
+        self.find(|span| span.name == name)
+    }
+
+    /// The first finished span of the trace called `name`.
+    pub fn span_by_trace_and_name(&self, trace_id: TraceId, name: &str) -> Option<OTelSpanData> {
+        let spans = self.spans.lock().unwrap();
+        spans
+            .iter()
+            .find(|span| span.trace_id == trace_id && span.name == name)
+            .cloned()
+    }
+
+    /// Finished spans with the attribute `key` set to `value`, in the order they finished.
+    pub fn spans_with_attribute(
+        &self,
+        key: &str,
+        value: impl Into<AttributeValue>,
+    ) -> Vec<OTelSpanData> {
+        let value = value.into();
+        self.find(|span| span.attributes.get(key) == Some(&value))
+    }
+
+    fn find(&self, matches: impl Fn(&OTelSpanData) -> bool) -> Vec<OTelSpanData> {
+        let spans = self.spans.lock().unwrap();
+        spans.iter().filter(|span| matches(span)).cloned().collect()
+    }
+
+    /// The resource set by the SDK, `None` until the exporter is registered.
+    pub fn resource(&self) -> Option<Resource> {
+        self.resource.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        self.spans.lock().unwrap().clear();
+    }
+}
+
+impl SpanExporter for InMemorySpanExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        self.spans.lock().unwrap().extend(spans);
+        Ok(())
+    }
+
+    fn shutdown(&self) {}
+
+    fn set_resource(&mut self, resource: &Resource) {
+        *self.resource.lock().unwrap() = Some(resource.clone());
+    }
+}
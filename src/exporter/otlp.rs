@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue},
+    resource::v1::Resource as OtlpResource,
+    trace::v1::{span, status, ResourceSpans, ScopeSpans, Span, SpanFlags, Status},
+};
+use prost::Message;
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Endpoint};
+use tracing::subscriber::NoSubscriber;
+
+use super::{system_time_to_unix_nanos, ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{AttributeValue, OTelSpanData, Resource, SpanKind, SpanStatus};
+
+pub const DEFAULT_OTLP_GRPC_ENDPOINT: &str = "http://localhost:4317";
+
+/// The largest message a collector accepts by default, 4 MiB.
+pub const DEFAULT_OTLP_MAX_REQUEST_SIZE: usize = 4 * 1024 * 1024;
+
+/// Sends spans to an OTLP collector over gRPC.
+///
+/// Exports block on a runtime owned by the exporter, so it must not be called from within
+/// another tokio runtime. Pair it with `BatchSpanProcessor`, which exports from its own thread.
+pub struct OtlpGrpcExporter {
+    client: TraceServiceClient<Channel>,
+    runtime: Runtime,
+    timeout: Duration,
+    max_request_size: usize,
+    resource: Resource,
+    is_shutdown: AtomicBool,
+}
+
+impl OtlpGrpcExporter {
+    /// Connects lazily to `endpoint`, e.g. `http://localhost:4317`.
+    pub fn new(endpoint: impl Into<String>) -> Result<OtlpGrpcExporter, ExportError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        let endpoint = Endpoint::from_shared(endpoint.into())
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        let channel = {
+            let _guard = runtime.enter();
+            endpoint.connect_lazy()
+        };
+        Ok(OtlpGrpcExporter {
+            client: TraceServiceClient::new(channel),
+            runtime,
+            timeout: Duration::from_secs(10),
+            max_request_size: DEFAULT_OTLP_MAX_REQUEST_SIZE,
+            resource: Resource::default(),
+            is_shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Maximum time a single export may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Maximum encoded size of a single export request. Larger batches are split into several
+    /// requests, sent one after the other. A span too large on its own is sent by itself.
+    pub fn with_max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = max_request_size;
+        self
+    }
+}
+
+impl SpanExporter for OtlpGrpcExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(ExportError::Shutdown);
+        }
+        let chunks = partition(&spans, &self.resource, self.max_request_size);
+        let mut errors = Vec::new();
+        // tonic and hyper are instrumented with tracing themselves; silence them so exporting
+        // doesn't produce spans that would be fed back into this exporter.
+        tracing::subscriber::with_default(NoSubscriber::default(), || {
+            for chunk in &chunks {
+                let mut request = tonic::Request::new(export_request(chunk, &self.resource));
+                request.set_timeout(self.timeout);
+                let mut client = self.client.clone();
+                // A failed request doesn't stop the rest of the batch from being sent.
+                if let Err(status) = self.runtime.block_on(client.export(request)) {
+                    errors.push(status.to_string());
+                }
+            }
+        });
+        match errors.first() {
+            None => Ok(()),
+            Some(error) if chunks.len() == 1 => Err(ExportError::Failed(error.clone())),
+            Some(error) => Err(ExportError::Failed(format!(
+                "{} of {} requests failed: {error}",
+                errors.len(),
+                chunks.len()
+            ))),
+        }
+    }
+
+    fn shutdown(&self) {
+        self.is_shutdown.store(true, Ordering::Relaxed);
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+    }
+}
+
+pub(crate) fn export_request(
+    spans: &[OTelSpanData],
+    resource: &Resource,
+) -> ExportTraceServiceRequest {
+    ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: Some(OtlpResource {
+                attributes: resource
+                    .iter()
+                    .map(|(key, value)| key_value(key, value))
+                    .collect(),
+                dropped_attributes_count: 0,
+            }),
+            scope_spans: scope_spans(spans),
+            schema_url: String::new(),
+        }],
+    }
+}
+
+// Splits `spans` into runs whose export requests encode to at most `max_request_size` bytes,
+// estimated from each span's own size plus the resource and scope they are sent with.
+fn partition<'a>(
+    spans: &'a [OTelSpanData],
+    resource: &Resource,
+    max_request_size: usize,
+) -> Vec<&'a [OTelSpanData]> {
+    let overhead = export_request(&[], resource).encoded_len();
+    let mut chunks = Vec::new();
+    let (mut start, mut size) = (0, overhead);
+    for (i, span) in spans.iter().enumerate() {
+        let span_size = {
+            let len = to_otlp_span(span).encoded_len();
+            // The span's own tag and length, and its scope's name and version.
+            let scope = span.scope_name.len() + span.scope_version.as_ref().map_or(0, String::len);
+            len + prost::length_delimiter_len(len) + 1 + scope + 16
+        };
+        if i > start && size + span_size > max_request_size {
+            chunks.push(&spans[start..i]);
+            (start, size) = (i, overhead);
+        }
+        size += span_size;
+    }
+    if start < spans.len() {
+        chunks.push(&spans[start..]);
+    }
+    chunks
+}
+
+// Groups spans by instrumentation scope, keeping the order scopes are first seen in.
+fn scope_spans(spans: &[OTelSpanData]) -> Vec<ScopeSpans> {
+    let mut scope_spans: Vec<ScopeSpans> = Vec::new();
+    for span in spans {
+        let version = span.scope_version.as_deref().unwrap_or_default();
+        let existing = scope_spans.iter_mut().find(|scope_spans| {
+            scope_spans
+                .scope
+                .as_ref()
+                .is_some_and(|scope| scope.name == span.scope_name && scope.version == version)
+        });
+        match existing {
+            Some(existing) => existing.spans.push(to_otlp_span(span)),
+            None => scope_spans.push(ScopeSpans {
+                scope: Some(InstrumentationScope {
+                    name: span.scope_name.clone(),
+                    version: version.to_string(),
+                    ..Default::default()
+                }),
+                spans: vec![to_otlp_span(span)],
+                schema_url: String::new(),
+            }),
+        }
+    }
+    scope_spans
+}
+
+// The trace flags, plus whether the parent is remote in bits 8 and 9.
+fn otlp_span_flags(span: &OTelSpanData) -> u32 {
+    let mut flags = span.flags.to_u8() as u32 | SpanFlags::ContextHasIsRemoteMask as u32;
+    if span.parent_is_remote {
+        flags |= SpanFlags::ContextIsRemoteMask as u32;
+    }
+    flags
+}
+
+fn otlp_span_kind(kind: SpanKind) -> span::SpanKind {
+    match kind {
+        SpanKind::Client => span::SpanKind::Client,
+        SpanKind::Server => span::SpanKind::Server,
+        SpanKind::Producer => span::SpanKind::Producer,
+        SpanKind::Consumer => span::SpanKind::Consumer,
+        SpanKind::Internal => span::SpanKind::Internal,
+    }
+}
+
+fn otlp_status(status: &SpanStatus) -> Status {
+    match status {
+        SpanStatus::Unset => Status::default(),
+        SpanStatus::Ok => Status {
+            code: status::StatusCode::Ok as i32,
+            message: String::new(),
+        },
+        SpanStatus::Error { description } => Status {
+            code: status::StatusCode::Error as i32,
+            message: description.clone(),
+        },
+    }
+}
+
+fn to_otlp_span(span: &OTelSpanData) -> Span {
+    Span {
+        // OTLP expects 16 byte trace ids and 8 byte span ids, big-endian.
+        trace_id: span.trace_id.0.to_be_bytes().to_vec(),
+        span_id: span.span_id.0.to_be_bytes().to_vec(),
+        parent_span_id: span
+            .parent_span_id
+            .map(|parent| parent.0.to_be_bytes().to_vec())
+            .unwrap_or_default(),
+        trace_state: span.trace_state.header_value(),
+        flags: otlp_span_flags(span),
+        name: span.name.clone(),
+        kind: otlp_span_kind(span.kind) as i32,
+        status: Some(otlp_status(&span.status)),
+        start_time_unix_nano: system_time_to_unix_nanos(span.start_time),
+        end_time_unix_nano: span.end_time.map_or(0, system_time_to_unix_nanos),
+        attributes: key_values(&span.attributes),
+        dropped_attributes_count: span.dropped_attributes_count,
+        dropped_events_count: span.dropped_events_count,
+        dropped_links_count: span.dropped_links_count,
+        links: span
+            .links
+            .iter()
+            .map(|link| span::Link {
+                trace_id: link.trace_id.0.to_be_bytes().to_vec(),
+                span_id: link.span_id.0.to_be_bytes().to_vec(),
+                attributes: key_values(&link.attributes),
+                ..Default::default()
+            })
+            .collect(),
+        events: span
+            .events
+            .iter()
+            .map(|event| span::Event {
+                time_unix_nano: system_time_to_unix_nanos(event.timestamp),
+                name: event.name.clone(),
+                attributes: key_values(&event.attributes),
+                dropped_attributes_count: event.dropped_attributes_count,
+            })
+            .collect(),
+    }
+}
+
+fn key_values(attributes: &HashMap<String, AttributeValue>) -> Vec<KeyValue> {
+    attributes
+        .iter()
+        .map(|(key, value)| key_value(key, value))
+        .collect()
+}
+
+fn key_value(key: &str, value: &AttributeValue) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(match value {
+                AttributeValue::Bool(value) => any_value::Value::BoolValue(*value),
+                AttributeValue::I64(value) => any_value::Value::IntValue(*value),
+                AttributeValue::F64(value) => any_value::Value::DoubleValue(*value),
+                AttributeValue::String(value) => any_value::Value::StringValue(value.clone()),
+            }),
+        }),
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http::{header, Method, Request, Uri};
+use http_body_util::Full;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use prost::Message;
+use tokio::runtime::Runtime;
+use tracing::subscriber::NoSubscriber;
+
+use super::{otlp::export_request, ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{OTelSpanData, Resource};
+
+pub const DEFAULT_OTLP_HTTP_ENDPOINT: &str = "http://localhost:4318/v1/traces";
+
+/// How `OtlpHttpExporter` encodes the export request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OtlpHttpEncoding {
+    #[default]
+    Protobuf,
+    /// The OTLP/JSON mapping, with ids as hex strings.
+    Json,
+}
+
+impl OtlpHttpEncoding {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OtlpHttpEncoding::Protobuf => "application/x-protobuf",
+            OtlpHttpEncoding::Json => "application/json",
+        }
+    }
+
+    fn encode(self, request: &ExportTraceServiceRequest) -> Result<Vec<u8>, ExportError> {
+        match self {
+            OtlpHttpEncoding::Protobuf => Ok(request.encode_to_vec()),
+            OtlpHttpEncoding::Json => {
+                serde_json::to_vec(request).map_err(|err| ExportError::Failed(err.to_string()))
+            }
+        }
+    }
+}
+
+/// Sends spans to an OTLP collector over HTTP, for environments where only `/v1/traces` is
+/// reachable. Spans are mapped the same way as by `OtlpGrpcExporter`.
+///
+/// Exports block on a runtime owned by the exporter, so it must not be called from within
+/// another tokio runtime. Pair it with `BatchSpanProcessor`, which exports from its own thread.
+pub struct OtlpHttpExporter {
+    client: Client<HttpConnector, Full<Bytes>>,
+    runtime: Runtime,
+    endpoint: Uri,
+    encoding: OtlpHttpEncoding,
+    timeout: Duration,
+    resource: Resource,
+    is_shutdown: AtomicBool,
+}
+
+impl OtlpHttpExporter {
+    /// Posts to `endpoint`, e.g. `http://localhost:4318/v1/traces`.
+    pub fn new(endpoint: impl AsRef<str>) -> Result<OtlpHttpExporter, ExportError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        let endpoint = endpoint
+            .as_ref()
+            .parse()
+            .map_err(|err: http::uri::InvalidUri| ExportError::Failed(err.to_string()))?;
+        Ok(OtlpHttpExporter {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            runtime,
+            endpoint,
+            encoding: OtlpHttpEncoding::default(),
+            timeout: Duration::from_secs(10),
+            resource: Resource::default(),
+            is_shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Protobuf by default.
+    pub fn with_encoding(mut self, encoding: OtlpHttpEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Maximum time a single export may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn request(&self, spans: &[OTelSpanData]) -> Result<Request<Full<Bytes>>, ExportError> {
+        let body = self.encoding.encode(&export_request(spans, &self.resource))?;
+        Request::builder()
+            .method(Method::POST)
+            .uri(self.endpoint.clone())
+            .header(header::CONTENT_TYPE, self.encoding.content_type())
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|err| ExportError::Failed(err.to_string()))
+    }
+}
+
+impl SpanExporter for OtlpHttpExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(ExportError::Shutdown);
+        }
+        let request = self.request(&spans)?;
+        // hyper is instrumented with tracing itself; silence it so exporting doesn't produce
+        // spans that would be fed back into this exporter.
+        tracing::subscriber::with_default(NoSubscriber::default(), || {
+            self.runtime.block_on(async {
+                let response = tokio::time::timeout(self.timeout, self.client.request(request))
+                    .await
+                    .map_err(|_| ExportError::Failed("export timed out".to_string()))?
+                    .map_err(|err| ExportError::Failed(err.to_string()))?;
+                if response.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(ExportError::Failed(format!(
+                        "collector responded with {}",
+                        response.status()
+                    )))
+                }
+            })
+        })
+    }
+
+    fn shutdown(&self) {
+        self.is_shutdown.store(true, Ordering::Relaxed);
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use serde_json::{json, Map, Value};
+
+use super::{system_time_to_unix_nanos, ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{AttributeValue, OTelSpanData, Resource, SpanStatus};
+
+/// Renders a span as the single line `StdoutExporter` writes for it, without the newline.
+pub trait SpanFormatter {
+    fn format(&self, span: &OTelSpanData, resource: &Resource) -> String;
+}
+
+/// Formats spans as JSON in the layout of `OTelSpanData`'s `serde` encoding, plus a `resource`
+/// object. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonFormatter;
+
+impl SpanFormatter for JsonFormatter {
+    fn format(&self, span: &OTelSpanData, resource: &Resource) -> String {
+        let mut line = to_json(span);
+        line["resource"] = Value::Object(resource_json(resource));
+        line.to_string()
+    }
+}
+
+/// Formats spans as logfmt `key=value` pairs: the span's name, ids, kind, status, start time
+/// and duration, followed by its attributes sorted by key. Events, links and the resource are
+/// left out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogfmtFormatter;
+
+impl SpanFormatter for LogfmtFormatter {
+    fn format(&self, span: &OTelSpanData, _resource: &Resource) -> String {
+        let mut line = String::new();
+        let mut pair = |key: &str, value: &str| {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(key);
+            line.push('=');
+            push_logfmt_value(&mut line, value);
+        };
+        pair("name", &span.name);
+        pair("trace_id", &span.trace_id.to_string());
+        pair("span_id", &span.span_id.to_string());
+        if let Some(parent_span_id) = span.parent_span_id {
+            pair("parent_span_id", &parent_span_id.to_string());
+        }
+        pair("kind", &format!("{:?}", span.kind).to_lowercase());
+        match &span.status {
+            SpanStatus::Unset => pair("status", "unset"),
+            SpanStatus::Ok => pair("status", "ok"),
+            SpanStatus::Error { description } => {
+                pair("status", "error");
+                pair("status_description", description);
+            }
+        }
+        pair(
+            "start_time",
+            &system_time_to_unix_nanos(span.start_time).to_string(),
+        );
+        pair("duration_ns", &span.duration().as_nanos().to_string());
+        let mut attributes: Vec<_> = span.attributes.iter().collect();
+        attributes.sort_by_key(|(key, _)| *key);
+        for (key, value) in attributes {
+            pair(key, &value.to_string());
+        }
+        line
+    }
+}
+
+// Quotes values that are empty or contain whitespace, `=`, quotes or backslashes.
+fn push_logfmt_value(line: &mut String, value: &str) {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '=' || c == '"' || c == '\\');
+    if !needs_quotes {
+        line.push_str(value);
+        return;
+    }
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Writes each span as one line, formatted by `JsonFormatter` unless another `SpanFormatter` is
+/// given, to stdout unless another writer is given.
+pub struct StdoutExporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+    formatter: Box<dyn SpanFormatter + Send + Sync>,
+    resource: Resource,
+}
+
+impl Default for StdoutExporter {
+    fn default() -> Self {
+        StdoutExporter::new()
+    }
+}
+
+impl StdoutExporter {
+    pub fn new() -> StdoutExporter {
+        StdoutExporter::with_writer(Box::new(io::stdout()))
+    }
+
+    pub fn with_writer(writer: Box<dyn Write + Send>) -> StdoutExporter {
+        StdoutExporter {
+            writer: Mutex::new(writer),
+            formatter: Box::new(JsonFormatter),
+            resource: Resource::default(),
+        }
+    }
+
+    pub fn with_formatter<T>(mut self, formatter: T) -> Self
+    where
+        T: SpanFormatter + Send + Sync + 'static,
+    {
+        self.formatter = Box::new(formatter);
+        self
+    }
+}
+
+impl SpanExporter for StdoutExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        let mut writer = self.writer.lock().unwrap();
+        for span in &spans {
+            let line = self.formatter.format(span, &self.resource);
+            writeln!(writer, "{line}").map_err(|err| ExportError::Failed(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn force_flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+
+    fn shutdown(&self) {
+        self.force_flush();
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+    }
+}
+
+fn status_json(status: &SpanStatus) -> Value {
+    match status {
+        SpanStatus::Unset => json!({ "code": "unset" }),
+        SpanStatus::Ok => json!({ "code": "ok" }),
+        SpanStatus::Error { description } => json!({ "code": "error", "description": description }),
+    }
+}
+
+fn to_json(span: &OTelSpanData) -> Value {
+    let links: Vec<Value> = span
+        .links
+        .iter()
+        .map(|link| {
+            json!({
+                "trace_id": link.trace_id.to_string(),
+                "span_id": link.span_id.to_string(),
+                "attributes": attributes_json(&link.attributes),
+            })
+        })
+        .collect();
+    let events: Vec<Value> = span
+        .events
+        .iter()
+        .map(|event| {
+            json!({
+                "name": event.name,
+                "timestamp": system_time_to_unix_nanos(event.timestamp),
+                "attributes": attributes_json(&event.attributes),
+                "dropped_attributes_count": event.dropped_attributes_count,
+            })
+        })
+        .collect();
+    json!({
+        "name": span.name,
+        "kind": format!("{:?}", span.kind).to_lowercase(),
+        "status": status_json(&span.status),
+        "trace_id": span.trace_id.to_string(),
+        "span_id": span.span_id.to_string(),
+        "scope_name": span.scope_name,
+        "scope_version": span.scope_version,
+        "parent_span_id": span.parent_span_id.map(|parent| parent.to_string()),
+        "parent_is_remote": span.parent_is_remote,
+        "start_time": system_time_to_unix_nanos(span.start_time),
+        "end_time": span.end_time.map_or(0, system_time_to_unix_nanos),
+        "attributes": attributes_json(&span.attributes),
+        "dropped_attributes_count": span.dropped_attributes_count,
+        "dropped_events_count": span.dropped_events_count,
+        "dropped_links_count": span.dropped_links_count,
+        "flags": span.flags.to_u8(),
+        "trace_state": span.trace_state.header_value(),
+        "depth": span.depth,
+        "links": links,
+        "events": events,
+    })
+}
+
+fn attributes_json(attributes: &HashMap<String, AttributeValue>) -> Map<String, Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| (key.clone(), attribute_json(value)))
+        .collect()
+}
+
+fn resource_json(resource: &Resource) -> Map<String, Value> {
+    resource
+        .iter()
+        .map(|(key, value)| (key.to_string(), attribute_json(value)))
+        .collect()
+}
+
+fn attribute_json(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::Bool(value) => Value::from(*value),
+        AttributeValue::I64(value) => Value::from(*value),
+        // Non-finite floats have no JSON representation and become null.
+        AttributeValue::F64(value) => Value::from(*value),
+        AttributeValue::String(value) => Value::from(value.as_str()),
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use http::{header, Method, Request, Uri};
+use http_body_util::Full;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use serde_json::{json, Map, Value};
+use tokio::runtime::Runtime;
+use tracing::subscriber::NoSubscriber;
+
+use super::{system_time_to_unix_nanos, ExportError, SpanExporter};
+use crate::opentelemetry_sdk::{OTelSpanData, Resource, SpanKind, SpanStatus, SERVICE_NAME};
+
+pub const DEFAULT_ZIPKIN_ENDPOINT: &str = "http://localhost:9411/api/v2/spans";
+
+/// POSTs spans to a Zipkin collector as a JSON array in the Zipkin v2 format.
+///
+/// Like `OtlpGrpcExporter`, it blocks on a runtime of its own, so pair it with
+/// `BatchSpanProcessor` rather than calling it from within another tokio runtime.
+pub struct ZipkinExporter {
+    client: Client<HttpConnector, Full<Bytes>>,
+    runtime: Runtime,
+    endpoint: Uri,
+    timeout: Duration,
+    service_name: String,
+    is_shutdown: AtomicBool,
+}
+
+impl ZipkinExporter {
+    /// Sends spans to `endpoint`, e.g. `http://localhost:9411/api/v2/spans`.
+    pub fn new(endpoint: impl AsRef<str>) -> Result<ZipkinExporter, ExportError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        let endpoint = endpoint
+            .as_ref()
+            .parse()
+            .map_err(|err: http::uri::InvalidUri| ExportError::Failed(err.to_string()))?;
+        Ok(ZipkinExporter {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            runtime,
+            endpoint,
+            timeout: Duration::from_secs(10),
+            service_name: service_name(&Resource::default()),
+            is_shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Maximum time a single export may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl SpanExporter for ZipkinExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(ExportError::Shutdown);
+        }
+        let body: Vec<Value> = spans
+            .iter()
+            .map(|span| to_zipkin(span, &self.service_name))
+            .collect();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.endpoint.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(Value::from(body).to_string())))
+            .map_err(|err| ExportError::Failed(err.to_string()))?;
+        // hyper is instrumented with tracing itself; silence it so exporting doesn't produce
+        // spans that would be fed back into this exporter.
+        tracing::subscriber::with_default(NoSubscriber::default(), || {
+            self.runtime.block_on(async {
+                let response = tokio::time::timeout(self.timeout, self.client.request(request))
+                    .await
+                    .map_err(|_| ExportError::Failed("export timed out".to_string()))?
+                    .map_err(|err| ExportError::Failed(err.to_string()))?;
+                if response.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(ExportError::Failed(format!(
+                        "zipkin responded with {}",
+                        response.status()
+                    )))
+                }
+            })
+        })
+    }
+
+    fn shutdown(&self) {
+        self.is_shutdown.store(true, Ordering::Relaxed);
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.service_name = service_name(resource);
+    }
+}
+
+fn service_name(resource: &Resource) -> String {
+    resource
+        .get(SERVICE_NAME)
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+fn unix_micros(time: SystemTime) -> u64 {
+    system_time_to_unix_nanos(time) / 1_000
+}
+
+// Zipkin has no internal kind; such spans leave `kind` out.
+fn zipkin_kind(kind: SpanKind) -> Option<&'static str> {
+    match kind {
+        SpanKind::Client => Some("CLIENT"),
+        SpanKind::Server => Some("SERVER"),
+        SpanKind::Producer => Some("PRODUCER"),
+        SpanKind::Consumer => Some("CONSUMER"),
+        SpanKind::Internal => None,
+    }
+}
+
+fn to_zipkin(span: &OTelSpanData, service_name: &str) -> Value {
+    // Zipkin tags are strings only.
+    let mut tags: Map<String, Value> = span
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::from(value.to_string())))
+        .collect();
+    match &span.status {
+        SpanStatus::Unset => {}
+        SpanStatus::Ok => {
+            tags.insert("otel.status_code".to_string(), "OK".into());
+        }
+        SpanStatus::Error { description } => {
+            tags.insert("otel.status_code".to_string(), "ERROR".into());
+            tags.insert("error".to_string(), description.as_str().into());
+        }
+    }
+    if !span.scope_name.is_empty() {
+        tags.insert("otel.scope.name".to_string(), span.scope_name.as_str().into());
+    }
+    let start = unix_micros(span.start_time);
+    let annotations: Vec<Value> = span
+        .events
+        .iter()
+        .map(|event| json!({ "timestamp": unix_micros(event.timestamp), "value": event.name }))
+        .collect();
+
+    let mut zipkin = json!({
+        "traceId": span.trace_id.to_string(),
+        "id": span.span_id.to_string(),
+        "name": span.name,
+        "timestamp": start,
+        // Zipkin rejects zero durations, so sub-microsecond spans are rounded up.
+        "duration": (span.duration().as_micros() as u64).max(1),
+        "localEndpoint": { "serviceName": service_name },
+        "tags": tags,
+    });
+    if let Some(parent) = span.parent_span_id {
+        zipkin["parentId"] = parent.to_string().into();
+    }
+    if let Some(kind) = zipkin_kind(span.kind) {
+        zipkin["kind"] = kind.into();
+    }
+    if !annotations.is_empty() {
+        zipkin["annotations"] = annotations.into();
+    }
+    zipkin
+}
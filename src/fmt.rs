@@ -0,0 +1,58 @@
+//! Trace ids in `tracing_subscriber::fmt` log lines.
+
+use std::fmt;
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::{
+    fmt::{
+        format::{Format, Writer},
+        FmtContext, FormatEvent, FormatFields,
+    },
+    registry::LookupSpan,
+};
+
+use crate::opentelemetry_sdk::local_context;
+
+/// Event formatter that starts each line with the `trace_id` and `span_id` of the span the
+/// event happened in, so logs can be matched with traces, then formats the event with the
+/// wrapped formatter, e.g. `fmt::layer().event_format(OtelFormat::new(fmt::format()))`.
+/// Events outside of any span the SDK created are formatted as is.
+#[derive(Clone, Debug, Default)]
+pub struct OtelFormat<F = Format> {
+    inner: F,
+}
+
+impl<F> OtelFormat<F> {
+    pub fn new(inner: F) -> OtelFormat<F> {
+        OtelFormat { inner }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for OtelFormat<F>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        // The innermost span with ids, skipping remote parent stand-ins.
+        let context = ctx
+            .event_scope()
+            .into_iter()
+            .flatten()
+            .find_map(|span| local_context(&span.extensions()));
+        if let Some(context) = context {
+            write!(
+                writer,
+                "trace_id={} span_id={} ",
+                context.trace_id, context.span_id
+            )?;
+        }
+        self.inner.format_event(ctx, writer.by_ref(), event)
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rand::{rngs, Rng, SeedableRng};
+
+use crate::opentelemetry_sdk::{SpanId, TraceId};
+
+thread_local! {
+    static CURRENT_RNG: RefCell<rngs::SmallRng> = RefCell::new(rngs::SmallRng::from_entropy());
+}
+
+/// Creates the ids of new spans and traces.
+pub trait IdGenerator {
+    fn new_trace_id(&self) -> TraceId;
+
+    fn new_span_id(&self) -> SpanId;
+}
+
+/// Generates random ids from a fast, non-cryptographic thread-local RNG. The default.
+///
+/// Each thread seeds its RNG from OS entropy, so ids created concurrently on different threads
+/// don't collide. Plug in another `IdGenerator` if ids must be unpredictable.
+///
+/// The RNG is only borrowed while drawing an id, so samplers, processors and exporters may
+/// create spans themselves without a double borrow.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        CURRENT_RNG.with(|rng| TraceId::from(rng.borrow_mut().gen::<u128>()))
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        CURRENT_RNG.with(|rng| SpanId::from(rng.borrow_mut().gen::<u64>()))
+    }
+}
+
+/// Returns the same trace id every time and span ids counting up from a starting value, so
+/// tests can assert on exact ids.
+#[derive(Debug)]
+pub struct FixedIdGenerator {
+    trace_id: TraceId,
+    next_span_id: AtomicU64,
+}
+
+impl FixedIdGenerator {
+    pub fn new(trace_id: TraceId, first_span_id: SpanId) -> FixedIdGenerator {
+        FixedIdGenerator {
+            trace_id,
+            next_span_id: AtomicU64::new(first_span_id.0),
+        }
+    }
+}
+
+impl IdGenerator for FixedIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        SpanId(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+}
@@ -1,13 +1,17 @@
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod clock;
+#[cfg(feature = "opentelemetry-compat")]
+pub mod compat;
+pub mod context;
+pub mod exporter;
+pub mod fmt;
+pub mod id_generator;
+pub mod middleware;
 pub mod opentelemetry_sdk;
-mod propagator;
-
-
-// Define a simple macro
-macro_rules! say_hello {
-    () => {
-        println!("Hello, world!");
-    };
-}
+pub mod processor;
+pub mod sampler;
+pub mod propagator;
 
 #[macro_export]
 macro_rules! span_with_remote_parent {
@@ -19,8 +23,33 @@ macro_rules! span_with_remote_parent {
                 $name,
                 $($fields)*
             );
+            #[allow(deprecated)]
             span.set_parent($remote_parent);
             span
         }
     };
-}
\ No newline at end of file
+}
+
+/// Creates a span like `tracing::span!` and links it to each of `$links`, e.g. the
+/// `traceparent` headers of the messages in a batch. Links may be header values, as parsed by
+/// `SpanLink::from_header`, or span contexts; see `IntoSpanLink`. A malformed header or invalid
+/// context is skipped rather than failing the span.
+#[macro_export]
+macro_rules! span_with_links {
+    ($links:expr, $lvl:expr, $name:expr, $($fields:tt)*) => {
+        {
+            let span = tracing::span!(
+                target: module_path!(),
+                $lvl,
+                $name,
+                $($fields)*
+            );
+            for link in $links {
+                if let Some(link) = $crate::opentelemetry_sdk::IntoSpanLink::into_span_link(link) {
+                    $crate::opentelemetry_sdk::OtelSpanExt::add_link(&span, link);
+                }
+            }
+            span
+        }
+    };
+}
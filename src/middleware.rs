@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use http::{Request, Response};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{field, info_span, Span};
+
+use crate::{
+    opentelemetry_sdk::{
+        apply_extracted, default_propagator, OtelSpanExt, RemoteSpanContext, SpanStatus,
+    },
+    propagator::TextMapPropagator,
+};
+
+pub const HTTP_METHOD: &str = "http.method";
+pub const HTTP_ROUTE: &str = "http.route";
+pub const HTTP_STATUS_CODE: &str = "http.status_code";
+
+/// Tower layer wrapping services in [`OtelService`].
+///
+/// The default layer extracts the remote parent of incoming requests with the propagator of
+/// the installed `OpenTelemetrySdk`.
+#[derive(Clone, Default)]
+pub struct OtelLayer {
+    // `None` for the SDK's propagator.
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+}
+
+impl OtelLayer {
+    /// Extracts the remote parent of incoming requests with `propagator` rather than the SDK's.
+    pub fn new(propagator: impl TextMapPropagator + Send + Sync + 'static) -> OtelLayer {
+        OtelLayer {
+            propagator: Some(Arc::new(propagator)),
+        }
+    }
+}
+
+impl<S> Layer<S> for OtelLayer {
+    type Service = OtelService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OtelService {
+            inner,
+            propagator: self.propagator.clone(),
+        }
+    }
+}
+
+/// Runs every request inside a `Server` span continuing the caller's trace.
+///
+/// The span is named `{method} {route}` and records `http.method`, `http.route` and, once the
+/// response is ready, `http.status_code`, from which 5xx responses get an Error status (see
+/// `OpenTelemetrySdkBuilder::with_http_status_mapping`). Errors from the inner service set it too.
+/// Baggage and W3C trace state sent by the caller are carried over.
+#[derive(Clone)]
+pub struct OtelService<S> {
+    inner: S,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+}
+
+impl<S> OtelService<S> {
+    fn request_span<B>(&self, request: &Request<B>) -> Span {
+        let headers = request.headers();
+        let extracted = self
+            .propagator
+            .clone()
+            .or_else(default_propagator)
+            .and_then(|propagator| propagator.extract_checked(headers));
+        let span = {
+            let remote_parent = extracted.as_ref().map(|extracted| extracted.context);
+            let _guard = remote_parent.map(|context| RemoteSpanContext::from(context).attach());
+            let method = request.method().as_str();
+            let route = route(request);
+            info_span!(
+                parent: None,
+                "HTTP request",
+                otel.name = format!("{method} {route}").as_str(),
+                otel.kind = "server",
+                http.method = method,
+                http.route = route,
+                http.status_code = field::Empty,
+            )
+        };
+        apply_extracted(&span, extracted, headers);
+        span
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for OtelService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: fmt::Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let span = self.request_span(&request);
+        let inner = span.in_scope(|| self.inner.call(request));
+        ResponseFuture { inner, span }
+    }
+}
+
+// The route pattern axum matched the request against, e.g. `/users/:id`, falling back to the
+// request's path.
+fn route<B>(request: &Request<B>) -> &str {
+    #[cfg(feature = "axum")]
+    if let Some(route) = request.extensions().get::<::axum::extract::MatchedPath>() {
+        return route.as_str();
+    }
+    request.uri().path()
+}
+
+pin_project! {
+    /// Response future of [`OtelService`], closing the request span once it resolves.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: fmt::Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = {
+            let _enter = this.span.enter();
+            ready!(this.inner.poll(cx))
+        };
+        match &result {
+            // The SDK derives the span status from the code when the span closes.
+            Ok(response) => {
+                this.span
+                    .record(HTTP_STATUS_CODE, response.status().as_u16() as i64);
+            }
+            Err(err) => this.span.set_status(SpanStatus::Error {
+                description: err.to_string(),
+            }),
+        }
+        // Close the span now rather than whenever the future is dropped.
+        drop(std::mem::replace(this.span, Span::none()));
+        Poll::Ready(result)
+    }
+}
@@ -1,19 +1,85 @@
-use std::{cell::RefCell, collections::HashMap, time::SystemTime};
-use std::fmt::format;
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt,
+    marker::PhantomData,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use rand::{rngs, Rng, SeedableRng};
-use tracing::{field::Visit, span, Event, Span};
-use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer, Registry};
+use rand::Rng;
+use tracing::{field::Visit, span, subscriber::Interest, Dispatch, Event, Level, Metadata, Span};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{Extensions, ExtensionsMut, LookupSpan, SpanRef},
+    Layer,
+};
+
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "otlp")]
+use crate::exporter::OtlpGrpcExporter;
+#[cfg(feature = "otlp")]
+use crate::processor::{BatchConfig, BatchSpanProcessor};
+use crate::middleware::HTTP_STATUS_CODE;
+use crate::id_generator::{IdGenerator, RandomIdGenerator};
+use crate::processor::SpanProcessor;
+use crate::propagator::{
+    BaggagePropagator, Extracted, Extractor, Injector, TextMapPropagator,
+    W3CTraceContextPropagator, CONTEXT_CONFLICT,
+};
+#[allow(deprecated)]
+pub use crate::sampler::OTelSampler;
+pub use crate::sampler::{AlwaysOff, AlwaysOn, SamplingContext, ShouldSample};
+use crate::sampler::{sampler_from_env, Decision, DecisionCache, SamplingResult};
 
 thread_local! {
-    static CURRENT_RNG: RefCell<rngs::SmallRng> = RefCell::new(rngs::SmallRng::from_entropy());
+    // Remote context attached by a `RemoteContextGuard`, used as the parent of root spans.
+    static CURRENT_REMOTE_CONTEXT: RefCell<Option<RemoteSpanContext>> = const { RefCell::new(None) };
 }
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, Default)]
-pub struct TraceId(u128);
+/// Displayed and parsed as 32 lowercase hex digits, the way it appears in `traceparent`.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Default)]
+pub struct TraceId(pub(crate) u128);
 
+/// Displayed and parsed as 16 lowercase hex digits.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Default)]
+pub struct SpanId(pub(crate) u64);
+
+/// The trace flags byte carried alongside a span context.
 #[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, Default)]
-pub struct SpanId(u64);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct TraceFlags(pub(crate) u8);
+
+impl TraceFlags {
+    pub const SAMPLED: TraceFlags = TraceFlags(0x01);
+
+    pub fn new(flags: u8) -> TraceFlags {
+        TraceFlags(flags)
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.0 & Self::SAMPLED.0 == Self::SAMPLED.0
+    }
+
+    /// These flags with the sampled bit set or cleared, leaving the other bits as they are.
+    pub fn with_sampled(self, sampled: bool) -> TraceFlags {
+        if sampled {
+            TraceFlags(self.0 | Self::SAMPLED.0)
+        } else {
+            TraceFlags(self.0 & !Self::SAMPLED.0)
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+}
 
 impl From<u128> for TraceId {
     fn from(value: u128) -> Self {
@@ -27,275 +93,3258 @@ impl From<u64> for SpanId {
     }
 }
 
-#[derive(Debug)]
-pub struct OTelSpan {
-    pub name: String,
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl fmt::Debug for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TraceId({self})")
+    }
+}
+
+impl FromStr for TraceId {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_hex_id(value, 32, "trace id").map(TraceId)
+    }
+}
+
+impl fmt::Display for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl fmt::Debug for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SpanId({self})")
+    }
+}
+
+impl FromStr for SpanId {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_hex_id(value, 16, "span id").map(|id| SpanId(id as u64))
+    }
+}
+
+// Parses an id of exactly `len` lowercase hex digits. Unlike in headers, a zero id is allowed.
+fn parse_hex_id(value: &str, len: usize, field: &'static str) -> Result<u128, ParseError> {
+    if value.len() != len {
+        return Err(ParseError::InvalidLength {
+            field,
+            expected: len,
+        });
+    }
+    if !value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(ParseError::InvalidHex { field });
+    }
+    u128::from_str_radix(value, 16).map_err(|_| ParseError::InvalidHex { field })
+}
+
+/// The portion of a span's identity that is propagated to other processes.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, Default)]
+pub struct SpanContext {
     pub trace_id: TraceId,
     pub span_id: SpanId,
     pub parent_span_id: Option<SpanId>,
-    pub start_time: SystemTime,
-    pub end_time: SystemTime,
-    pub attributes: HashMap<String, String>,
-    pub is_recording: bool,
+    pub flags: TraceFlags,
+    /// Whether the context was received from another process rather than created locally.
+    pub is_remote: bool,
 }
 
-impl OTelSpan {
-    pub fn new(
-        name: String,
-        trace_id: TraceId,
-        parent_span_id: Option<SpanId>,
-        is_recording: bool,
-    ) -> OTelSpan {
-        OTelSpan {
-            name,
-            trace_id: trace_id,
-            span_id: CURRENT_RNG.with(|rng| SpanId::from(rng.borrow_mut().gen::<u64>())),
-            parent_span_id,
-            start_time: SystemTime::now(),
-            end_time: SystemTime::now(),
-            attributes: HashMap::new(),
-            is_recording,
+impl SpanContext {
+    pub fn new(trace_id: TraceId, span_id: SpanId, flags: TraceFlags) -> SpanContext {
+        SpanContext {
+            trace_id,
+            span_id,
+            parent_span_id: None,
+            flags,
+            is_remote: false,
+        }
+    }
+
+    /// A context extracted from another process.
+    pub fn new_remote(trace_id: TraceId, span_id: SpanId, flags: TraceFlags) -> SpanContext {
+        SpanContext {
+            is_remote: true,
+            ..SpanContext::new(trace_id, span_id, flags)
         }
     }
+
+    /// Whether both ids are non-zero. An invalid context must not be used as a parent.
+    pub fn is_valid(&self) -> bool {
+        self.trace_id.0 != 0 && self.span_id.0 != 0
+    }
+
+    pub fn with_parent_span_id(mut self, parent_span_id: SpanId) -> Self {
+        self.parent_span_id = Some(parent_span_id);
+        self
+    }
 }
 
-impl Visit for OTelSpan {
-    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-        self.attributes
-            .insert(field.name().to_string(), value.to_string());
+/// The context of a span living in another process, e.g. extracted from request headers.
+///
+/// A local span marked with `OtelSpanExt::set_remote_parent` stands in for it: it is never
+/// exported, must not be entered and receives no events, but spans created with it as their
+/// parent continue the remote trace.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, Default)]
+pub struct RemoteSpanContext {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub flags: TraceFlags,
+}
+
+impl RemoteSpanContext {
+    pub fn new(trace_id: TraceId, span_id: SpanId, flags: TraceFlags) -> RemoteSpanContext {
+        RemoteSpanContext {
+            trace_id,
+            span_id,
+            flags,
+        }
     }
 
-    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        self.attributes
-            .insert(field.name().to_string(), format!("{value:?}"));
+    /// Shorthand for `RemoteContextGuard::new(self)`.
+    pub fn attach(self) -> RemoteContextGuard {
+        RemoteContextGuard::new(self)
     }
 }
 
-pub trait ShouldSample {
-    fn should_sample(&self, trace_id: &TraceId) -> bool;
+pub(crate) fn current_remote_context() -> Option<RemoteSpanContext> {
+    CURRENT_REMOTE_CONTEXT.with(|current| *current.borrow())
 }
 
-pub struct OTelSampler;
+/// Makes a remote context the parent of root spans created on this thread until dropped, at
+/// which point the previously attached context, if any, is restored.
+///
+/// Spans with a local parent are unaffected. The guard can't be sent to another thread, so
+/// don't hold it across an `.await`.
+#[must_use = "the remote context is detached as soon as the guard is dropped"]
+pub struct RemoteContextGuard {
+    previous: Option<RemoteSpanContext>,
+    _not_send: PhantomData<*const ()>,
+}
 
-impl ShouldSample for OTelSampler {
-    fn should_sample(&self, _trace_id: &TraceId) -> bool {
-        true
+impl RemoteContextGuard {
+    pub fn new(context: RemoteSpanContext) -> RemoteContextGuard {
+        let previous = CURRENT_REMOTE_CONTEXT.with(|current| current.replace(Some(context)));
+        RemoteContextGuard {
+            previous,
+            _not_send: PhantomData,
+        }
     }
 }
 
-#[derive(PartialEq, Eq)]
-pub enum EventExportMode {
-    LogRecord,
-    SpanEvent,
+impl Drop for RemoteContextGuard {
+    fn drop(&mut self) {
+        CURRENT_REMOTE_CONTEXT.with(|current| *current.borrow_mut() = self.previous.take());
+    }
 }
 
-pub struct OpenTelemetrySdk {
-    sampler: OTelSampler,
-    event_export_mode: EventExportMode,
+impl From<SpanContext> for RemoteSpanContext {
+    fn from(value: SpanContext) -> Self {
+        RemoteSpanContext::new(value.trace_id, value.span_id, value.flags)
+    }
 }
 
-impl Default for OpenTelemetrySdk {
-    fn default() -> Self {
-        Self::new()
+impl From<RemoteSpanContext> for SpanContext {
+    fn from(value: RemoteSpanContext) -> Self {
+        SpanContext::new_remote(value.trace_id, value.span_id, value.flags)
     }
 }
 
-impl OpenTelemetrySdk {
-    pub fn new() -> OpenTelemetrySdk {
-        OpenTelemetrySdk {
-            sampler: OTelSampler,
-            event_export_mode: EventExportMode::SpanEvent,
+/// A typed attribute value, mapped onto the matching OTLP/JSON type by exporters.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
+pub enum AttributeValue {
+    Bool(bool),
+    I64(i64),
+    F64(f64),
+    String(String),
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::Bool(value) => write!(f, "{value}"),
+            AttributeValue::I64(value) => write!(f, "{value}"),
+            AttributeValue::F64(value) => write!(f, "{value}"),
+            AttributeValue::String(value) => f.write_str(value),
         }
     }
 }
 
-impl<S> Layer<S> for OpenTelemetrySdk
-    where
-        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
-{
-    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).expect("Span expected here");
-        let mut extensions = span.extensions_mut();
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        AttributeValue::Bool(value)
+    }
+}
 
-        let parent_span = ctx.current_span();
-        if let Some(parent_id) = parent_span.id() {
-            // parent span exists.
-            // reuse traceid for the new span being created
-            // and store parent span id to the new span being created.
-            let parent_span = ctx.span(parent_id).expect("Parent span expected here");
-            let mut parent_extensions = parent_span.extensions_mut();
-            let parent_span = parent_extensions
-                .get_mut::<OTelSpan>()
-                .expect("Parent span data expected here");
-
-            let parent_trace_id = parent_span.trace_id;
-            let parent_span_id = parent_span.span_id;
-
-            // Overly simplified sampling logic for POC.
-            let sampling_result = self.sampler.should_sample(&parent_trace_id);
-            let mut span = OTelSpan::new(
-                attrs.metadata().name().to_string(),
-                parent_trace_id,
-                Some(parent_span_id),
-                sampling_result,
-            );
-            attrs.record(&mut span);
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        AttributeValue::I64(value)
+    }
+}
 
-            // store span in span extension.
-            extensions.insert(span);
-        } else {
-            // parent span does not exist.
-            // TODO: This is where remote parent's span context needs to be extracted, if any.
-            let trace_id_to_be_created_span =
-                CURRENT_RNG.with(|rng| TraceId::from(rng.borrow_mut().gen::<u128>()));
-            let sampling_result = self.sampler.should_sample(&trace_id_to_be_created_span);
-            let mut span = OTelSpan::new(
-                attrs.metadata().name().to_string(),
-                trace_id_to_be_created_span,
-                None,
-                sampling_result,
-            );
-            attrs.record(&mut span);
+impl From<u64> for AttributeValue {
+    // Values beyond i64::MAX have no OTLP integer representation, so they're kept as strings.
+    fn from(value: u64) -> Self {
+        i64::try_from(value)
+            .map_or_else(|_| AttributeValue::String(value.to_string()), AttributeValue::I64)
+    }
+}
+
+impl From<i128> for AttributeValue {
+    fn from(value: i128) -> Self {
+        i64::try_from(value)
+            .map_or_else(|_| AttributeValue::String(value.to_string()), AttributeValue::I64)
+    }
+}
+
+impl From<u128> for AttributeValue {
+    fn from(value: u128) -> Self {
+        i64::try_from(value)
+            .map_or_else(|_| AttributeValue::String(value.to_string()), AttributeValue::I64)
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> Self {
+        AttributeValue::F64(value)
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        AttributeValue::String(value)
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+/// Attributes describing the entity producing spans, e.g. `service.name`. Attached once per
+/// export rather than to every span.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resource(HashMap<String, AttributeValue>);
+
+pub const SERVICE_NAME: &str = "service.name";
 
-            // store span in span extension.
-            extensions.insert(span);
+impl Default for Resource {
+    /// A resource holding only the default `service.name`.
+    fn default() -> Self {
+        Resource::empty().with_default_service_name()
+    }
+}
+
+impl Resource {
+    pub fn new(attributes: impl IntoIterator<Item = (String, AttributeValue)>) -> Resource {
+        Resource(attributes.into_iter().collect())
+    }
+
+    pub fn empty() -> Resource {
+        Resource(HashMap::new())
+    }
+
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<AttributeValue>,
+    ) -> Self {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&AttributeValue> {
+        self.0.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AttributeValue)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Falls back to the executable's name when no `service.name` was configured.
+    fn with_default_service_name(mut self) -> Self {
+        self.0.entry(SERVICE_NAME.to_string()).or_insert_with(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|path| path.file_stem().map(|name| name.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "unknown_service".to_string())
+                .into()
+        });
+        self
+    }
+}
+
+impl From<HashMap<String, AttributeValue>> for Resource {
+    fn from(value: HashMap<String, AttributeValue>) -> Self {
+        Resource(value)
+    }
+}
+
+/// Key/value pairs propagated alongside the trace context, stored in the span's extensions
+/// next to its `OTelSpan` and inherited by child spans.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Baggage(HashMap<String, String>);
+
+impl Baggage {
+    pub fn new() -> Baggage {
+        Baggage::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<HashMap<String, String>> for Baggage {
+    fn from(value: HashMap<String, String>) -> Self {
+        Baggage(value)
+    }
+}
+
+/// Vendor-specific key/value pairs carried in the W3C `tracestate` header, kept in order and
+/// passed on untouched. Members that break the W3C rules are dropped rather than rejected.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TraceState(Vec<(String, String)>);
+
+impl TraceState {
+    /// The most members a `tracestate` header may carry.
+    pub const MAX_ENTRIES: usize = 32;
+
+    pub fn new() -> TraceState {
+        TraceState::default()
+    }
+
+    /// Parses a `tracestate` header value, skipping invalid and duplicate members and keeping
+    /// at most `MAX_ENTRIES`.
+    pub fn from_header(value: &str) -> TraceState {
+        let mut trace_state = TraceState::new();
+        for member in value.split(',') {
+            let member = member.trim();
+            if trace_state.0.len() >= Self::MAX_ENTRIES {
+                break;
+            }
+            let Some((key, value)) = member.split_once('=') else {
+                continue;
+            };
+            if is_valid_trace_state_key(key)
+                && is_valid_trace_state_value(value)
+                && trace_state.get(key).is_none()
+            {
+                trace_state.0.push((key.to_string(), value.to_string()));
+            }
         }
+        trace_state
+    }
 
-        // This is where SpanProcessors' OnBegin will be called.
+    /// The `tracestate` header value, members separated by `,`.
+    pub fn header_value(&self) -> String {
+        self.iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(",")
     }
 
-    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).expect("Span expected here");
-        let mut extensions = span.extensions_mut();
-        let mut span = extensions.remove::<OTelSpan>().expect("Span expected here");
-        span.end_time = SystemTime::now();
-        println!("Span {:?}", span);
-        if span.is_recording {
-            // This is where SpanProcessors' OnEnd will be called.
-            // SpanProcessors can pass Spans to exporter(s) which can export in OTLP format/others.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Adds or updates a member, moving it to the front as W3C requires of the vendor that
+    /// modified it. Returns `false`, leaving the state unchanged, if the key or value is invalid.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> bool {
+        let (key, value) = (key.into(), value.into());
+        if !is_valid_trace_state_key(&key) || !is_valid_trace_state_value(&value) {
+            return false;
         }
+        self.0.retain(|(existing, _)| *existing != key);
+        self.0.insert(0, (key, value));
+        self.0.truncate(Self::MAX_ENTRIES);
+        true
     }
 
-    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(span).expect("Span expected here");
-        let mut extensions = span.extensions_mut();
-        let existing_span = extensions
-            .get_mut::<OTelSpan>()
-            .expect("Span expected here");
-        values.record(existing_span);
+    /// Removes a member, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.0.iter().position(|(existing, _)| existing == key)?;
+        Some(self.0.remove(index).1)
     }
 
-    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        if event.metadata().is_event() {
-            if let Some(span) = event.parent().and_then(|id| ctx.span(id)).or_else(|| {
-                event
-                    .is_contextual()
-                    .then(|| ctx.lookup_current())
-                    .flatten()
-            }) {
-                let mut extensions = span.extensions_mut();
-                let existing_span = extensions
-                    .get_mut::<OTelSpan>()
-                    .expect("Span expected here");
-
-                if self.event_export_mode == EventExportMode::SpanEvent {
-                    if existing_span.is_recording {
-                        // Add SpanEvent to the Span.
-                        println!("SpanEvent {} for Span with SpanId {}", event.metadata().name(), existing_span.span_id.0);
-                    }
-                } else {
-                    // Emit LogRecord using the Event, similar to how opentelemetry-tracing-appender works today.
-                    println!("LogRecord {} for Span with SpanId {}", event.metadata().name(), existing_span.span_id.0);
-                }
-            }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// `simple-key` or `tenant-id@system-id`: lowercase letters, digits and `_-*/`, starting with a
+// letter (a letter or digit for tenant ids).
+fn is_valid_trace_state_key(key: &str) -> bool {
+    fn is_key_char(b: u8) -> bool {
+        matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'*' | b'/')
+    }
+    fn is_valid_part(part: &str, max_len: usize, digit_start: bool) -> bool {
+        let bytes = part.as_bytes();
+        !bytes.is_empty()
+            && bytes.len() <= max_len
+            && (bytes[0].is_ascii_lowercase() || (digit_start && bytes[0].is_ascii_digit()))
+            && bytes.iter().copied().all(is_key_char)
+    }
+    match key.split_once('@') {
+        Some((tenant, system)) => {
+            is_valid_part(tenant, 241, true) && is_valid_part(system, 14, false)
         }
+        None => is_valid_part(key, 256, false),
     }
 }
 
-pub trait OtelSpanExt {
-    fn set_parent(&self, jaeger_format: String);
+// Up to 256 printable ASCII characters other than `,` and `=`, not ending in a space.
+fn is_valid_trace_state_value(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 256
+        && !value.ends_with(' ')
+        && value
+            .bytes()
+            .all(|b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+}
 
-    fn tract_id(&self) -> TraceId;
+/// The role a span plays in a trace, used by backends to build service graphs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SpanKind {
+    Client,
+    Server,
+    Producer,
+    Consumer,
+    #[default]
+    Internal,
+}
 
-    fn span_id(&self) -> SpanId;
+impl SpanKind {
+    // Parses the value of the `otel.kind` field, ignoring case.
+    fn parse(value: &str) -> Option<SpanKind> {
+        match value.to_ascii_lowercase().as_str() {
+            "client" => Some(SpanKind::Client),
+            "server" => Some(SpanKind::Server),
+            "producer" => Some(SpanKind::Producer),
+            "consumer" => Some(SpanKind::Consumer),
+            "internal" => Some(SpanKind::Internal),
+            _ => None,
+        }
+    }
+}
 
-    fn parent_span_id(&self) -> SpanId;
+/// Tracing field that sets the span kind instead of being recorded as an attribute. Works from
+/// `#[instrument(fields(otel.kind = "client"))]` too, as `tracing` keeps dotted names as they are.
+pub const SPAN_KIND_FIELD: &str = "otel.kind";
 
-    fn extract_jaeger_propagation(&self) -> String;
+/// Tracing field that overrides the span name, for names only known at runtime.
+pub const SPAN_NAME_FIELD: &str = "otel.name";
 
-    fn with_otel_span<F, T>(&self, f: F) -> T
-        where F: Fn(&OTelSpan) -> Option<T>,
-              T: Default;
-}
+/// Tracing field that records the span whatever the sampler decides when it is `true`, e.g. to
+/// debug a single request. Such spans get the `sampling.forced` attribute instead.
+pub const FORCE_SAMPLE_FIELD: &str = "otel.force_sample";
+pub const SAMPLING_FORCED: &str = "sampling.forced";
 
-impl OtelSpanExt for Span {
-    fn set_parent(&self, jaeger_format: String) {
-        self.with_subscriber(move |(id, subscriber)| {
-            if let Some(registry) = subscriber.downcast_ref::<Registry>() {
-                let span = registry
-                    .span(id)
-                    .expect("registry should have a span for the current ID");
-
-                let mut extensions = span.extensions_mut();
-                if let Some(otel_span) = extensions.get_mut::<OTelSpan>() {
-                    let (trace_id, span_id) = parse_jaeger_trace_id(&jaeger_format);
-                    otel_span.trace_id = trace_id;
-                    otel_span.parent_span_id = Some(span_id);
-                }
+// Whether the span was created with `otel.force_sample = true`.
+fn force_sample(attrs: &span::Attributes<'_>) -> bool {
+    struct ForceSample(bool);
+
+    impl Visit for ForceSample {
+        fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+            if field.name() == FORCE_SAMPLE_FIELD {
+                self.0 = value;
             }
-        });
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn fmt::Debug) {}
     }
 
-    fn tract_id(&self) -> TraceId {
-        self.with_otel_span(|otel_span| Some(otel_span.trace_id))
+    if attrs.metadata().fields().field(FORCE_SAMPLE_FIELD).is_none() {
+        return false;
     }
+    let mut visitor = ForceSample(false);
+    attrs.record(&mut visitor);
+    visitor.0
+}
 
-    fn span_id(&self) -> SpanId {
-        self.with_otel_span(|otel_span| Some(otel_span.span_id))
+/// Whether the operation a span represents succeeded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "code", rename_all = "lowercase")
+)]
+pub enum SpanStatus {
+    #[default]
+    Unset,
+    Ok,
+    Error { description: String },
+}
+
+/// Tracing field setting the span status: `ok`, `error` or `unset`. An `#[instrument]`ed function
+/// can declare it `Empty` in `fields(...)` and `record` it on `Span::current()` once it knows.
+pub const STATUS_CODE_FIELD: &str = "otel.status_code";
+/// Tracing field holding the description of an error status.
+pub const STATUS_MESSAGE_FIELD: &str = "otel.status_message";
+
+/// A reference to a span outside this span's parent chain, e.g. one of several upstream requests.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpanLink {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+impl SpanLink {
+    pub fn new(trace_id: TraceId, span_id: SpanId) -> SpanLink {
+        SpanLink {
+            trace_id,
+            span_id,
+            attributes: HashMap::new(),
+        }
     }
 
-    fn parent_span_id(&self) -> SpanId {
-        self.with_otel_span(|otel_span| otel_span.parent_span_id)
+    /// Builds a link from a W3C `traceparent` or Jaeger `uber-trace-id` header value.
+    /// Returns `None` if neither yields a valid trace and span id.
+    pub fn from_header(header: &str) -> Option<SpanLink> {
+        let (trace_id, span_id, _) = W3CTraceContextPropagator::new()
+            .parse_traceparent(header)
+            .or_else(|| parse_jaeger_trace_id(header).ok())?;
+        Some(SpanLink::new(trace_id, span_id))
     }
 
-    // Get the span, extract trace id, span id, parent span id and sampling decision
-    // build a jaeger propagation header.
-    fn extract_jaeger_propagation(&self) -> String {
-        return format!("{}:{}:{}:{}", self.tract_id().0, self.span_id().0, self.parent_span_id().0, 1);
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<AttributeValue>,
+    ) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
     }
+}
 
-    fn with_otel_span<F, T>(&self, f: F) -> T
-        where F: Fn(&OTelSpan) -> Option<T>,
-              T: Default {
-        let mut result: Option<T> = None;
-        self.with_subscriber(|(id, subscriber)| {
-            if let Some(registry) = subscriber.downcast_ref::<Registry>() {
-                let span = registry
-                    .span(id)
-                    .expect("registry should have a span for the current ID");
-
-                let mut extensions = span.extensions_mut();
-                if let Some(otel_span) = extensions.get_mut::<OTelSpan>() {
-                    result = f(otel_span);
-                }
-            }
-        });
-        result.unwrap_or_default()
+/// What `span_with_links!` accepts as links: header values, as parsed by
+/// `SpanLink::from_header`, and span contexts.
+pub trait IntoSpanLink {
+    /// `None` for a header that doesn't parse or a context with a zero id.
+    fn into_span_link(self) -> Option<SpanLink>;
+}
+
+impl IntoSpanLink for SpanLink {
+    fn into_span_link(self) -> Option<SpanLink> {
+        Some(self)
     }
 }
 
+impl IntoSpanLink for &str {
+    fn into_span_link(self) -> Option<SpanLink> {
+        SpanLink::from_header(self)
+    }
+}
 
-fn parse_jaeger_trace_id(header_value: &str) -> (TraceId, SpanId) {
-    let parts: Vec<&str> = header_value.split(':').collect();
-    if parts.len() != 4 {
-        return (TraceId::default(), SpanId::default());
+impl IntoSpanLink for String {
+    fn into_span_link(self) -> Option<SpanLink> {
+        SpanLink::from_header(&self)
     }
+}
 
-    let trace_id_str = parts[0];
-    let span_id_str = parts[1];
+impl IntoSpanLink for &String {
+    fn into_span_link(self) -> Option<SpanLink> {
+        SpanLink::from_header(self)
+    }
+}
 
-    let trace_id = u128::from_str_radix(trace_id_str, 10).unwrap_or(0);
-    let span_id = u64::from_str_radix(span_id_str, 10).unwrap_or(0);
+impl IntoSpanLink for SpanContext {
+    fn into_span_link(self) -> Option<SpanLink> {
+        self.is_valid().then(|| SpanLink::new(self.trace_id, self.span_id))
+    }
+}
 
-    (TraceId(trace_id), SpanId(span_id))
-}
\ No newline at end of file
+impl IntoSpanLink for RemoteSpanContext {
+    fn into_span_link(self) -> Option<SpanLink> {
+        SpanContext::from(self).into_span_link()
+    }
+}
+
+/// Starts a `Consumer` span named `name` for processing messages from several producers, e.g.
+/// a batch drawn from a queue. The span has no parent, so it starts its own trace unless a
+/// `RemoteContextGuard` is active, and links to each of `contexts`, the producers' spans.
+/// Its target is this module, which filters must enable for it to be recorded.
+pub fn new_span_with_contexts(name: &str, contexts: Vec<RemoteSpanContext>) -> Span {
+    let span = tracing::info_span!(
+        parent: None,
+        "fan_in",
+        otel.name = name,
+        otel.kind = "consumer",
+        messaging.batch.message_count = contexts.len(),
+    );
+    for (index, context) in contexts.into_iter().enumerate() {
+        if let Some(link) = context.into_span_link() {
+            span.add_link(link.with_attribute(MESSAGING_MESSAGE_INDEX, index as i64));
+        }
+    }
+    span
+}
+
+/// Link attribute with the position, in the batch, of the message a fan-in span links to.
+pub const MESSAGING_MESSAGE_INDEX: &str = "messaging.batch.message_index";
+
+/// Caps on how much a single span may hold. Anything beyond a limit is dropped and counted in
+/// the span's `dropped_*_count` fields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanLimits {
+    /// Applies to the span's attributes and to the attributes of each of its events.
+    pub max_attributes: usize,
+    pub max_events: usize,
+    /// Once a span holds `max_events` events, have each further event replace a random one
+    /// (reservoir sampling) rather than be dropped, so the events kept are a uniform sample of
+    /// all the span's events instead of its first ones. Either way, `dropped_events_count`
+    /// counts the events not kept.
+    pub sample_events: bool,
+    pub max_links: usize,
+    /// String values longer than this many characters are cut down to it and end in
+    /// [`TRUNCATION_MARKER`]. Other values are never truncated.
+    pub max_attribute_value_len: usize,
+}
+
+impl Default for SpanLimits {
+    fn default() -> Self {
+        SpanLimits {
+            max_attributes: 128,
+            max_events: 128,
+            sample_events: false,
+            max_links: 128,
+            max_attribute_value_len: usize::MAX,
+        }
+    }
+}
+
+/// Appended to string attribute values cut down to `SpanLimits::max_attribute_value_len`.
+pub const TRUNCATION_MARKER: &str = "...[truncated]";
+
+impl SpanLimits {
+    // Inserts an attribute unless the map is full, returning whether it was kept. Overwriting
+    // an existing key is always allowed. Counts values it truncated in `truncated`.
+    fn insert_attribute(
+        &self,
+        attributes: &mut HashMap<String, AttributeValue>,
+        key: String,
+        mut value: AttributeValue,
+        truncated: &mut u32,
+    ) -> bool {
+        if attributes.len() >= self.max_attributes && !attributes.contains_key(&key) {
+            return false;
+        }
+        if let AttributeValue::String(value) = &mut value {
+            if let Some((end, _)) = value.char_indices().nth(self.max_attribute_value_len) {
+                value.truncate(end);
+                value.push_str(TRUNCATION_MARKER);
+                *truncated += 1;
+            }
+        }
+        attributes.insert(key, value);
+        true
+    }
+}
+
+/// A timestamped `tracing` event recorded on the span it occurred in.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpanEvent {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "serde_impls::unix_nanos"))]
+    pub timestamp: SystemTime,
+    pub attributes: HashMap<String, AttributeValue>,
+    pub dropped_attributes_count: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    limits: SpanLimits,
+    // Reported through `SdkStats::attributes_truncated` once the span closes.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    truncated_attributes: u32,
+}
+
+impl SpanEvent {
+    pub fn new(name: impl Into<String>) -> SpanEvent {
+        SpanEvent {
+            name: name.into(),
+            timestamp: SystemTime::now(),
+            attributes: HashMap::new(),
+            dropped_attributes_count: 0,
+            limits: SpanLimits::default(),
+            truncated_attributes: 0,
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        if !self
+            .limits
+            .insert_attribute(
+                &mut self.attributes,
+                key.into(),
+                value.into(),
+                &mut self.truncated_attributes,
+            )
+        {
+            self.dropped_attributes_count += 1;
+        }
+    }
+}
+
+impl Visit for SpanEvent {
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.set_attribute(field.name(), value);
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.set_attribute(field.name(), format!("{value:?}"));
+    }
+}
+
+/// A span as the SDK records it while it's open, stored in the `tracing` span's extensions.
+/// Processors and exporters get an [`OTelSpanData`] snapshot of it instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OTelSpan {
+    pub name: String,
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id: Option<SpanId>,
+    /// Whether the parent was received from another process, e.g. through `set_parent` or a
+    /// propagator, rather than created locally.
+    pub parent_is_remote: bool,
+    /// The instrumentation scope, the `tracing` target the span was created under by default.
+    pub scope_name: String,
+    pub scope_version: Option<String>,
+    pub start_time: SystemTime,
+    /// `None` until the span is closed.
+    pub end_time: Option<SystemTime>,
+    pub kind: SpanKind,
+    pub status: SpanStatus,
+    pub links: Vec<SpanLink>,
+    pub events: Vec<SpanEvent>,
+    pub attributes: HashMap<String, AttributeValue>,
+    pub dropped_attributes_count: u32,
+    pub dropped_events_count: u32,
+    pub dropped_links_count: u32,
+    // Only recording spans are exported, so that's what deserialized spans are.
+    pub is_recording: bool,
+    /// Propagated alongside the ids, with the sampled bit following `is_recording`.
+    pub flags: TraceFlags,
+    /// Inherited from the parent and passed on to downstream services.
+    pub trace_state: TraceState,
+    // Number of local spans from the trace root to this span, the root being 1.
+    pub depth: usize,
+    limits: SpanLimits,
+    truncated_attributes: u32,
+    // Set when the span is first entered.
+    queue_time: Option<Duration>,
+    child_count: u32,
+    // Set by `set_parent_context` for a parent that was an open span of this SDK, so
+    // `with_orphan_detection` checks it like a local parent.
+    parent_is_local: bool,
+    // Keys of the attributes the sampler set, which fields recorded later don't overwrite.
+    sampler_attributes: Vec<String>,
+}
+
+/// A snapshot of an `OTelSpan`, as handed to processors and exporters. Changes to the live span
+/// after it was taken don't show up in it.
+///
+/// With the `serde` feature it serializes to the layout the stdout exporter writes: ids as hex
+/// strings, times as Unix nanoseconds and an `end_time` of 0 for spans not closed yet.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OTelSpanData {
+    pub name: String,
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id: Option<SpanId>,
+    /// Whether the parent was received from another process, e.g. through `set_parent` or a
+    /// propagator, rather than created locally.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parent_is_remote: bool,
+    /// The instrumentation scope, the `tracing` target the span was created under by default.
+    pub scope_name: String,
+    pub scope_version: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_impls::unix_nanos"))]
+    pub start_time: SystemTime,
+    /// `None` until the span is closed.
+    #[cfg_attr(feature = "serde", serde(with = "serde_impls::unix_nanos_or_zero"))]
+    pub end_time: Option<SystemTime>,
+    pub kind: SpanKind,
+    pub status: SpanStatus,
+    pub links: Vec<SpanLink>,
+    pub events: Vec<SpanEvent>,
+    pub attributes: HashMap<String, AttributeValue>,
+    pub dropped_attributes_count: u32,
+    pub dropped_events_count: u32,
+    pub dropped_links_count: u32,
+    // Only recording spans are exported, so that's what deserialized spans are.
+    #[cfg_attr(feature = "serde", serde(skip, default = "serde_impls::recording"))]
+    pub is_recording: bool,
+    /// Propagated alongside the ids, with the sampled bit following `is_recording`.
+    pub flags: TraceFlags,
+    /// Inherited from the parent and passed on to downstream services.
+    pub trace_state: TraceState,
+    // Number of local spans from the trace root to this span, the root being 1.
+    pub depth: usize,
+}
+
+impl OTelSpanData {
+    /// See `OTelSpan::duration`.
+    pub fn duration(&self) -> Duration {
+        self.end_time
+            .and_then(|end_time| end_time.duration_since(self.start_time).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn span_context(&self) -> SpanContext {
+        SpanContext {
+            parent_span_id: self.parent_span_id,
+            ..SpanContext::new(self.trace_id, self.span_id, self.flags)
+        }
+    }
+}
+
+impl From<OTelSpan> for OTelSpanData {
+    fn from(span: OTelSpan) -> Self {
+        OTelSpanData {
+            name: span.name,
+            trace_id: span.trace_id,
+            span_id: span.span_id,
+            parent_span_id: span.parent_span_id,
+            parent_is_remote: span.parent_is_remote,
+            scope_name: span.scope_name,
+            scope_version: span.scope_version,
+            start_time: span.start_time,
+            end_time: span.end_time,
+            kind: span.kind,
+            status: span.status,
+            links: span.links,
+            events: span.events,
+            attributes: span.attributes,
+            dropped_attributes_count: span.dropped_attributes_count,
+            dropped_events_count: span.dropped_events_count,
+            dropped_links_count: span.dropped_links_count,
+            is_recording: span.is_recording,
+            flags: span.flags,
+            trace_state: span.trace_state,
+            depth: span.depth,
+        }
+    }
+}
+
+impl From<&OTelSpan> for OTelSpanData {
+    fn from(span: &OTelSpan) -> Self {
+        span.clone().into()
+    }
+}
+
+impl OTelSpan {
+    pub fn new(
+        name: String,
+        trace_id: TraceId,
+        span_id: SpanId,
+        parent_span_id: Option<SpanId>,
+        is_recording: bool,
+    ) -> OTelSpan {
+        OTelSpan {
+            name,
+            trace_id,
+            span_id,
+            parent_span_id,
+            parent_is_remote: false,
+            scope_name: String::new(),
+            scope_version: None,
+            start_time: SystemTime::now(),
+            end_time: None,
+            kind: SpanKind::default(),
+            status: SpanStatus::default(),
+            links: Vec::new(),
+            events: Vec::new(),
+            attributes: HashMap::new(),
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            dropped_links_count: 0,
+            is_recording,
+            flags: TraceFlags::default().with_sampled(is_recording),
+            trace_state: TraceState::default(),
+            depth: 1,
+            limits: SpanLimits::default(),
+            truncated_attributes: 0,
+            queue_time: None,
+            child_count: 0,
+            parent_is_local: false,
+            sampler_attributes: Vec::new(),
+        }
+    }
+
+    /// Time between the span's start and the first time it was entered, e.g. how long an
+    /// instrumented future waited to be polled. Zero until it is entered.
+    pub fn queue_time(&self) -> Duration {
+        self.queue_time.unwrap_or_default()
+    }
+
+    /// Number of spans created with this span as their parent so far.
+    pub fn child_count(&self) -> u32 {
+        self.child_count
+    }
+
+    /// Time between the span's start and end, zero while it is still open. The SDK measures
+    /// it on the clock's monotonic time and sets `end_time` to match, so wall clock adjustments
+    /// while the span was open don't affect it. `OtelSpanExt::elapsed` uses the same time.
+    pub fn duration(&self) -> Duration {
+        self.end_time
+            .and_then(|end_time| end_time.duration_since(self.start_time).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn span_context(&self) -> SpanContext {
+        SpanContext {
+            parent_span_id: self.parent_span_id,
+            ..SpanContext::new(self.trace_id, self.span_id, self.flags)
+        }
+    }
+
+    // String values truncated on the span and its events.
+    fn truncated_attributes(&self) -> u64 {
+        let events: u64 = self
+            .events
+            .iter()
+            .map(|event| u64::from(event.truncated_attributes))
+            .sum();
+        u64::from(self.truncated_attributes) + events
+    }
+
+    // Attributes `SpanLimits` dropped from the span and its events.
+    fn dropped_attributes(&self) -> u64 {
+        let events: u64 = self
+            .events
+            .iter()
+            .map(|event| u64::from(event.dropped_attributes_count))
+            .sum();
+        u64::from(self.dropped_attributes_count) + events
+    }
+
+    // Records the sampling decision, keeping the sampled flag in step with `is_recording`.
+    fn set_sampled(&mut self, sampled: bool) {
+        self.is_recording = sampled;
+        self.flags = self.flags.with_sampled(sampled);
+    }
+
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        if !self
+            .limits
+            .insert_attribute(
+                &mut self.attributes,
+                key.into(),
+                value.into(),
+                &mut self.truncated_attributes,
+            )
+        {
+            self.dropped_attributes_count += 1;
+        }
+    }
+
+    pub fn add_event(&mut self, event: SpanEvent) {
+        if self.events.len() >= self.limits.max_events {
+            self.dropped_events_count += 1;
+            if self.limits.sample_events {
+                // Keep the event with probability `max_events / seen`, in place of a random one.
+                let seen = self.events.len() as u64 + u64::from(self.dropped_events_count);
+                let index = rand::thread_rng().gen_range(0..seen) as usize;
+                if index < self.events.len() {
+                    // Removed rather than overwritten, so the events stay in order.
+                    self.events.remove(index);
+                    self.events.push(event);
+                }
+            }
+            return;
+        }
+        self.events.push(event);
+    }
+
+    pub fn add_link(&mut self, link: SpanLink) {
+        if self.links.len() >= self.limits.max_links {
+            self.dropped_links_count += 1;
+            return;
+        }
+        self.links.push(link);
+    }
+
+    fn record_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        if !self.sampler_attributes.iter().any(|sampled| sampled == key) {
+            self.set_attribute(key, value);
+        }
+    }
+
+    // Span events created for this span must pick up its limits before recording fields.
+    fn new_event(&self, name: impl Into<String>, timestamp: SystemTime) -> SpanEvent {
+        let mut event = SpanEvent::new(name);
+        event.timestamp = timestamp;
+        event.limits = self.limits;
+        event
+    }
+}
+
+impl Visit for OTelSpan {
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        // Read by the sampling path; the span gets `sampling.forced` instead.
+        if field.name() == FORCE_SAMPLE_FIELD {
+            return;
+        }
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == SPAN_NAME_FIELD {
+            self.name = value.to_string();
+            return;
+        }
+        if field.name() == SPAN_KIND_FIELD {
+            if let Some(kind) = SpanKind::parse(value) {
+                self.kind = kind;
+                return;
+            }
+        }
+        if field.name() == STATUS_CODE_FIELD {
+            let status = match value.to_ascii_lowercase().as_str() {
+                "ok" => Some(SpanStatus::Ok),
+                "unset" => Some(SpanStatus::Unset),
+                // The message may have been recorded before the code.
+                "error" => Some(SpanStatus::Error {
+                    description: self
+                        .attributes
+                        .remove(STATUS_MESSAGE_FIELD)
+                        .map(|message| message.to_string())
+                        .unwrap_or_default(),
+                }),
+                _ => None,
+            };
+            if let Some(status) = status {
+                self.status = status;
+                return;
+            }
+        }
+        if field.name() == STATUS_MESSAGE_FIELD {
+            if let SpanStatus::Error { description } = &mut self.status {
+                *description = value.to_string();
+                return;
+            }
+        }
+        self.record_attribute(field.name(), value);
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        // `otel.name = %name` and the like arrive here rather than in `record_str`, and
+        // `otel.kind = ?kind` with a string `kind` arrives quoted.
+        if matches!(
+            field.name(),
+            SPAN_NAME_FIELD | SPAN_KIND_FIELD | STATUS_CODE_FIELD | STATUS_MESSAGE_FIELD
+        ) {
+            let value = format!("{value:?}");
+            let unquoted = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(&value);
+            self.record_str(field, unquoted);
+            return;
+        }
+        self.record_attribute(field.name(), format!("{value:?}"));
+    }
+}
+
+// Stored instead of an `OTelSpan` for spans the sampler dropped, keeping only what children
+// and outgoing requests need.
+struct NonRecordingSpan {
+    context: SpanContext,
+    depth: usize,
+    trace_state: TraceState,
+}
+
+// What a new span inherits from its parent.
+enum Parent {
+    Local {
+        context: SpanContext,
+        depth: usize,
+        trace_state: TraceState,
+    },
+    // A stand-in for a span in another process, or a context attached to the thread.
+    Remote {
+        context: RemoteSpanContext,
+        trace_state: TraceState,
+    },
+    None,
+}
+
+impl Parent {
+    // Reads the parent of a span being created, counting the new span as its child.
+    fn read(extensions: &mut ExtensionsMut<'_>) -> Parent {
+        if let Some(parent) = extensions.get_mut::<OTelSpan>() {
+            parent.child_count += 1;
+            return Parent::Local {
+                context: parent.span_context(),
+                depth: parent.depth,
+                trace_state: parent.trace_state.clone(),
+            };
+        }
+        if let Some(parent) = extensions.get_mut::<NonRecordingSpan>() {
+            return Parent::Local {
+                context: parent.context,
+                depth: parent.depth,
+                trace_state: parent.trace_state.clone(),
+            };
+        }
+        // A stand-in for an invalid context doesn't make a parent.
+        match extensions
+            .get_mut::<RemoteSpanContext>()
+            .map(|context| *context)
+            .filter(|context| SpanContext::from(*context).is_valid())
+        {
+            Some(context) => Parent::Remote {
+                context,
+                trace_state: extensions.get_mut::<TraceState>().cloned().unwrap_or_default(),
+            },
+            None => Parent::None,
+        }
+    }
+}
+
+// When a span started on the SDK clock's monotonic time, next to its `OTelSpan`.
+struct SpanStart(Instant);
+
+/// Attribute recording `OTelSpan::queue_time` in nanoseconds, for spans entered after a delay.
+pub const QUEUE_TIME_NS: &str = "queue_time_ns";
+/// Attribute recording `OTelSpan::child_count`, for spans with children.
+pub const CHILD_COUNT: &str = "child_count";
+/// Attribute set to `true` by `with_orphan_detection` on spans whose local parent wasn't open
+/// when they closed.
+pub const ORPHANED: &str = "otel.orphaned";
+
+// Context switches accumulated while a span is entered, stamped onto the span at close.
+#[derive(Default)]
+struct ContextSwitches {
+    entered: Option<(u64, u64)>,
+    measured: bool,
+    voluntary: u64,
+    involuntary: u64,
+}
+
+// Returns the (voluntary, involuntary) context switch counts of the calling thread.
+#[cfg(target_os = "linux")]
+fn thread_context_switches() -> Option<(u64, u64)> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: getrusage only writes into the rusage struct we hand it.
+    if unsafe { libc::getrusage(libc::RUSAGE_THREAD, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    Some((usage.ru_nvcsw as u64, usage.ru_nivcsw as u64))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_context_switches() -> Option<(u64, u64)> {
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventExportMode {
+    LogRecord,
+    SpanEvent,
+    /// Attach events to the span and also emit them as log records correlated with the span.
+    Both,
+    /// Ignore events, e.g. when they are logged by another layer.
+    None,
+}
+
+/// The OTel severity of a log record, by the `Level` of the event it was emitted for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Trace = 1,
+    Debug = 5,
+    Info = 9,
+    Warn = 13,
+    Error = 17,
+}
+
+impl Severity {
+    /// The severity number of the OTel log data model.
+    pub fn number(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::TRACE => Severity::Trace,
+            Level::DEBUG => Severity::Debug,
+            Level::INFO => Severity::Info,
+            Level::WARN => Severity::Warn,
+            Level::ERROR => Severity::Error,
+        }
+    }
+}
+
+pub struct OpenTelemetrySdk {
+    sampler: Box<dyn ShouldSample + Send + Sync>,
+    id_generator: Box<dyn IdGenerator + Send + Sync>,
+    clock: Box<dyn Clock + Send + Sync>,
+    event_export_mode: EventExportMode,
+    record_context_switches: bool,
+    record_exceptions: bool,
+    http_status_mapping: Arc<HttpStatusMapping>,
+    attribute_processors: Vec<Box<AttributeProcessor>>,
+    capture_code_location: bool,
+    capture_thread: bool,
+    span_level: bool,
+    name_from_attribute: Option<String>,
+    // Longest prefix first.
+    target_filters: Vec<(String, bool)>,
+    max_trace_depth: Option<usize>,
+    decision_cache: Option<DecisionCache>,
+    // Shared with `TracerProviderHandle`s, like the processors.
+    counters: Arc<SpanCounters>,
+    open_spans: Option<Arc<OpenSpans>>,
+    // Ids of the open spans, kept by `with_orphan_detection`.
+    live_span_ids: Option<Mutex<HashSet<SpanId>>>,
+    // Set in strict mode, shared with `TracerProviderHandle`s.
+    violations: Option<Arc<Mutex<Vec<Violation>>>>,
+    // Shared with `TracerProviderHandle`s, which also see processors registered later.
+    processors: Arc<RwLock<Vec<Box<dyn SpanProcessor + Send + Sync>>>>,
+    span_limits: SpanLimits,
+    resource: Resource,
+    propagator: Arc<dyn TextMapPropagator + Send + Sync>,
+    // Set once the SDK is layered onto a subscriber.
+    with_context: Option<WithContext>,
+}
+
+impl Default for OpenTelemetrySdk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenTelemetrySdk {
+    pub fn new() -> OpenTelemetrySdk {
+        OpenTelemetrySdk {
+            sampler: Box::new(AlwaysOn),
+            id_generator: Box::new(RandomIdGenerator),
+            clock: Box::new(SystemClock),
+            event_export_mode: EventExportMode::SpanEvent,
+            record_context_switches: false,
+            record_exceptions: false,
+            http_status_mapping: Arc::new(http_span_status),
+            attribute_processors: Vec::new(),
+            capture_code_location: false,
+            capture_thread: false,
+            span_level: false,
+            name_from_attribute: None,
+            target_filters: Vec::new(),
+            max_trace_depth: None,
+            decision_cache: None,
+            counters: Arc::default(),
+            open_spans: None,
+            live_span_ids: None,
+            violations: None,
+            processors: Arc::default(),
+            span_limits: SpanLimits::default(),
+            resource: Resource::default(),
+            propagator: Arc::new(W3CTraceContextPropagator::new()),
+            with_context: None,
+        }
+    }
+
+    /// Starts a builder configured from the spec's environment variables:
+    ///
+    /// - `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG` pick the sampler, e.g.
+    ///   `parentbased_traceidratio` with a ratio of `0.25`. Defaults to `parentbased_always_on`.
+    /// - `OTEL_SERVICE_NAME` sets `service.name`.
+    /// - `OTEL_EXPORTER_OTLP_ENDPOINT` adds a `BatchSpanProcessor` exporting to that endpoint
+    ///   with `OtlpGrpcExporter`. No processor is added when it is unset, or without the `otlp`
+    ///   feature.
+    ///
+    /// Invalid values are reported on stderr and ignored.
+    pub fn from_env() -> OpenTelemetrySdkBuilder {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        let mut builder = OpenTelemetrySdk::builder();
+        builder.sdk.sampler = sampler_from_env(
+            var("OTEL_TRACES_SAMPLER").as_deref(),
+            var("OTEL_TRACES_SAMPLER_ARG").as_deref(),
+        );
+        if let Some(service_name) = var("OTEL_SERVICE_NAME") {
+            builder = builder.with_service_name(service_name.trim());
+        }
+        #[cfg(feature = "otlp")]
+        if let Some(endpoint) = var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            match OtlpGrpcExporter::new(endpoint.trim()) {
+                Ok(exporter) => {
+                    let processor =
+                        BatchSpanProcessor::new(Box::new(exporter), BatchConfig::default());
+                    builder = builder.with_span_processor(processor);
+                }
+                Err(err) => eprintln!("OTEL_EXPORTER_OTLP_ENDPOINT: {err}"),
+            }
+        }
+        builder
+    }
+
+    pub fn builder() -> OpenTelemetrySdkBuilder {
+        OpenTelemetrySdkBuilder {
+            sdk: OpenTelemetrySdk::new(),
+        }
+    }
+
+    /// A handle to flush and shut down the processors after the SDK has been moved into the
+    /// subscriber, including processors registered after it was taken.
+    pub fn handle(&self) -> TracerProviderHandle {
+        TracerProviderHandle {
+            processors: Arc::clone(&self.processors),
+            counters: Arc::clone(&self.counters),
+            open_spans: self.open_spans.clone(),
+            violations: self.violations.clone(),
+        }
+    }
+
+    /// Exports the spans every processor is still holding on to.
+    pub fn force_flush(&self) {
+        for processor in self.processors.read().unwrap().iter() {
+            processor.force_flush();
+        }
+    }
+
+    pub fn shutdown(&self) {
+        for processor in self.processors.read().unwrap().iter() {
+            processor.shutdown();
+        }
+    }
+
+    /// Number of spans not recorded because they exceeded the maximum trace depth.
+    pub fn dropped_by_depth(&self) -> u64 {
+        self.counters.dropped_by_depth.load(Ordering::Relaxed)
+    }
+
+    /// Counts of spans created, sampled and lost since the SDK was built.
+    pub fn stats(&self) -> SdkStats {
+        self.counters.stats(&self.processors.read().unwrap())
+    }
+
+    /// Snapshots of the recording spans of `trace_id` that are still open, as of their last
+    /// `Span::record`. Always empty without `with_open_span_tracking`.
+    pub fn open_spans(&self, trace_id: TraceId) -> Vec<OTelSpanData> {
+        self.open_spans
+            .as_ref()
+            .map_or_else(Vec::new, |open_spans| open_spans.get(trace_id))
+    }
+
+    /// The violations found since the last call, oldest first. Always empty outside strict
+    /// mode.
+    pub fn take_violations(&self) -> Vec<Violation> {
+        take_violations(&self.violations)
+    }
+
+    fn report(&self, violation: Violation) {
+        if let Some(violations) = &self.violations {
+            violations.lock().unwrap().push(violation);
+        }
+    }
+
+    // Looks up the span a callback is for, reporting it in strict mode if there is none.
+    fn lookup<'a, S>(
+        &self,
+        ctx: &'a Context<'_, S>,
+        id: &span::Id,
+        callback: &'static str,
+    ) -> Option<SpanRef<'a, S>>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let span = ctx.span(id);
+        if span.is_none() {
+            self.report(Violation::UnknownSpan {
+                callback,
+                id: id.into_u64(),
+            });
+        }
+        span
+    }
+
+    fn is_target_enabled(&self, target: &str) -> bool {
+        self.target_filters
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .is_none_or(|(_, enabled)| *enabled)
+    }
+
+    fn is_disabled_span(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() && !self.is_target_enabled(metadata.target())
+    }
+
+    fn sample(&self, context: SamplingContext<'_>, depth: usize) -> SamplingResult {
+        if !self.within_trace_depth(depth) {
+            return Decision::Drop.into();
+        }
+        match &self.decision_cache {
+            Some(cache) => {
+                cache.get_or_insert_with(context.trace_id, || self.sampler.should_sample(context))
+            }
+            None => self.sampler.should_sample(context),
+        }
+    }
+
+    // Builds the span of a sampled `tracing` span, recording the fields it was created with.
+    fn new_otel_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        trace_id: TraceId,
+        span_id: SpanId,
+        parent: Option<SpanContext>,
+        depth: usize,
+    ) -> OTelSpan {
+        let mut span = OTelSpan::new(
+            attrs.metadata().name().to_string(),
+            trace_id,
+            span_id,
+            parent.map(|parent| parent.span_id),
+            false,
+        );
+        span.depth = depth;
+        span.parent_is_remote = parent.is_some_and(|parent| parent.is_remote);
+        span.start_time = self.clock.now();
+        span.limits = self.span_limits;
+        span.scope_name = attrs.metadata().target().to_string();
+        // Most fields become attributes; size the map once rather than growing it per field.
+        // Spans without fields leave it unallocated.
+        let fields = attrs.metadata().fields().len();
+        span.attributes.reserve(fields.min(self.span_limits.max_attributes));
+        attrs.record(&mut span);
+        span
+    }
+
+    // Hands the processors a snapshot of a span that was just sampled.
+    fn start_span(&self, span: &OTelSpan) {
+        let processors = self.processors.read().unwrap();
+        if processors.is_empty() {
+            return;
+        }
+        let span = OTelSpanData::from(span);
+        for processor in processors.iter() {
+            processor.on_start(&span);
+        }
+    }
+
+    // Tells the processors a started span won't reach `on_end`.
+    fn drop_span(&self, span: &OTelSpan) {
+        let processors = self.processors.read().unwrap();
+        if processors.is_empty() {
+            return;
+        }
+        let span = OTelSpanData::from(span);
+        for processor in processors.iter() {
+            processor.on_drop(&span);
+        }
+    }
+
+    // Swaps the `NonRecordingSpan` of a span the sampler dropped for an `OTelSpan`, starting
+    // now. The fields it was created with are gone by then and aren't recorded.
+    fn record_dropped_span(&self, span: &Span) {
+        let Some(dropped) = with_extensions_mut(span, |extensions| {
+            extensions.remove::<NonRecordingSpan>()
+        })
+        .flatten() else {
+            return;
+        };
+        let Some(metadata) = span.metadata() else {
+            return;
+        };
+        let mut otel_span = OTelSpan::new(
+            metadata.name().to_string(),
+            dropped.context.trace_id,
+            dropped.context.span_id,
+            dropped.context.parent_span_id,
+            true,
+        );
+        otel_span.depth = dropped.depth;
+        otel_span.start_time = self.clock.now();
+        otel_span.limits = self.span_limits;
+        otel_span.scope_name = metadata.target().to_string();
+        otel_span.flags = dropped.context.flags.with_sampled(true);
+        otel_span.trace_state = dropped.trace_state;
+        if self.capture_code_location {
+            for (key, value) in code_location(metadata) {
+                otel_span.set_attribute(key, value);
+            }
+        }
+        self.start_span(&otel_span);
+        self.counters.sampled.fetch_add(1, Ordering::Relaxed);
+        let started = SpanStart(self.clock.instant());
+        with_extensions_mut(span, move |extensions| {
+            extensions.insert(otel_span);
+            extensions.insert(started);
+            if self.record_context_switches {
+                extensions.insert(ContextSwitches::default());
+            }
+        });
+    }
+
+    // Records the event's fields, level and target onto a span event or log record.
+    fn record_event(&self, span_event: &mut SpanEvent, event: &Event<'_>) {
+        event.record(span_event);
+        let metadata = event.metadata();
+        span_event.set_attribute(EVENT_LEVEL, metadata.level().as_str());
+        span_event.set_attribute(EVENT_TARGET, metadata.target());
+        if self.capture_code_location {
+            for (key, value) in code_location(metadata) {
+                span_event.set_attribute(key, value);
+            }
+        }
+    }
+
+    fn within_trace_depth(&self, depth: usize) -> bool {
+        match self.max_trace_depth {
+            Some(max_depth) if depth > max_depth => {
+                self.counters.dropped_by_depth.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Why `OpenTelemetrySdkBuilder::build` rejected the SDK's configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// A sampler was given a ratio outside `0.0..=1.0`.
+    InvalidSamplingRatio(f64),
+    /// `SpanLimits::sample_events` with a `max_events` of 0, which leaves no events to sample.
+    EventSamplingWithoutEvents,
+    /// `with_trace_decision_cache(0)`, which has no room for a decision.
+    ZeroDecisionCacheCapacity,
+    /// A `BatchConfig` with a `max_queue_size` or `max_export_batch_size` of 0.
+    ZeroBatchLimit,
+    /// A `BatchConfig` whose batches are larger than its queue, so they never fill up.
+    ExportBatchLargerThanQueue {
+        max_export_batch_size: usize,
+        max_queue_size: usize,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidSamplingRatio(ratio) => {
+                write!(f, "sampling ratio {ratio} is not within 0.0..=1.0")
+            }
+            ConfigError::EventSamplingWithoutEvents => {
+                write!(f, "event sampling needs a max_events above 0")
+            }
+            ConfigError::ZeroDecisionCacheCapacity => {
+                write!(f, "trace decision cache capacity must be above 0")
+            }
+            ConfigError::ZeroBatchLimit => {
+                write!(f, "batch queue and export batch sizes must be above 0")
+            }
+            ConfigError::ExportBatchLargerThanQueue {
+                max_export_batch_size,
+                max_queue_size,
+            } => write!(
+                f,
+                "export batch size {max_export_batch_size} exceeds the queue size {max_queue_size}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Collects the SDK's configuration, checked by `build`. `OpenTelemetrySdk::new` is the SDK with
+/// every default.
+pub struct OpenTelemetrySdkBuilder {
+    sdk: OpenTelemetrySdk,
+}
+
+impl OpenTelemetrySdkBuilder {
+    pub fn with_sampler<T>(mut self, sampler: T) -> Self
+    where
+        T: ShouldSample + Send + Sync + 'static,
+    {
+        self.sdk.sampler = Box::new(sampler);
+        self
+    }
+
+    pub fn with_id_generator<T>(mut self, id_generator: T) -> Self
+    where
+        T: IdGenerator + Send + Sync + 'static,
+    {
+        self.sdk.id_generator = Box::new(id_generator);
+        self
+    }
+
+    pub fn with_clock<T>(mut self, clock: T) -> Self
+    where
+        T: Clock + Send + Sync + 'static,
+    {
+        self.sdk.clock = Box::new(clock);
+        self
+    }
+
+    /// Registers a processor notified when spans start and end. Processors add up, and are
+    /// notified, flushed and shut down in the order they were registered.
+    pub fn with_span_processor<T>(self, processor: T) -> Self
+    where
+        T: SpanProcessor + Send + Sync + 'static,
+    {
+        let mut processor = processor;
+        processor.set_resource(&self.sdk.resource);
+        self.sdk.processors.write().unwrap().push(Box::new(processor));
+        self
+    }
+
+    /// Sets the resource handed to every processor's exporter. `service.name` defaults to the
+    /// executable's name if the resource doesn't set it.
+    pub fn with_resource(mut self, resource: Resource) -> Self {
+        let resource = resource.with_default_service_name();
+        for processor in self.sdk.processors.write().unwrap().iter_mut() {
+            processor.set_resource(&resource);
+        }
+        self.sdk.resource = resource;
+        self
+    }
+
+    pub fn with_service_name(self, service_name: impl Into<String>) -> Self {
+        let resource = self.sdk.resource.clone().with_attribute(SERVICE_NAME, service_name.into());
+        self.with_resource(resource)
+    }
+
+    pub fn with_event_export_mode(mut self, mode: EventExportMode) -> Self {
+        self.sdk.event_export_mode = mode;
+        self
+    }
+
+    /// Record the voluntary/involuntary context switches a span accumulates while entered.
+    /// Only supported on Linux, a no-op elsewhere. Off by default.
+    pub fn with_context_switches(mut self, enabled: bool) -> Self {
+        self.sdk.record_context_switches = enabled;
+        self
+    }
+
+    /// Record `ERROR` events as `exception` span events and mark the enclosing span as failed.
+    /// Off by default.
+    pub fn with_exception_events(mut self, enabled: bool) -> Self {
+        self.sdk.record_exceptions = enabled;
+        self
+    }
+
+    /// Overrides how spans with an `http.status_code` attribute and no status of their own get
+    /// one when they close. Defaults to [`http_span_status`].
+    pub fn with_http_status_mapping<F>(mut self, mapping: F) -> Self
+    where
+        F: Fn(SpanKind, u16) -> SpanStatus + Send + Sync + 'static,
+    {
+        self.sdk.http_status_mapping = Arc::new(mapping);
+        self
+    }
+
+    /// Runs `processor` on the attributes of each finished span and of its events before they go
+    /// to the span processors, e.g. to drop, rename or hash personal data. Processors run in the
+    /// order they were added. Snapshots taken while the span is open are not processed.
+    pub fn with_attribute_processor<F>(mut self, processor: F) -> Self
+    where
+        F: Fn(&mut HashMap<String, AttributeValue>) + Send + Sync + 'static,
+    {
+        self.sdk.attribute_processors.push(Box::new(processor));
+        self
+    }
+
+    /// Add `code.filepath`, `code.lineno` and `code.namespace` from the callsite to spans and
+    /// span events. Off by default.
+    pub fn with_code_location(mut self, enabled: bool) -> Self {
+        self.sdk.capture_code_location = enabled;
+        self
+    }
+
+    /// Add `thread.id` and, for named threads, `thread.name` of the thread spans are created
+    /// on. Off by default.
+    pub fn with_thread_info(mut self, enabled: bool) -> Self {
+        self.sdk.capture_thread = enabled;
+        self
+    }
+
+    /// Add the span's tracing level as the `level` attribute when it closes, unless a field
+    /// of that name is set. Off by default, since it counts towards `max_attributes`.
+    pub fn with_span_level_attribute(mut self, enabled: bool) -> Self {
+        self.sdk.span_level = enabled;
+        self
+    }
+
+    /// Export spans under the value of their `key` attribute, e.g. `http.route`, rather than
+    /// their name, if they have one when they close.
+    pub fn with_name_from_attribute(mut self, key: impl Into<String>) -> Self {
+        self.sdk.name_from_attribute = Some(key.into());
+        self
+    }
+
+    /// Enables or disables spans whose target starts with `prefix`, e.g. to leave out `sqlx`.
+    /// When several prefixes match, the longest wins; targets matching none are enabled.
+    /// Disabled spans are turned off through `Layer::register_callsite`, so like with a global
+    /// filter, other layers on the same subscriber don't see them either; put the SDK behind a
+    /// per-layer filter to keep them elsewhere. Unlike spans the sampler drops, disabled spans
+    /// get no ids or OTel data at all: their children continue the trace of the closest enabled
+    /// ancestor.
+    pub fn with_target_filter(mut self, prefix: impl Into<String>, enabled: bool) -> Self {
+        let prefix = prefix.into();
+        self.sdk.target_filters.retain(|(existing, _)| *existing != prefix);
+        self.sdk.target_filters.push((prefix, enabled));
+        self.sdk.target_filters.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        self
+    }
+
+    /// Stop recording spans nested deeper than `max_depth` within a trace. Such spans still
+    /// carry the trace context so their children stay connected, but they are not exported.
+    pub fn with_max_trace_depth(mut self, max_depth: usize) -> Self {
+        self.sdk.max_trace_depth = Some(max_depth);
+        self
+    }
+
+    pub fn with_span_limits(mut self, limits: SpanLimits) -> Self {
+        self.sdk.span_limits = limits;
+        self
+    }
+
+    /// Ask the sampler once per trace and reuse its decision for the trace's other spans,
+    /// remembering the decisions of the last `capacity` traces. Spans reusing a decision don't
+    /// get the sampler's extra attributes. Off by default, as samplers that decide per span,
+    /// e.g. `TargetOverrideSampler`, are then only consulted for each trace's first span.
+    pub fn with_trace_decision_cache(mut self, capacity: usize) -> Self {
+        self.sdk.decision_cache = Some(DecisionCache::new(capacity));
+        self
+    }
+
+    /// Keep a snapshot of every open recording span, so `open_spans` can list a trace's spans
+    /// that haven't closed yet, e.g. for a debug endpoint. Off by default: each open span is
+    /// held twice until it closes, and spans that never close are never released.
+    pub fn with_open_span_tracking(mut self) -> Self {
+        self.sdk.open_spans = Some(Arc::default());
+        self
+    }
+
+    /// Flag recording spans that close after their local parent with `otel.orphaned = true`, and
+    /// count them in `SdkStats::spans_orphaned`: exporters may send them in a later batch than
+    /// their parent. Children of a `tracing` parent keep it open, so this mostly catches
+    /// contexts of local spans passed to `set_parent_context`. Off by default, as it tracks the
+    /// id of every open span.
+    pub fn with_orphan_detection(mut self) -> Self {
+        self.sdk.live_span_ids = Some(Mutex::default());
+        self
+    }
+
+    /// Collect the misuses of `tracing` the SDK otherwise ignores, e.g. in CI, to be read with
+    /// `take_violations`. Off by default.
+    pub fn with_strict_mode(mut self, enabled: bool) -> Self {
+        self.sdk.violations = enabled.then(Arc::default);
+        self
+    }
+
+    /// The propagator `OtelSpanExt::inject_into` writes outbound headers with. W3C Trace
+    /// Context by default.
+    pub fn with_propagator<T>(mut self, propagator: T) -> Self
+    where
+        T: TextMapPropagator + Send + Sync + 'static,
+    {
+        self.sdk.propagator = Arc::new(propagator);
+        self
+    }
+
+    /// A handle to the SDK being built, see `OpenTelemetrySdk::handle`.
+    pub fn handle(&self) -> TracerProviderHandle {
+        self.sdk.handle()
+    }
+
+    /// Builds the SDK, or reports the first problem found with its configuration.
+    pub fn build(self) -> Result<OpenTelemetrySdk, ConfigError> {
+        self.sdk.sampler.validate()?;
+        let limits = &self.sdk.span_limits;
+        if limits.sample_events && limits.max_events == 0 {
+            return Err(ConfigError::EventSamplingWithoutEvents);
+        }
+        if self.sdk.decision_cache.as_ref().is_some_and(|cache| cache.capacity() == 0) {
+            return Err(ConfigError::ZeroDecisionCacheCapacity);
+        }
+        for processor in self.sdk.processors.read().unwrap().iter() {
+            processor.validate()?;
+        }
+        Ok(self.sdk)
+    }
+
+    /// Builds the SDK along with a handle to flush and shut it down once it is owned by the
+    /// subscriber.
+    pub fn build_with_handle(
+        self,
+    ) -> Result<(OpenTelemetrySdk, TracerProviderHandle), ConfigError> {
+        let sdk = self.build()?;
+        let handle = sdk.handle();
+        Ok((sdk, handle))
+    }
+}
+
+/// Flushes and shuts down the SDK's span processors, e.g. from `main` before it returns.
+#[derive(Clone)]
+pub struct TracerProviderHandle {
+    processors: Arc<RwLock<Vec<Box<dyn SpanProcessor + Send + Sync>>>>,
+    counters: Arc<SpanCounters>,
+    open_spans: Option<Arc<OpenSpans>>,
+    violations: Option<Arc<Mutex<Vec<Violation>>>>,
+}
+
+impl TracerProviderHandle {
+    pub fn force_flush(&self) {
+        for processor in self.processors.read().unwrap().iter() {
+            processor.force_flush();
+        }
+    }
+
+    pub fn shutdown(&self) {
+        for processor in self.processors.read().unwrap().iter() {
+            processor.shutdown();
+        }
+    }
+
+    /// See `OpenTelemetrySdk::stats`.
+    pub fn stats(&self) -> SdkStats {
+        self.counters.stats(&self.processors.read().unwrap())
+    }
+
+    /// See `OpenTelemetrySdk::open_spans`.
+    pub fn open_spans(&self, trace_id: TraceId) -> Vec<OTelSpanData> {
+        self.open_spans
+            .as_ref()
+            .map_or_else(Vec::new, |open_spans| open_spans.get(trace_id))
+    }
+
+    /// See `OpenTelemetrySdk::take_violations`.
+    pub fn take_violations(&self) -> Vec<Violation> {
+        take_violations(&self.violations)
+    }
+}
+
+/// A misuse of `tracing` reported in strict mode, see `OpenTelemetrySdkBuilder::with_strict_mode`.
+/// Fields recorded that a span didn't declare aren't among them: `tracing` drops those before
+/// the SDK sees them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A callback, e.g. `on_record`, for a span the subscriber doesn't know, such as one
+    /// already closed.
+    UnknownSpan { callback: &'static str, id: u64 },
+    /// An event without a message or any other field.
+    EmptyEvent { name: String, target: String },
+}
+
+fn take_violations(violations: &Option<Arc<Mutex<Vec<Violation>>>>) -> Vec<Violation> {
+    violations
+        .as_ref()
+        .map_or_else(Vec::new, |violations| std::mem::take(&mut *violations.lock().unwrap()))
+}
+
+// Whether an event recorded any field.
+fn has_fields(event: &Event<'_>) -> bool {
+    struct AnyField(bool);
+
+    impl Visit for AnyField {
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn fmt::Debug) {
+            self.0 = true;
+        }
+    }
+
+    let mut visitor = AnyField(false);
+    event.record(&mut visitor);
+    visitor.0
+}
+
+/// Span counts reported by `OpenTelemetrySdk::stats`, e.g. to alert on telemetry loss.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SdkStats {
+    pub spans_created: u64,
+    /// Spans the sampler decided to record.
+    pub spans_sampled: u64,
+    /// Finished spans dropped by processors, e.g. because `BatchSpanProcessor`'s queue was full.
+    pub spans_dropped_queue_full: u64,
+    /// Spans not recorded because they exceeded the maximum trace depth.
+    pub spans_dropped_limits: u64,
+    /// Attributes `SpanLimits` dropped from exported spans and their events.
+    pub attributes_dropped_limits: u64,
+    /// Events `SpanLimits` dropped from exported spans.
+    pub events_dropped_limits: u64,
+    /// Links `SpanLimits` dropped from exported spans.
+    pub links_dropped_limits: u64,
+    /// String attribute values of exported spans and their events cut down to
+    /// `SpanLimits::max_attribute_value_len`.
+    pub attributes_truncated: u64,
+    /// Spans closed after their local parent, counted with `with_orphan_detection`.
+    pub spans_orphaned: u64,
+}
+
+// Open recording spans by trace, kept by `with_open_span_tracking`.
+#[derive(Default)]
+struct OpenSpans {
+    traces: Mutex<HashMap<TraceId, HashMap<SpanId, OTelSpanData>>>,
+}
+
+impl OpenSpans {
+    fn get(&self, trace_id: TraceId) -> Vec<OTelSpanData> {
+        let traces = self.traces.lock().unwrap();
+        traces
+            .get(&trace_id)
+            .map_or_else(Vec::new, |spans| spans.values().cloned().collect())
+    }
+
+    fn update(&self, span: &OTelSpan) {
+        let mut traces = self.traces.lock().unwrap();
+        traces
+            .entry(span.trace_id)
+            .or_default()
+            .insert(span.span_id, span.into());
+    }
+
+    fn remove(&self, span: &OTelSpan) {
+        let mut traces = self.traces.lock().unwrap();
+        if let Some(spans) = traces.get_mut(&span.trace_id) {
+            spans.remove(&span.span_id);
+            // Drop the trace's entry with its last span, so finished traces take no memory.
+            if spans.is_empty() {
+                traces.remove(&span.trace_id);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SpanCounters {
+    created: AtomicU64,
+    sampled: AtomicU64,
+    dropped_by_depth: AtomicU64,
+    attributes_dropped_by_limits: AtomicU64,
+    events_dropped_by_limits: AtomicU64,
+    links_dropped_by_limits: AtomicU64,
+    attributes_truncated: AtomicU64,
+    orphaned: AtomicU64,
+}
+
+impl SpanCounters {
+    fn stats(&self, processors: &[Box<dyn SpanProcessor + Send + Sync>]) -> SdkStats {
+        SdkStats {
+            spans_created: self.created.load(Ordering::Relaxed),
+            spans_sampled: self.sampled.load(Ordering::Relaxed),
+            spans_dropped_queue_full: processors
+                .iter()
+                .map(|processor| processor.dropped_spans())
+                .sum(),
+            spans_dropped_limits: self.dropped_by_depth.load(Ordering::Relaxed),
+            attributes_dropped_limits: self.attributes_dropped_by_limits.load(Ordering::Relaxed),
+            events_dropped_limits: self.events_dropped_by_limits.load(Ordering::Relaxed),
+            links_dropped_limits: self.links_dropped_by_limits.load(Ordering::Relaxed),
+            attributes_truncated: self.attributes_truncated.load(Ordering::Relaxed),
+            spans_orphaned: self.orphaned.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<S> Layer<S> for OpenTelemetrySdk
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_layer(&mut self, _subscriber: &mut S) {
+        self.with_context = Some(WithContext::new::<S>());
+    }
+
+    // Besides the SDK itself, hands out the `WithContext` for the subscriber it is layered on,
+    // which is how `OtelSpanExt` reaches span extensions without knowing that subscriber's type.
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const Self as *const ())
+        } else if id == TypeId::of::<WithContext>() {
+            self.with_context
+                .as_ref()
+                .map(|with_context| with_context as *const WithContext as *const ())
+        } else {
+            None
+        }
+    }
+
+    // Spans of disabled targets are turned off at the callsite, so tracing doesn't create them.
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.is_disabled_span(metadata) {
+            Interest::never()
+        } else {
+            Interest::always()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        !self.is_disabled_span(metadata)
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.is_target_enabled(attrs.metadata().target()) {
+            return;
+        }
+        // Resolve the parent the way tracing does: an explicit parent wins, otherwise the span
+        // current on this thread (e.g. the one entered by `Instrument` while polling) is used.
+        // A parent the layer has no OTel data for (e.g. it was created before the subscriber
+        // was initialized) is treated as absent and the new span starts its own trace.
+        let parent_span = if let Some(parent_id) = attrs.parent() {
+            ctx.span(parent_id)
+        } else if attrs.is_contextual() {
+            ctx.lookup_current()
+        } else {
+            None
+        };
+        // Spans of disabled targets are skipped, as they have no OTel data.
+        let parent_span = parent_span.and_then(|parent_span| {
+            parent_span
+                .scope()
+                .find(|span| self.is_target_enabled(span.metadata().target()))
+        });
+        // Copy what the new span inherits out of the parent and release its extensions before
+        // locking the new span's, so two extension locks are never held at once.
+        let (parent, baggage) = match parent_span {
+            Some(parent_span) => {
+                let mut extensions = parent_span.extensions_mut();
+                let parent = Parent::read(&mut extensions);
+                (parent, extensions.get_mut::<Baggage>().cloned())
+            }
+            None => (Parent::None, None),
+        };
+        let parent = match parent {
+            Parent::None => current_remote_context()
+                .filter(|context| SpanContext::from(*context).is_valid())
+                .map_or(Parent::None, |context| Parent::Remote {
+                    context,
+                    trace_state: TraceState::default(),
+                }),
+            parent => parent,
+        };
+
+        let upstream_sampled = match &parent {
+            Parent::Remote { context, .. } => context.flags.is_sampled(),
+            _ => true,
+        };
+        let (trace_id, parent_context, depth, trace_state) = match parent {
+            // reuse the parent's trace id and record it as the new span's parent.
+            Parent::Local {
+                context,
+                depth,
+                trace_state,
+            } => (context.trace_id, Some(context), depth + 1, trace_state),
+            // the parent stands in for a span in another process, or a propagator attached
+            // an extracted context to this thread: continue the remote trace.
+            Parent::Remote {
+                context,
+                trace_state,
+            } => (context.trace_id, Some(context.into()), 1, trace_state),
+            Parent::None => (
+                self.id_generator.new_trace_id(),
+                None,
+                1,
+                TraceState::default(),
+            ),
+        };
+        let span_id = self.id_generator.new_span_id();
+
+        // Attributes are only recorded ahead of the sampling decision for samplers that look at
+        // them; otherwise dropped spans never record any.
+        let recorded = self
+            .sampler
+            .uses_attributes()
+            .then(|| self.new_otel_span(attrs, trace_id, span_id, parent_context, depth));
+        let no_attributes = HashMap::new();
+        let forced = force_sample(attrs);
+        let mut result = if forced {
+            SamplingResult::new(Decision::RecordAndSample).with_attribute(SAMPLING_FORCED, true)
+        } else {
+            self.sample(
+                SamplingContext {
+                    parent: parent_context,
+                    trace_id,
+                    name: attrs.metadata().name(),
+                    target: attrs.metadata().target(),
+                    level: *attrs.metadata().level(),
+                    attributes: recorded.as_ref().map_or(&no_attributes, |span| &span.attributes),
+                    trace_state: &trace_state,
+                },
+                depth,
+            )
+        };
+        // Like `set_parent_context`, follow the upstream sampling decision, unless forced.
+        let sampled = result.is_sampled() && (upstream_sampled || forced);
+        let trace_state = result.trace_state.take().unwrap_or(trace_state);
+        // Other flag bits are inherited from the parent.
+        let flags = parent_context
+            .map(|parent| parent.flags)
+            .unwrap_or_default()
+            .with_sampled(sampled);
+        self.counters.created.fetch_add(1, Ordering::Relaxed);
+
+        let otel_span = sampled.then(|| {
+            let mut otel_span = recorded.unwrap_or_else(|| {
+                self.new_otel_span(attrs, trace_id, span_id, parent_context, depth)
+            });
+            otel_span.is_recording = true;
+            otel_span.flags = flags;
+            otel_span.trace_state = trace_state.clone();
+            // Attributes returned by the sampler take precedence over the ones recorded at
+            // creation.
+            for (key, value) in result.attributes {
+                otel_span.sampler_attributes.push(key.clone());
+                otel_span.set_attribute(key, value);
+            }
+            if self.capture_code_location {
+                for (key, value) in code_location(attrs.metadata()) {
+                    otel_span.set_attribute(key, value);
+                }
+            }
+            if self.capture_thread {
+                for (key, value) in thread_info() {
+                    otel_span.set_attribute(key, value);
+                }
+            }
+            self.start_span(&otel_span);
+            self.counters.sampled.fetch_add(1, Ordering::Relaxed);
+            otel_span
+        });
+
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(live_span_ids) = &self.live_span_ids {
+            live_span_ids.lock().unwrap().insert(span_id);
+        }
+        let mut extensions = span.extensions_mut();
+        // baggage set on the parent is visible to its children.
+        if let Some(baggage) = baggage {
+            extensions.insert(baggage);
+        }
+        match otel_span {
+            Some(otel_span) => {
+                if let Some(open_spans) = &self.open_spans {
+                    open_spans.update(&otel_span);
+                }
+                // store span in span extension.
+                extensions.insert(otel_span);
+                extensions.insert(SpanStart(self.clock.instant()));
+                if self.record_context_switches {
+                    extensions.insert(ContextSwitches::default());
+                }
+            }
+            None => extensions.insert(NonRecordingSpan {
+                context: SpanContext {
+                    parent_span_id: parent_context.map(|parent| parent.span_id),
+                    ..SpanContext::new(trace_id, span_id, flags)
+                },
+                depth,
+                trace_state,
+            }),
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = self.lookup(&ctx, id, "on_enter") else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let start = extensions.get_mut::<SpanStart>().map(|start| start.0);
+        if let (Some(start), Some(otel_span)) = (start, extensions.get_mut::<OTelSpan>()) {
+            if otel_span.queue_time.is_none() {
+                otel_span.queue_time = Some(self.clock.instant().saturating_duration_since(start));
+            }
+        }
+        if !self.record_context_switches {
+            return;
+        }
+        if let Some(switches) = extensions.get_mut::<ContextSwitches>() {
+            switches.entered = thread_context_switches();
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.record_context_switches && self.violations.is_none() {
+            return;
+        }
+        let Some(span) = self.lookup(&ctx, id, "on_exit") else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(switches) = extensions.get_mut::<ContextSwitches>() {
+            if let (Some((voluntary, involuntary)), Some(now)) =
+                (switches.entered.take(), thread_context_switches())
+            {
+                switches.voluntary += now.0.saturating_sub(voluntary);
+                switches.involuntary += now.1.saturating_sub(involuntary);
+                switches.measured = true;
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        // Lifecycle callbacks must not panic, e.g. for spans a filter hid from this layer.
+        let Some(span) = self.lookup(&ctx, &id, "on_close") else {
+            return;
+        };
+        let level = *span.metadata().level();
+        let mut extensions = span.extensions_mut();
+        // Remote parent stand-ins are never exported.
+        let Some(mut span) = extensions.remove::<OTelSpan>() else {
+            if let (Some(live_span_ids), Some(span)) =
+                (&self.live_span_ids, extensions.get_mut::<NonRecordingSpan>())
+            {
+                live_span_ids.lock().unwrap().remove(&span.context.span_id);
+            }
+            return;
+        };
+        // Added on close rather than creation, so spans without fields allocate no attributes
+        // while they are open. A field named `level` takes precedence.
+        if self.span_level && !span.attributes.contains_key(EVENT_LEVEL) {
+            span.set_attribute(EVENT_LEVEL, level.as_str());
+        }
+        if let Some(open_spans) = &self.open_spans {
+            open_spans.remove(&span);
+        }
+        // Measured on the monotonic clock, so the end never precedes the start.
+        span.end_time = Some(match extensions.remove::<SpanStart>() {
+            Some(SpanStart(start)) => {
+                span.start_time + self.clock.instant().saturating_duration_since(start)
+            }
+            None => self.clock.now(),
+        });
+        if let Some(name) = self
+            .name_from_attribute
+            .as_ref()
+            .and_then(|key| span.attributes.get(key))
+        {
+            span.name = name.to_string();
+        }
+        if let Some(status) = derived_http_status(&span, self.http_status_mapping.as_ref()) {
+            span.status = status;
+        }
+        let queue_time = span.queue_time();
+        if !queue_time.is_zero() {
+            span.set_attribute(QUEUE_TIME_NS, queue_time.as_nanos() as i64);
+        }
+        if span.child_count > 0 {
+            span.set_attribute(CHILD_COUNT, i64::from(span.child_count));
+        }
+        if let Some(live_span_ids) = &self.live_span_ids {
+            let mut live_span_ids = live_span_ids.lock().unwrap();
+            live_span_ids.remove(&span.span_id);
+            let orphaned = (!span.parent_is_remote || span.parent_is_local)
+                && span
+                    .parent_span_id
+                    .is_some_and(|parent| !live_span_ids.contains(&parent));
+            drop(live_span_ids);
+            if orphaned {
+                span.set_attribute(ORPHANED, true);
+                self.counters.orphaned.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if let Some(switches) = extensions.remove::<ContextSwitches>().filter(|s| s.measured) {
+            span.set_attribute("thread.context_switches.voluntary", switches.voluntary);
+            span.set_attribute("thread.context_switches.involuntary", switches.involuntary);
+        }
+        if span.is_recording {
+            self.counters
+                .attributes_truncated
+                .fetch_add(span.truncated_attributes(), Ordering::Relaxed);
+            self.counters
+                .attributes_dropped_by_limits
+                .fetch_add(span.dropped_attributes(), Ordering::Relaxed);
+            self.counters
+                .events_dropped_by_limits
+                .fetch_add(u64::from(span.dropped_events_count), Ordering::Relaxed);
+            self.counters
+                .links_dropped_by_limits
+                .fetch_add(u64::from(span.dropped_links_count), Ordering::Relaxed);
+            for processor in &self.attribute_processors {
+                processor(&mut span.attributes);
+                for event in &mut span.events {
+                    processor(&mut event.attributes);
+                }
+            }
+            // SpanProcessors can pass Spans to exporter(s) which can export in OTLP format/others.
+            let span = OTelSpanData::from(span);
+            for processor in self.processors.read().unwrap().iter() {
+                processor.on_end(&span);
+            }
+        } else {
+            // Started, then stopped recording by an unsampled parent.
+            self.drop_span(&span);
+        }
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = self.lookup(&ctx, span, "on_record") else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        // Remote parent stand-ins have no OTel span to record onto.
+        if let Some(existing_span) = extensions.get_mut::<OTelSpan>() {
+            values.record(existing_span);
+            if let Some(open_spans) = &self.open_spans {
+                open_spans.update(existing_span);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !event.metadata().is_event() {
+            return;
+        }
+        if self.violations.is_some() && !has_fields(event) {
+            self.report(Violation::EmptyEvent {
+                name: event.metadata().name().to_string(),
+                target: event.metadata().target().to_string(),
+            });
+        }
+        if self.event_export_mode == EventExportMode::None {
+            return;
+        }
+        let span = event.parent().and_then(|id| ctx.span(id)).or_else(|| {
+            event
+                .is_contextual()
+                .then(|| ctx.lookup_current())
+                .flatten()
+        });
+        let mut extensions = span.as_ref().map(|span| span.extensions_mut());
+        // Events outside any span still make valid log records, just without a span context.
+        let (trace_id, span_id) = extensions
+            .as_mut()
+            .and_then(|extensions| {
+                if let Some(span) = extensions.get_mut::<OTelSpan>() {
+                    return Some(span.span_context());
+                }
+                extensions.get_mut::<NonRecordingSpan>().map(|span| span.context)
+            })
+            .map(|context| (context.trace_id, context.span_id))
+            .unwrap_or_default();
+        // Remote parent stand-ins and dropped spans have no OTel span and receive no span events.
+        let existing_span = extensions
+            .as_mut()
+            .and_then(|extensions| extensions.get_mut::<OTelSpan>());
+
+        let mode = self.event_export_mode;
+        if let Some(existing_span) = existing_span.filter(|span| span.is_recording) {
+            if matches!(mode, EventExportMode::SpanEvent | EventExportMode::Both) {
+                if self.record_exceptions && *event.metadata().level() == Level::ERROR {
+                    let mut span_event = existing_span.new_event(EXCEPTION_EVENT, self.clock.now());
+                    self.record_event(&mut span_event, event);
+                    let message = exception_attributes(&mut span_event);
+                    existing_span.status = SpanStatus::Error {
+                        description: message,
+                    };
+                    existing_span.add_event(span_event);
+                } else {
+                    let mut span_event = existing_span
+                        .new_event(event.metadata().name(), self.clock.now());
+                    self.record_event(&mut span_event, event);
+                    existing_span.add_event(span_event);
+                }
+            }
+        }
+        if matches!(mode, EventExportMode::LogRecord | EventExportMode::Both) {
+            // Emit LogRecord using the Event, similar to how opentelemetry-tracing-appender works today.
+            // The record carries the enclosing span's ids so it can be correlated with the trace.
+            let mut record = SpanEvent::new(event.metadata().name());
+            self.record_event(&mut record, event);
+            let mut attributes: Vec<String> = record
+                .attributes
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            attributes.sort();
+            let severity = Severity::from(*event.metadata().level());
+            println!(
+                "LogRecord {} severity {} ({}) for Span with TraceId {} SpanId {}: {}",
+                record.name,
+                severity.number(),
+                event.metadata().level(),
+                trace_id,
+                span_id,
+                attributes.join(" ")
+            );
+        }
+    }
+}
+
+/// A layer that does nothing, for builds that keep their `span!` and event macros but export
+/// nothing: swap it in for `OpenTelemetrySdk` when layering the registry. Spans get no
+/// extensions, so `OtelSpanExt` methods see them as having no OpenTelemetry span.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisabledSdk;
+
+impl DisabledSdk {
+    pub fn new() -> DisabledSdk {
+        DisabledSdk
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for DisabledSdk {}
+
+// Attribute keys for the `tracing` level of a span or event, and the target of an event.
+pub const EVENT_LEVEL: &str = "level";
+pub const EVENT_TARGET: &str = "target";
+
+// Semantic convention keys for the callsite of a span or event.
+pub const CODE_FILEPATH: &str = "code.filepath";
+pub const CODE_LINENO: &str = "code.lineno";
+pub const CODE_NAMESPACE: &str = "code.namespace";
+
+fn code_location(metadata: &Metadata) -> Vec<(&'static str, AttributeValue)> {
+    let mut location = Vec::with_capacity(3);
+    if let Some(file) = metadata.file() {
+        location.push((CODE_FILEPATH, file.into()));
+    }
+    if let Some(line) = metadata.line() {
+        location.push((CODE_LINENO, i64::from(line).into()));
+    }
+    if let Some(module_path) = metadata.module_path() {
+        location.push((CODE_NAMESPACE, module_path.into()));
+    }
+    location
+}
+
+// Semantic convention keys for the thread a span was created on.
+pub const THREAD_ID: &str = "thread.id";
+pub const THREAD_NAME: &str = "thread.name";
+
+fn thread_info() -> Vec<(&'static str, AttributeValue)> {
+    let thread = std::thread::current();
+    let mut info = Vec::with_capacity(2);
+    // `ThreadId::as_u64` is unstable, but its `Debug` form is `ThreadId(n)`.
+    let id = format!("{:?}", thread.id());
+    if let Ok(id) = id.trim_start_matches("ThreadId(").trim_end_matches(')').parse::<i64>() {
+        info.push((THREAD_ID, id.into()));
+    }
+    if let Some(name) = thread.name() {
+        info.push((THREAD_NAME, name.to_string().into()));
+    }
+    info
+}
+
+type HttpStatusMapping = dyn Fn(SpanKind, u16) -> SpanStatus + Send + Sync;
+
+type AttributeProcessor = dyn Fn(&mut HashMap<String, AttributeValue>) + Send + Sync;
+
+/// The status HTTP semantic conventions give a span with HTTP status `code`: Error for 5xx, and
+/// for 4xx on all but server spans, as those are the client's fault. Unset otherwise.
+pub fn http_span_status(kind: SpanKind, code: u16) -> SpanStatus {
+    let is_error = match kind {
+        SpanKind::Server => code >= 500,
+        _ => code >= 400,
+    };
+    if is_error {
+        SpanStatus::Error {
+            description: http::StatusCode::from_u16(code)
+                .map_or_else(|_| code.to_string(), |status| status.to_string()),
+        }
+    } else {
+        SpanStatus::Unset
+    }
+}
+
+// The status derived from the span's `http.status_code`, if it has one and no status yet.
+fn derived_http_status(span: &OTelSpan, mapping: &HttpStatusMapping) -> Option<SpanStatus> {
+    if span.status != SpanStatus::Unset {
+        return None;
+    }
+    let code = match span.attributes.get(HTTP_STATUS_CODE)? {
+        AttributeValue::I64(code) => u16::try_from(*code).ok()?,
+        AttributeValue::String(code) => code.parse().ok()?,
+        _ => return None,
+    };
+    Some(mapping(span.kind, code))
+}
+
+// Semantic convention names for exceptions recorded from `ERROR` events.
+pub const EXCEPTION_EVENT: &str = "exception";
+pub const EXCEPTION_TYPE: &str = "exception.type";
+pub const EXCEPTION_MESSAGE: &str = "exception.message";
+
+// Moves the event's `message` field to `exception.message`, unless the event set that field
+// itself, and returns it. `exception.type` is only present if the event recorded it; as `type`
+// is a keyword, `tracing` names that field `exception.r#type`.
+fn exception_attributes(event: &mut SpanEvent) -> String {
+    if let Some(exception_type) = event.attributes.remove("exception.r#type") {
+        event
+            .attributes
+            .insert(EXCEPTION_TYPE.to_string(), exception_type);
+    }
+    if let Some(message) = event.attributes.remove("message") {
+        event
+            .attributes
+            .entry(EXCEPTION_MESSAGE.to_string())
+            .or_insert(message);
+    }
+    event
+        .attributes
+        .get(EXCEPTION_MESSAGE)
+        .map(|message| message.to_string())
+        .unwrap_or_default()
+}
+
+// Attribute keys used by the result size helpers on `OtelSpanExt`.
+pub const ROWS_PROCESSED: &str = "data.rows_processed";
+pub const BYTES_IN: &str = "data.bytes_in";
+pub const BYTES_OUT: &str = "data.bytes_out";
+
+/// OpenTelemetry accessors for `tracing` spans.
+///
+/// Spans the OTel layer never saw, such as ones created before the subscriber was initialized,
+/// carry no OTel data: setters are no-ops and getters return defaults. Such a span entered after
+/// initialization does not act as a parent, so spans created inside it start a new trace.
+pub trait OtelSpanExt {
+    #[deprecated(note = "use `set_parent_from`, which follows the SDK's configured propagator")]
+    fn set_parent(&self, jaeger_format: String);
+
+    /// Extracts the remote parent from `carrier`, e.g. the headers of an incoming request,
+    /// with the propagator the SDK was configured with, and applies it like
+    /// `set_parent_context`, along with the trace state sent with it. The parent is left
+    /// unchanged if the carrier holds no valid context. Baggage in the carrier, W3C or Jaeger,
+    /// is added either way.
+    fn set_parent_from<E: Extractor>(&self, carrier: &E);
+
+    /// Sets the remote parent from an already decoded context, e.g. ids stored alongside a job.
+    /// An invalid context, with a zero trace or span id, is ignored. The span follows the
+    /// parent's sampling decision; one the local sampler dropped is recorded from then on,
+    /// without the fields it was created with.
+    fn set_parent_context(&self, parent: SpanContext);
+
+    /// Turns this span into a stand-in for `parent`, a span in another process. It is no longer
+    /// exported; spans created with it as their parent continue the remote trace. Don't enter
+    /// it or record events on it.
+    fn set_remote_parent(&self, parent: RemoteSpanContext);
+
+    /// The span's trace id, span id, parent span id and sampling flag, read together.
+    /// Remote stand-ins return the remote context. `None` if there is no OTel data.
+    fn span_context(&self) -> Option<SpanContext>;
+
+    fn trace_id(&self) -> TraceId;
+
+    #[deprecated(note = "use `trace_id`")]
+    fn tract_id(&self) -> TraceId;
+
+    fn span_id(&self) -> SpanId;
+
+    fn parent_span_id(&self) -> SpanId;
+
+    fn extract_jaeger_propagation(&self) -> String;
+
+    fn set_span_kind(&self, kind: SpanKind);
+
+    fn set_status(&self, status: SpanStatus);
+
+    fn add_link(&self, link: SpanLink);
+
+    /// Sets an attribute on the span, whether or not the `span!` macro declared a field for
+    /// it. Subject to the span's attribute limits.
+    fn set_attribute(&self, key: impl Into<String>, value: impl Into<AttributeValue>);
+
+    /// Adds an event stamped with the SDK's clock, as if recorded by a `tracing` event in
+    /// `EventExportMode::SpanEvent` mode. Subject to the span's event and attribute limits.
+    fn add_event(&self, name: impl Into<String>, attributes: Vec<(String, AttributeValue)>);
+
+    /// Sets an Error status and adds an `exception` event for `err`, described by its message
+    /// followed by those of its `source()` chain, e.g. `loading config: file not found`.
+    /// Subject to the span's event limits.
+    fn record_error(&self, err: &dyn std::error::Error);
+
+    /// How long the span has been open so far on the SDK clock's monotonic time, `None` if there
+    /// is no OTel span.
+    fn elapsed(&self) -> Option<Duration>;
+
+    /// Reads the OTel span, `T::default()` if there is none or `f` returns `None`.
+    fn with_otel_span<F, T>(&self, f: F) -> T
+        where F: FnOnce(&OTelSpan) -> Option<T>,
+              T: Default;
+
+    /// Like `with_otel_span`, but with mutable access.
+    fn with_otel_span_mut<F, T>(&self, f: F) -> T
+        where F: FnOnce(&mut OTelSpan) -> Option<T>,
+              T: Default;
+
+    /// The W3C trace state carried by this span.
+    fn trace_state(&self) -> TraceState;
+
+    /// Replaces the trace state, typically with one extracted from an incoming request. Spans
+    /// created under this one afterwards inherit it.
+    fn set_trace_state(&self, trace_state: TraceState);
+
+    /// The baggage carried by this span, including entries inherited from its parents.
+    fn baggage(&self) -> Baggage;
+
+    /// Sets a baggage entry on this span. Spans created under it afterwards inherit it.
+    fn set_baggage(&self, key: impl Into<String>, value: impl Into<String>);
+
+    /// Wraps `f` so that, when run on another thread (e.g. via `std::thread::spawn`), it executes
+    /// inside this span and spans created by it continue this span's trace.
+    fn propagate_into<F, T>(&self, f: F) -> impl FnOnce() -> T + Send + 'static
+        where F: FnOnce() -> T + Send + 'static;
+
+    /// Writes this span's context into `carrier` using the propagator the SDK was configured
+    /// with, e.g. the headers of an outbound request.
+    fn inject_into<I: Injector>(&self, carrier: &mut I);
+
+    /// Shorthand for `inject_into` on the headers of a request being built.
+    fn inject_into_request(&self, request: http::request::Builder) -> http::request::Builder;
+
+    /// Records the number of rows processed by this span under `data.rows_processed`.
+    fn record_rows(&self, rows: u64);
+
+    /// Records the number of bytes read by this span under `data.bytes_in`.
+    fn record_bytes_in(&self, bytes: u64);
+
+    /// Records the number of bytes written by this span under `data.bytes_out`.
+    fn record_bytes_out(&self, bytes: u64);
+}
+
+impl OtelSpanExt for Span {
+    fn set_parent(&self, jaeger_format: String) {
+        match parse_jaeger_trace_id(&jaeger_format) {
+            Ok((trace_id, span_id, flags)) => {
+                self.set_parent_context(SpanContext::new_remote(trace_id, span_id, flags))
+            }
+            Err(err) => eprintln!("set_parent: ignoring uber-trace-id {jaeger_format:?}: {err}"),
+        }
+    }
+
+    fn set_parent_from<E: Extractor>(&self, carrier: &E) {
+        let propagator = self.with_subscriber(|(_, subscriber)| {
+            subscriber
+                .downcast_ref::<OpenTelemetrySdk>()
+                .map(|sdk| Arc::clone(&sdk.propagator))
+        });
+        let parent = propagator
+            .flatten()
+            .and_then(|propagator| propagator.extract_checked(carrier));
+        if let Some(parent) = &parent {
+            self.set_parent_context(parent.context);
+        }
+        apply_extracted(self, parent, carrier);
+    }
+
+    fn set_parent_context(&self, parent: SpanContext) {
+        // The span stays the root of its own trace.
+        if !parent.is_valid() {
+            return;
+        }
+        let parent_is_local = self
+            .with_subscriber(|(_, dispatch)| {
+                let sdk = dispatch.downcast_ref::<OpenTelemetrySdk>()?;
+                if parent.flags.is_sampled() {
+                    // A span the local sampler dropped is recorded after all.
+                    sdk.record_dropped_span(self);
+                }
+                let live_span_ids = sdk.live_span_ids.as_ref()?;
+                Some(live_span_ids.lock().unwrap().contains(&parent.span_id))
+            })
+            .flatten()
+            .unwrap_or(false);
+        with_extensions_mut(self, |extensions| {
+            if let Some(dropped) = extensions.get_mut::<NonRecordingSpan>() {
+                dropped.context.trace_id = parent.trace_id;
+                dropped.context.parent_span_id = Some(parent.span_id);
+                dropped.context.flags = parent.flags.with_sampled(false);
+            }
+        });
+        self.with_otel_span_mut(move |otel_span| {
+            otel_span.trace_id = parent.trace_id;
+            otel_span.parent_span_id = Some(parent.span_id);
+            otel_span.parent_is_remote = true;
+            otel_span.parent_is_local = parent_is_local;
+            // Follow the upstream sampling decision rather than the local sampler.
+            otel_span.flags = parent.flags;
+            otel_span.set_sampled(parent.flags.is_sampled());
+            Some(())
+        })
+    }
+
+    fn set_remote_parent(&self, parent: RemoteSpanContext) {
+        // The span no longer closes as an SDK span.
+        let span_id = self.span_context().map(|context| context.span_id);
+        let removed = with_extensions_mut(self, move |extensions| {
+            let removed = extensions.remove::<OTelSpan>();
+            extensions.remove::<NonRecordingSpan>();
+            extensions.remove::<ContextSwitches>();
+            extensions.replace(parent);
+            removed
+        })
+        .flatten();
+        self.with_subscriber(|(_, dispatch)| {
+            let sdk = dispatch.downcast_ref::<OpenTelemetrySdk>()?;
+            if let Some(removed) = &removed {
+                sdk.drop_span(removed);
+            }
+            sdk.live_span_ids.as_ref()?.lock().unwrap().remove(&span_id?);
+            Some(())
+        });
+    }
+
+    fn span_context(&self) -> Option<SpanContext> {
+        with_extensions(self, |extensions| {
+            if let Some(otel_span) = extensions.get::<OTelSpan>() {
+                return Some(otel_span.span_context());
+            }
+            if let Some(dropped) = extensions.get::<NonRecordingSpan>() {
+                return Some(dropped.context);
+            }
+            extensions
+                .get::<RemoteSpanContext>()
+                .map(|remote| SpanContext::from(*remote))
+        })
+        .flatten()
+    }
+
+    fn trace_id(&self) -> TraceId {
+        local_span_context(self).trace_id
+    }
+
+    fn tract_id(&self) -> TraceId {
+        self.trace_id()
+    }
+
+    fn span_id(&self) -> SpanId {
+        local_span_context(self).span_id
+    }
+
+    fn parent_span_id(&self) -> SpanId {
+        local_span_context(self).parent_span_id.unwrap_or_default()
+    }
+
+    // Get the span, extract trace id, span id, parent span id and sampling decision
+    // build a jaeger propagation header.
+    fn extract_jaeger_propagation(&self) -> String {
+        let context = self.span_context().unwrap_or_default();
+        format!(
+            "{}:{}:{}:{:x}",
+            context.trace_id,
+            context.span_id,
+            context.parent_span_id.unwrap_or_default(),
+            context.flags.0
+        )
+    }
+
+    fn set_span_kind(&self, kind: SpanKind) {
+        self.with_otel_span_mut(move |otel_span| {
+            otel_span.kind = kind;
+            Some(())
+        })
+    }
+
+    fn set_status(&self, status: SpanStatus) {
+        self.with_otel_span_mut(move |otel_span| {
+            otel_span.status = status;
+            Some(())
+        })
+    }
+
+    fn add_link(&self, link: SpanLink) {
+        self.with_otel_span_mut(move |otel_span| {
+            otel_span.add_link(link);
+            Some(())
+        })
+    }
+
+    fn set_attribute(&self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        self.with_otel_span_mut(move |otel_span| {
+            otel_span.set_attribute(key, value);
+            Some(())
+        })
+    }
+
+    fn add_event(&self, name: impl Into<String>, attributes: Vec<(String, AttributeValue)>) {
+        let now = self
+            .with_subscriber(|(_, subscriber)| {
+                subscriber
+                    .downcast_ref::<OpenTelemetrySdk>()
+                    .map(|sdk| sdk.clock.now())
+            })
+            .flatten()
+            .unwrap_or_else(SystemTime::now);
+        self.with_otel_span_mut(move |otel_span| {
+            let mut event = otel_span.new_event(name, now);
+            for (key, value) in attributes {
+                event.set_attribute(key, value);
+            }
+            otel_span.add_event(event);
+            Some(())
+        })
+    }
+
+    fn record_error(&self, err: &dyn std::error::Error) {
+        let mut message = err.to_string();
+        for source in std::iter::successors(err.source(), |err| err.source()) {
+            message.push_str(": ");
+            message.push_str(&source.to_string());
+        }
+        self.add_event(
+            EXCEPTION_EVENT,
+            vec![(EXCEPTION_MESSAGE.to_string(), message.clone().into())],
+        );
+        self.set_status(SpanStatus::Error {
+            description: message,
+        });
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        let start = with_extensions(self, |extensions| {
+            extensions.get::<SpanStart>().map(|start| start.0)
+        })
+        .flatten()?;
+        self.with_subscriber(|(_, subscriber)| {
+            let sdk = subscriber.downcast_ref::<OpenTelemetrySdk>()?;
+            Some(sdk.clock.instant().saturating_duration_since(start))
+        })
+        .flatten()
+    }
+
+    fn with_otel_span<F, T>(&self, f: F) -> T
+        where F: FnOnce(&OTelSpan) -> Option<T>,
+              T: Default {
+        with_extensions(self, |extensions| extensions.get::<OTelSpan>().and_then(f))
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    fn with_otel_span_mut<F, T>(&self, f: F) -> T
+        where F: FnOnce(&mut OTelSpan) -> Option<T>,
+              T: Default {
+        with_extensions_mut(self, |extensions| extensions.get_mut::<OTelSpan>().and_then(f))
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    fn trace_state(&self) -> TraceState {
+        with_extensions(self, |extensions| {
+            if let Some(otel_span) = extensions.get::<OTelSpan>() {
+                return Some(otel_span.trace_state.clone());
+            }
+            if let Some(dropped) = extensions.get::<NonRecordingSpan>() {
+                return Some(dropped.trace_state.clone());
+            }
+            extensions.get::<TraceState>().cloned()
+        })
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    fn set_trace_state(&self, trace_state: TraceState) {
+        with_extensions_mut(self, move |extensions| {
+            if let Some(otel_span) = extensions.get_mut::<OTelSpan>() {
+                otel_span.trace_state = trace_state;
+            } else if let Some(dropped) = extensions.get_mut::<NonRecordingSpan>() {
+                dropped.trace_state = trace_state;
+            } else if extensions.get_mut::<RemoteSpanContext>().is_some() {
+                // Remote parent stand-ins hand it on to the spans created under them.
+                extensions.replace(trace_state);
+            }
+        });
+    }
+
+    fn baggage(&self) -> Baggage {
+        with_extensions(self, |extensions| extensions.get::<Baggage>().cloned())
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    fn set_baggage(&self, key: impl Into<String>, value: impl Into<String>) {
+        let (key, value) = (key.into(), value.into());
+        with_extensions_mut(self, move |extensions| {
+            if extensions.get_mut::<OTelSpan>().is_none()
+                && extensions.get_mut::<NonRecordingSpan>().is_none()
+                && extensions.get_mut::<RemoteSpanContext>().is_none()
+            {
+                return;
+            }
+            match extensions.get_mut::<Baggage>() {
+                Some(baggage) => baggage.insert(key, value),
+                None => {
+                    let mut baggage = Baggage::new();
+                    baggage.insert(key, value);
+                    extensions.insert(baggage);
+                }
+            }
+        });
+    }
+
+    fn propagate_into<F, T>(&self, f: F) -> impl FnOnce() -> T + Send + 'static
+        where F: FnOnce() -> T + Send + 'static {
+        let span = self.clone();
+        // The other thread doesn't share this one's default subscriber, so spans created by `f`
+        // need the span's own.
+        let dispatch = self.with_subscriber(|(_, dispatch)| dispatch.clone());
+        move || match dispatch {
+            Some(dispatch) => tracing::dispatcher::with_default(&dispatch, || span.in_scope(f)),
+            None => span.in_scope(f),
+        }
+    }
+
+    fn inject_into<I: Injector>(&self, carrier: &mut I) {
+        let propagator = self.with_subscriber(|(_, subscriber)| {
+            subscriber
+                .downcast_ref::<OpenTelemetrySdk>()
+                .map(|sdk| Arc::clone(&sdk.propagator))
+        });
+        if let Some(propagator) = propagator.flatten() {
+            propagator.inject(self, carrier);
+        }
+    }
+
+    fn inject_into_request(&self, mut request: http::request::Builder) -> http::request::Builder {
+        if let Some(headers) = request.headers_mut() {
+            self.inject_into(headers);
+        }
+        request
+    }
+
+    fn record_rows(&self, rows: u64) {
+        self.set_attribute(ROWS_PROCESSED, rows);
+    }
+
+    fn record_bytes_in(&self, bytes: u64) {
+        self.set_attribute(BYTES_IN, bytes);
+    }
+
+    fn record_bytes_out(&self, bytes: u64) {
+        self.set_attribute(BYTES_OUT, bytes);
+    }
+}
+
+// The propagator of the SDK in the current default subscriber, if there is one.
+pub(crate) fn default_propagator() -> Option<Arc<dyn TextMapPropagator + Send + Sync>> {
+    tracing::dispatcher::get_default(|dispatch| {
+        dispatch
+            .downcast_ref::<OpenTelemetrySdk>()
+            .map(|sdk| Arc::clone(&sdk.propagator))
+    })
+}
+
+// Applies what arrived alongside an extracted parent context, the conflict marker and trace
+// state, and the baggage in `carrier`. Shared by `set_parent_from` and `OtelService`.
+pub(crate) fn apply_extracted(span: &Span, extracted: Option<Extracted>, carrier: &dyn Extractor) {
+    if let Some(extracted) = extracted {
+        if extracted.conflict {
+            span.set_attribute(CONTEXT_CONFLICT, true);
+        }
+        if !extracted.trace_state.is_empty() {
+            span.set_trace_state(extracted.trace_state);
+        }
+    }
+    for (key, value) in BaggagePropagator::new().extract(carrier).iter() {
+        span.set_baggage(key, value);
+    }
+}
+
+// Looks spans up in whichever `LookupSpan` subscriber the SDK was layered onto. The functions
+// are instantiated for that subscriber's type in `on_layer`, and found from a `Span` by
+// downcasting its dispatcher.
+#[derive(Clone, Copy)]
+struct WithContext {
+    extensions: fn(&Dispatch, &span::Id, ReadExtensions<'_>),
+    extensions_mut: fn(&Dispatch, &span::Id, WriteExtensions<'_>),
+}
+
+type ReadExtensions<'a> = &'a mut dyn FnMut(&Extensions<'_>);
+type WriteExtensions<'a> = &'a mut dyn FnMut(&mut ExtensionsMut<'_>);
+
+impl WithContext {
+    fn new<S>() -> WithContext
+        where S: tracing::Subscriber + for<'span> LookupSpan<'span>
+    {
+        WithContext {
+            extensions: |dispatch, id, f| {
+                if let Some(span) = dispatch.downcast_ref::<S>().and_then(|s| s.span(id)) {
+                    f(&span.extensions());
+                }
+            },
+            extensions_mut: |dispatch, id, f| {
+                if let Some(span) = dispatch.downcast_ref::<S>().and_then(|s| s.span(id)) {
+                    f(&mut span.extensions_mut());
+                }
+            },
+        }
+    }
+}
+
+// Read-only counterpart of `with_extensions_mut`, so concurrent readers don't contend.
+fn with_extensions<F, T>(span: &Span, f: F) -> Option<T>
+    where F: FnOnce(&Extensions<'_>) -> T
+{
+    span.with_subscriber(move |(id, dispatch)| {
+        let with_context = dispatch.downcast_ref::<WithContext>()?;
+        let (mut f, mut result) = (Some(f), None);
+        (with_context.extensions)(dispatch, id, &mut |extensions| {
+            result = f.take().map(|f| f(extensions));
+        });
+        result
+    })
+    .flatten()
+}
+
+// Runs `f` against the span's extensions. Returns `None` if the span isn't known to the
+// subscriber or the SDK isn't part of it.
+fn with_extensions_mut<F, T>(span: &Span, f: F) -> Option<T>
+    where F: FnOnce(&mut ExtensionsMut<'_>) -> T
+{
+    span.with_subscriber(move |(id, dispatch)| {
+        let with_context = dispatch.downcast_ref::<WithContext>()?;
+        let (mut f, mut result) = (Some(f), None);
+        (with_context.extensions_mut)(dispatch, id, &mut |extensions| {
+            result = f.take().map(|f| f(extensions));
+        });
+        result
+    })
+    .flatten()
+}
+
+// The ids of a span, recording or dropped by the sampler. Remote stand-ins have none.
+fn local_span_context(span: &Span) -> SpanContext {
+    with_extensions(span, local_context)
+        .flatten()
+        .unwrap_or_default()
+}
+
+// The context of a span created by the SDK, sampled or not.
+pub(crate) fn local_context(extensions: &Extensions<'_>) -> Option<SpanContext> {
+    if let Some(otel_span) = extensions.get::<OTelSpan>() {
+        return Some(otel_span.span_context());
+    }
+    extensions.get::<NonRecordingSpan>().map(|dropped| dropped.context)
+}
+
+/// Why a trace or span id, or an `uber-trace-id` header, couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The header didn't have the four `:`-separated fields; holds the number found.
+    FieldCount(usize),
+    /// A field wasn't valid hex.
+    InvalidHex { field: &'static str },
+    /// An id didn't have the expected number of hex digits.
+    InvalidLength { field: &'static str, expected: usize },
+    /// The trace or span id was zero, which marks an invalid context.
+    ZeroId,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::FieldCount(count) => write!(f, "expected 4 fields, found {count}"),
+            ParseError::InvalidHex { field } => write!(f, "{field} is not valid hex"),
+            ParseError::InvalidLength { field, expected } => {
+                write!(f, "{field} must be {expected} hex digits")
+            }
+            ParseError::ZeroId => f.write_str("trace id and span id must be non-zero"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a Jaeger `uber-trace-id` value, `{trace id}:{span id}:{parent span id}:{flags}`.
+/// The parent span id is deprecated in Jaeger and ignored.
+pub fn parse_jaeger_trace_id(
+    header_value: &str,
+) -> Result<(TraceId, SpanId, TraceFlags), ParseError> {
+    let parts: Vec<&str> = header_value.split(':').collect();
+    if parts.len() != 4 {
+        return Err(ParseError::FieldCount(parts.len()));
+    }
+
+    // Jaeger encodes both ids and the flags as hex.
+    let trace_id = u128::from_str_radix(parts[0], 16)
+        .map_err(|_| ParseError::InvalidHex { field: "trace id" })?;
+    let span_id = u64::from_str_radix(parts[1], 16)
+        .map_err(|_| ParseError::InvalidHex { field: "span id" })?;
+    let flags = u8::from_str_radix(parts[3], 16)
+        .map_err(|_| ParseError::InvalidHex { field: "flags" })?;
+    if trace_id == 0 || span_id == 0 {
+        return Err(ParseError::ZeroId);
+    }
+
+    Ok((TraceId(trace_id), SpanId(span_id), TraceFlags(flags)))
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{SpanId, TraceId, TraceState};
+    use crate::exporter::system_time_to_unix_nanos;
+
+    // Ids are written in their `Display` form.
+    impl Serialize for TraceId {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TraceId {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(D::Error::custom)
+        }
+    }
+
+    impl Serialize for SpanId {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SpanId {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(D::Error::custom)
+        }
+    }
+
+    // As the `tracestate` header value.
+    impl Serialize for TraceState {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.header_value())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TraceState {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(TraceState::from_header(&String::deserialize(deserializer)?))
+        }
+    }
+
+    pub(super) mod unix_nanos {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &SystemTime,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(system_time_to_unix_nanos(*time))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<SystemTime, D::Error> {
+            Ok(UNIX_EPOCH + Duration::from_nanos(u64::deserialize(deserializer)?))
+        }
+    }
+
+    pub(super) mod unix_nanos_or_zero {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<SystemTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(time.map_or(0, system_time_to_unix_nanos))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<SystemTime>, D::Error> {
+            let nanos = u64::deserialize(deserializer)?;
+            Ok((nanos != 0).then(|| UNIX_EPOCH + Duration::from_nanos(nanos)))
+        }
+    }
+
+    pub(super) fn recording() -> bool {
+        true
+    }
+}
@@ -0,0 +1,911 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    exporter::SpanExporter,
+    opentelemetry_sdk::{
+        AttributeValue, ConfigError, OTelSpanData, Resource, SpanId, TraceId, QUEUE_TIME_NS,
+    },
+};
+
+/// Hooks invoked by `OpenTelemetrySdk` as spans start and end.
+pub trait SpanProcessor {
+    /// Called when a span is created, if it was sampled.
+    fn on_start(&self, span: &OTelSpanData);
+
+    /// Called when a recording span is closed.
+    fn on_end(&self, span: &OTelSpanData);
+
+    /// Called instead of `on_end` for a started span that won't end as a recording span, because
+    /// a later unsampled parent stopped it recording or `set_remote_parent` replaced it.
+    fn on_drop(&self, _span: &OTelSpanData) {}
+
+    /// Exports any spans the processor is still holding on to.
+    fn force_flush(&self);
+
+    /// Flushes and releases the processor's resources. Spans ending afterwards are ignored.
+    fn shutdown(&self);
+
+    /// Passes the SDK's resource on to the processor's exporter.
+    fn set_resource(&mut self, _resource: &Resource) {}
+
+    /// Number of finished spans dropped instead of exported, e.g. because a queue was full.
+    fn dropped_spans(&self) -> u64 {
+        0
+    }
+
+    /// Checks the processor's configuration when the SDK is built, e.g. its batch sizes.
+    fn validate(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+/// Exports every finished span synchronously, one at a time, from `on_end`.
+pub struct SimpleSpanProcessor {
+    exporter: Box<dyn SpanExporter + Send + Sync>,
+    is_shutdown: AtomicBool,
+}
+
+impl SimpleSpanProcessor {
+    pub fn new(exporter: Box<dyn SpanExporter + Send + Sync>) -> SimpleSpanProcessor {
+        SimpleSpanProcessor {
+            exporter,
+            is_shutdown: AtomicBool::new(false),
+        }
+    }
+}
+
+impl SpanProcessor for SimpleSpanProcessor {
+    fn on_start(&self, _span: &OTelSpanData) {}
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if !span.is_recording || self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(err) = self.exporter.export(vec![span.clone()]) {
+            eprintln!("SimpleSpanProcessor: {err}");
+        }
+    }
+
+    fn force_flush(&self) {
+        self.exporter.force_flush();
+    }
+
+    fn shutdown(&self) {
+        if !self.is_shutdown.swap(true, Ordering::Relaxed) {
+            self.exporter.shutdown();
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporter.set_resource(resource);
+    }
+}
+
+/// Limits used by `BatchSpanProcessor`.
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    max_queue_size: usize,
+    max_export_batch_size: usize,
+    scheduled_delay: Duration,
+    sort_by_start_time: bool,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_queue_size: 2048,
+            max_export_batch_size: 512,
+            scheduled_delay: Duration::from_secs(5),
+            sort_by_start_time: false,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Spans ending while the queue holds this many are dropped.
+    pub fn with_max_queue_size(mut self, max_queue_size: usize) -> Self {
+        self.max_queue_size = max_queue_size;
+        self
+    }
+
+    /// At most `max_queue_size`.
+    pub fn with_max_export_batch_size(mut self, max_export_batch_size: usize) -> Self {
+        self.max_export_batch_size = max_export_batch_size;
+        self
+    }
+
+    /// How long a partial batch may wait before it is exported anyway.
+    pub fn with_scheduled_delay(mut self, scheduled_delay: Duration) -> Self {
+        self.scheduled_delay = scheduled_delay;
+        self
+    }
+
+    /// Order each batch by span start time, keeping the order spans ended in for equal start
+    /// times, instead of the order they ended in. Off by default.
+    pub fn with_sort_by_start_time(mut self, sort_by_start_time: bool) -> Self {
+        self.sort_by_start_time = sort_by_start_time;
+        self
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_queue_size == 0 || self.max_export_batch_size == 0 {
+            return Err(ConfigError::ZeroBatchLimit);
+        }
+        if self.max_export_batch_size > self.max_queue_size {
+            return Err(ConfigError::ExportBatchLargerThanQueue {
+                max_export_batch_size: self.max_export_batch_size,
+                max_queue_size: self.max_queue_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+enum BatchMessage {
+    Span(Box<OTelSpanData>),
+    Flush(SyncSender<()>),
+    SetResource(Resource),
+    Shutdown,
+}
+
+/// Queues finished spans and exports them in batches from a background thread.
+pub struct BatchSpanProcessor {
+    config: BatchConfig,
+    sender: SyncSender<BatchMessage>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    dropped: AtomicU64,
+    is_shutdown: AtomicBool,
+}
+
+impl BatchSpanProcessor {
+    pub fn new(
+        exporter: Box<dyn SpanExporter + Send + Sync>,
+        config: BatchConfig,
+    ) -> BatchSpanProcessor {
+        // Rejected by `validate`, but kept working for processors used outside the SDK.
+        let (sender, receiver) = mpsc::sync_channel(config.max_queue_size.max(1));
+        let worker_config = BatchConfig {
+            max_export_batch_size: config.max_export_batch_size.max(1),
+            ..config.clone()
+        };
+        let worker = thread::Builder::new()
+            .name("otel-batch-span-processor".to_string())
+            .spawn(move || run_batch_worker(exporter, receiver, worker_config))
+            .expect("failed to spawn the batch span processor thread");
+        BatchSpanProcessor {
+            config,
+            sender,
+            worker: Mutex::new(Some(worker)),
+            dropped: AtomicU64::new(0),
+            is_shutdown: AtomicBool::new(false),
+        }
+    }
+
+    /// Number of spans dropped because the queue was full.
+    pub fn dropped_spans(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl SpanProcessor for BatchSpanProcessor {
+    fn on_start(&self, _span: &OTelSpanData) {}
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if !span.is_recording || self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(TrySendError::Full(_)) = self
+            .sender
+            .try_send(BatchMessage::Span(Box::new(span.clone())))
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn force_flush(&self) {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        let (done, flushed) = mpsc::sync_channel(1);
+        if self.sender.send(BatchMessage::Flush(done)).is_ok() {
+            let _ = flushed.recv();
+        }
+    }
+
+    fn shutdown(&self) {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let _ = self.sender.send(BatchMessage::Shutdown);
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        let _ = self
+            .sender
+            .send(BatchMessage::SetResource(resource.clone()));
+    }
+
+    fn dropped_spans(&self) -> u64 {
+        BatchSpanProcessor::dropped_spans(self)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.config.validate()
+    }
+}
+
+impl Drop for BatchSpanProcessor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_batch_worker(
+    mut exporter: Box<dyn SpanExporter + Send + Sync>,
+    receiver: Receiver<BatchMessage>,
+    config: BatchConfig,
+) {
+    let mut batch = Vec::with_capacity(config.max_export_batch_size);
+    let mut deadline = Instant::now() + config.scheduled_delay;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(BatchMessage::Span(span)) => {
+                batch.push(*span);
+                if batch.len() >= config.max_export_batch_size {
+                    export_batch(exporter.as_ref(), &mut batch, &config);
+                    deadline = Instant::now() + config.scheduled_delay;
+                }
+            }
+            Ok(BatchMessage::Flush(done)) => {
+                export_batch(exporter.as_ref(), &mut batch, &config);
+                exporter.force_flush();
+                let _ = done.send(());
+            }
+            Ok(BatchMessage::SetResource(resource)) => exporter.set_resource(&resource),
+            Ok(BatchMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                export_batch(exporter.as_ref(), &mut batch, &config);
+                exporter.shutdown();
+                return;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                export_batch(exporter.as_ref(), &mut batch, &config);
+                deadline = Instant::now() + config.scheduled_delay;
+            }
+        }
+    }
+}
+
+fn export_batch(
+    exporter: &(dyn SpanExporter + Send + Sync),
+    batch: &mut Vec<OTelSpanData>,
+    config: &BatchConfig,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    if config.sort_by_start_time {
+        batch.sort_by_key(|span| span.start_time);
+    }
+    if let Err(err) = exporter.export(std::mem::take(batch)) {
+        eprintln!("BatchSpanProcessor: {err}");
+    }
+}
+
+/// Holds on to the spans of each trace until its local root span closes, then exports the whole
+/// trace only if `policy` returns true for it, e.g. to keep just the traces containing an error.
+///
+/// Traces whose root hasn't closed `timeout` after their first span ended are decided on the
+/// spans collected so far, when the next span ends or on `force_flush`. Decisions are remembered
+/// for another `timeout`, so spans of the trace ending in the meantime are exported or dropped
+/// with it. Exports happen synchronously from `on_end` and `force_flush`.
+pub struct TailSamplingProcessor {
+    exporter: Box<dyn SpanExporter + Send + Sync>,
+    policy: Box<TracePolicy>,
+    timeout: Duration,
+    clock: Box<dyn Clock + Send + Sync>,
+    traces: Mutex<TailTraces>,
+    is_shutdown: AtomicBool,
+}
+
+type TracePolicy = dyn Fn(&[OTelSpanData]) -> bool + Send + Sync;
+
+#[derive(Default)]
+struct TailTraces {
+    pending: HashMap<TraceId, PendingTrace>,
+    // Whether each recently decided trace was kept, and when it was decided.
+    decided: HashMap<TraceId, (bool, Instant)>,
+}
+
+struct PendingTrace {
+    first_seen: Instant,
+    spans: Vec<OTelSpanData>,
+}
+
+impl TailSamplingProcessor {
+    pub fn new<P>(exporter: Box<dyn SpanExporter + Send + Sync>, policy: P) -> TailSamplingProcessor
+    where
+        P: Fn(&[OTelSpanData]) -> bool + Send + Sync + 'static,
+    {
+        TailSamplingProcessor {
+            exporter,
+            policy: Box::new(policy),
+            timeout: Duration::from_secs(30),
+            clock: Box::new(SystemClock),
+            traces: Mutex::default(),
+            is_shutdown: AtomicBool::new(false),
+        }
+    }
+
+    /// How long to wait for a trace's root span before deciding on it anyway. 30s by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The clock timeouts are measured on. `SystemClock` by default.
+    pub fn with_clock<T>(mut self, clock: T) -> Self
+    where
+        T: Clock + Send + Sync + 'static,
+    {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    fn export(&self, spans: Vec<OTelSpanData>) {
+        if let Err(err) = self.exporter.export(spans) {
+            eprintln!("TailSamplingProcessor: {err}");
+        }
+    }
+
+    // Removes the traces waiting longer than the timeout, along with `trace_id` if its root
+    // just closed, and returns the spans of those the policy keeps.
+    fn take_ready(&self, closed_root: Option<TraceId>) -> Vec<Vec<OTelSpanData>> {
+        let mut traces = self.traces.lock().unwrap();
+        let now = self.clock.instant();
+        traces
+            .decided
+            .retain(|_, (_, decided_at)| now.duration_since(*decided_at) < self.timeout);
+        let expired: Vec<TraceId> = traces
+            .pending
+            .iter()
+            .filter(|(_, trace)| now.duration_since(trace.first_seen) >= self.timeout)
+            .map(|(trace_id, _)| *trace_id)
+            .chain(closed_root)
+            .collect();
+        let mut kept = Vec::new();
+        for trace_id in expired {
+            let Some(trace) = traces.pending.remove(&trace_id) else {
+                continue;
+            };
+            let keep = (self.policy)(&trace.spans);
+            traces.decided.insert(trace_id, (keep, now));
+            if keep {
+                kept.push(trace.spans);
+            }
+        }
+        kept
+    }
+}
+
+impl SpanProcessor for TailSamplingProcessor {
+    fn on_start(&self, _span: &OTelSpanData) {}
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if !span.is_recording || self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        let decided = {
+            let mut traces = self.traces.lock().unwrap();
+            match traces.decided.get(&span.trace_id) {
+                Some((keep, _)) => Some(*keep),
+                None => {
+                    let first_seen = self.clock.instant();
+                    traces
+                        .pending
+                        .entry(span.trace_id)
+                        .or_insert_with(|| PendingTrace {
+                            first_seen,
+                            spans: Vec::new(),
+                        })
+                        .spans
+                        .push(span.clone());
+                    None
+                }
+            }
+        };
+        // A late span of a trace already decided on follows that decision.
+        if decided == Some(true) {
+            self.export(vec![span.clone()]);
+        }
+        // Children close before their parents, so the trace is complete once its root closes.
+        let closed_root = (decided.is_none() && span.depth == 1).then_some(span.trace_id);
+        for spans in self.take_ready(closed_root) {
+            self.export(spans);
+        }
+    }
+
+    fn force_flush(&self) {
+        for spans in self.take_ready(None) {
+            self.export(spans);
+        }
+        self.exporter.force_flush();
+    }
+
+    /// Decides on the traces still waiting for their root before shutting the exporter down.
+    fn shutdown(&self) {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let pending = std::mem::take(&mut *self.traces.lock().unwrap()).pending;
+        for trace in pending.into_values() {
+            if (self.policy)(&trace.spans) {
+                self.export(trace.spans);
+            }
+        }
+        self.exporter.shutdown();
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporter.set_resource(resource);
+    }
+}
+
+/// Attribute set to `true` on spans `MaxDurationSpanProcessor` exported before they closed.
+pub const TIMED_OUT: &str = "otel.timed_out";
+
+/// Exports finished spans like `SimpleSpanProcessor`, and spans still open `max_span_duration`
+/// after they started, e.g. because their guard leaked, as if they had ended then, marked with
+/// `otel.timed_out = true`. Those carry the attributes they were started with, and aren't
+/// exported again when they do close.
+///
+/// Open spans are checked whenever a span starts or ends, on `force_flush`, and every
+/// `check_interval` from a background thread if one is set with `with_check_interval`.
+///
+/// The ids of the last 4096 timed out spans are remembered to skip them when they close. A
+/// span that closes after more than that many others timed out is exported a second time.
+pub struct MaxDurationSpanProcessor {
+    state: Arc<MaxDurationState>,
+    clock: Arc<dyn Clock + Send + Sync>,
+    check_interval: Option<Duration>,
+    // Started with the first span, once the processor is configured.
+    watchdog: Mutex<Option<Watchdog>>,
+}
+
+// What the watchdog thread shares with the processor.
+struct MaxDurationState {
+    exporter: Mutex<Box<dyn SpanExporter + Send + Sync>>,
+    max_span_duration: Duration,
+    spans: Mutex<OpenSpans>,
+    is_shutdown: AtomicBool,
+}
+
+struct Watchdog {
+    // Dropping it wakes the thread up to stop.
+    stop: SyncSender<()>,
+    handle: JoinHandle<()>,
+}
+
+const MAX_TIMED_OUT: usize = 4096;
+
+#[derive(Default)]
+struct OpenSpans {
+    open: HashMap<SpanId, OTelSpanData>,
+    // Exported before they closed, so they're skipped when they do. Oldest first in `order`.
+    timed_out: HashSet<SpanId>,
+    order: VecDeque<SpanId>,
+}
+
+impl OpenSpans {
+    fn time_out(&mut self, span_id: SpanId) {
+        if !self.timed_out.insert(span_id) {
+            return;
+        }
+        self.order.push_back(span_id);
+        if self.order.len() > MAX_TIMED_OUT {
+            if let Some(oldest) = self.order.pop_front() {
+                self.timed_out.remove(&oldest);
+            }
+        }
+    }
+
+    // Whether the span had timed out, forgetting it either way.
+    fn close(&mut self, span_id: SpanId) -> bool {
+        self.open.remove(&span_id);
+        let timed_out = self.timed_out.remove(&span_id);
+        if timed_out {
+            self.order.retain(|id| *id != span_id);
+        }
+        timed_out
+    }
+}
+
+impl MaxDurationSpanProcessor {
+    pub fn new(
+        exporter: Box<dyn SpanExporter + Send + Sync>,
+        max_span_duration: Duration,
+    ) -> MaxDurationSpanProcessor {
+        MaxDurationSpanProcessor {
+            state: Arc::new(MaxDurationState {
+                exporter: Mutex::new(exporter),
+                max_span_duration,
+                spans: Mutex::default(),
+                is_shutdown: AtomicBool::new(false),
+            }),
+            clock: Arc::new(SystemClock),
+            check_interval: None,
+            watchdog: Mutex::new(None),
+        }
+    }
+
+    /// The clock spans are timed against; should be the SDK's. `SystemClock` by default.
+    pub fn with_clock<T>(mut self, clock: T) -> Self
+    where
+        T: Clock + Send + Sync + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Also check open spans every `check_interval` from a background thread, so spans time out
+    /// when no other span starts or ends. Off by default.
+    pub fn with_check_interval(mut self, check_interval: Duration) -> Self {
+        self.check_interval = Some(check_interval);
+        self
+    }
+
+    fn start_watchdog(&self) {
+        let Some(check_interval) = self.check_interval else {
+            return;
+        };
+        let mut watchdog = self.watchdog.lock().unwrap();
+        if watchdog.is_some() {
+            return;
+        }
+        let (stop, stopped) = mpsc::sync_channel::<()>(0);
+        let (state, clock) = (Arc::clone(&self.state), Arc::clone(&self.clock));
+        let handle = thread::Builder::new()
+            .name("otel-max-duration-watchdog".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(check_interval) {
+                    state.export_timed_out(clock.as_ref());
+                }
+            })
+            .expect("failed to spawn the max duration watchdog thread");
+        *watchdog = Some(Watchdog { stop, handle });
+    }
+
+    fn stop_watchdog(&self) {
+        if let Some(watchdog) = self.watchdog.lock().unwrap().take() {
+            drop(watchdog.stop);
+            let _ = watchdog.handle.join();
+        }
+    }
+}
+
+impl MaxDurationState {
+    fn export(&self, spans: Vec<OTelSpanData>) {
+        if let Err(err) = self.exporter.lock().unwrap().export(spans) {
+            eprintln!("MaxDurationSpanProcessor: {err}");
+        }
+    }
+
+    fn export_timed_out(&self, clock: &(dyn Clock + Send + Sync)) {
+        let now = clock.now();
+        let timed_out: Vec<OTelSpanData> = {
+            let mut spans = self.spans.lock().unwrap();
+            let expired: Vec<SpanId> = spans
+                .open
+                .values()
+                .filter(|span| {
+                    now.duration_since(span.start_time).unwrap_or_default()
+                        >= self.max_span_duration
+                })
+                .map(|span| span.span_id)
+                .collect();
+            expired
+                .into_iter()
+                .filter_map(|span_id| {
+                    spans.time_out(span_id);
+                    spans.open.remove(&span_id)
+                })
+                .map(|mut span| {
+                    span.end_time = Some(now);
+                    span.attributes.insert(TIMED_OUT.to_string(), true.into());
+                    span
+                })
+                .collect()
+        };
+        if !timed_out.is_empty() {
+            self.export(timed_out);
+        }
+    }
+}
+
+impl SpanProcessor for MaxDurationSpanProcessor {
+    fn on_start(&self, span: &OTelSpanData) {
+        if self.state.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        self.start_watchdog();
+        self.state
+            .spans
+            .lock()
+            .unwrap()
+            .open
+            .insert(span.span_id, span.clone());
+        self.state.export_timed_out(self.clock.as_ref());
+    }
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if !span.is_recording || self.state.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        let timed_out = self.state.spans.lock().unwrap().close(span.span_id);
+        if !timed_out {
+            self.state.export(vec![span.clone()]);
+        }
+        self.state.export_timed_out(self.clock.as_ref());
+    }
+
+    fn on_drop(&self, span: &OTelSpanData) {
+        self.state.spans.lock().unwrap().close(span.span_id);
+    }
+
+    fn force_flush(&self) {
+        self.state.export_timed_out(self.clock.as_ref());
+        self.state.exporter.lock().unwrap().force_flush();
+    }
+
+    fn shutdown(&self) {
+        if !self.state.is_shutdown.swap(true, Ordering::Relaxed) {
+            self.stop_watchdog();
+            self.state.exporter.lock().unwrap().shutdown();
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.state.exporter.lock().unwrap().set_resource(resource);
+    }
+}
+
+impl Drop for MaxDurationSpanProcessor {
+    fn drop(&mut self) {
+        self.stop_watchdog();
+    }
+}
+
+/// Passes only the spans without a local parent, the entry points of each trace into this
+/// service, on to `inner`, e.g. to export just top-level latency. Their children still carry
+/// the trace context, but `inner` never sees them.
+pub struct RootSpanProcessor<P> {
+    inner: P,
+}
+
+impl<P: SpanProcessor> RootSpanProcessor<P> {
+    pub fn new(inner: P) -> RootSpanProcessor<P> {
+        RootSpanProcessor { inner }
+    }
+}
+
+fn is_local_root(span: &OTelSpanData) -> bool {
+    span.parent_span_id.is_none() || span.parent_is_remote
+}
+
+impl<P: SpanProcessor> SpanProcessor for RootSpanProcessor<P> {
+    fn on_start(&self, span: &OTelSpanData) {
+        if is_local_root(span) {
+            self.inner.on_start(span);
+        }
+    }
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if is_local_root(span) {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn on_drop(&self, span: &OTelSpanData) {
+        if is_local_root(span) {
+            self.inner.on_drop(span);
+        }
+    }
+
+    fn force_flush(&self) {
+        self.inner.force_flush();
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown();
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+
+    fn dropped_spans(&self) -> u64 {
+        self.inner.dropped_spans()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.inner.validate()
+    }
+}
+
+/// Attribute set on a span `DeduplicatingProcessor` coalesced, counting the spans it stands for.
+pub const REPEAT_COUNT: &str = "repeat_count";
+
+/// Collapses consecutive sibling spans of a trace with the same name and attributes, e.g. the
+/// attempts of a retry loop, into the first of them, passed on to `inner` with a
+/// `repeat_count` attribute and the end time of the last.
+///
+/// Holds the last ended span of each trace until a different span ends in it, its local root
+/// ends, or the processor is flushed. Spans are held for at most `capacity` traces, 1024 by
+/// default, flushing the oldest beyond that.
+pub struct DeduplicatingProcessor<P> {
+    inner: P,
+    capacity: usize,
+    pending: Mutex<PendingRuns>,
+}
+
+#[derive(Default)]
+struct PendingRuns {
+    runs: HashMap<TraceId, Run>,
+    // Oldest first, for flushing beyond the capacity.
+    order: VecDeque<TraceId>,
+}
+
+// The first span of a run of repeats and how many spans the run holds.
+struct Run {
+    span: OTelSpanData,
+    count: i64,
+}
+
+impl Run {
+    fn repeats(&self, span: &OTelSpanData) -> bool {
+        self.span.name == span.name
+            && self.span.parent_span_id == span.parent_span_id
+            && compared_attributes(&self.span) == compared_attributes(span)
+    }
+
+    fn into_span(self) -> OTelSpanData {
+        let mut span = self.span;
+        if self.count > 1 {
+            span.attributes
+                .insert(REPEAT_COUNT.to_string(), self.count.into());
+        }
+        span
+    }
+}
+
+// The attributes repeats must share. Spans entered after a delay record it, which is expected to
+// differ between repeats.
+fn compared_attributes(span: &OTelSpanData) -> HashMap<&str, &AttributeValue> {
+    span.attributes
+        .iter()
+        .filter(|(key, _)| key.as_str() != QUEUE_TIME_NS)
+        .map(|(key, value)| (key.as_str(), value))
+        .collect()
+}
+
+impl PendingRuns {
+    fn take(&mut self, trace_id: TraceId) -> Option<Run> {
+        let run = self.runs.remove(&trace_id)?;
+        self.order.retain(|pending| *pending != trace_id);
+        Some(run)
+    }
+}
+
+impl<P: SpanProcessor> DeduplicatingProcessor<P> {
+    pub fn new(inner: P) -> DeduplicatingProcessor<P> {
+        DeduplicatingProcessor {
+            inner,
+            capacity: 1024,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// How many traces to hold a pending span for.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    // Adds the span to its trace's run, returning the spans ready to be passed on.
+    fn coalesce(&self, span: &OTelSpanData) -> Vec<OTelSpanData> {
+        let mut pending = self.pending.lock().unwrap();
+        let mut ready = Vec::new();
+        if let Some(run) = pending.runs.get_mut(&span.trace_id) {
+            if run.repeats(span) {
+                run.count += 1;
+                run.span.end_time = span.end_time;
+                return ready;
+            }
+        }
+        ready.extend(pending.take(span.trace_id).map(Run::into_span));
+        if is_local_root(span) {
+            ready.push(span.clone());
+            return ready;
+        }
+        pending.runs.insert(
+            span.trace_id,
+            Run {
+                span: span.clone(),
+                count: 1,
+            },
+        );
+        pending.order.push_back(span.trace_id);
+        if pending.order.len() > self.capacity {
+            if let Some(oldest) = pending.order.pop_front() {
+                ready.extend(pending.runs.remove(&oldest).map(Run::into_span));
+            }
+        }
+        ready
+    }
+
+    fn flush_pending(&self) {
+        let runs: Vec<Run> = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.order.clear();
+            pending.runs.drain().map(|(_, run)| run).collect()
+        };
+        for run in runs {
+            self.inner.on_end(&run.into_span());
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for DeduplicatingProcessor<P> {
+    fn on_start(&self, span: &OTelSpanData) {
+        self.inner.on_start(span);
+    }
+
+    fn on_end(&self, span: &OTelSpanData) {
+        if !span.is_recording {
+            self.inner.on_end(span);
+            return;
+        }
+        for span in self.coalesce(span) {
+            self.inner.on_end(&span);
+        }
+    }
+
+    fn on_drop(&self, span: &OTelSpanData) {
+        self.inner.on_drop(span);
+    }
+
+    fn force_flush(&self) {
+        self.flush_pending();
+        self.inner.force_flush();
+    }
+
+    fn shutdown(&self) {
+        self.flush_pending();
+        self.inner.shutdown();
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+
+    fn dropped_spans(&self) -> u64 {
+        self.inner.dropped_spans()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.inner.validate()
+    }
+}
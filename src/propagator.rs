@@ -1,2 +1,641 @@
+use std::collections::HashMap;
 
-struct OtelPropagator;
+use http::{header::HeaderName, HeaderMap, HeaderValue};
+#[cfg(feature = "tonic")]
+use tonic::metadata::{KeyRef, MetadataKey, MetadataMap, MetadataValue};
+use tracing::Span;
+
+use crate::opentelemetry_sdk::{
+    default_propagator, parse_jaeger_trace_id, Baggage, OTelSpan, OtelSpanExt,
+    RemoteSpanContext, SpanContext, SpanId, TraceFlags, TraceId, TraceState,
+};
+
+/// A carrier propagators write headers into.
+pub trait Injector {
+    fn set(&mut self, key: &str, value: String);
+}
+
+/// A carrier propagators read headers from. Keys are matched case-insensitively.
+pub trait Extractor {
+    fn get(&self, key: &str) -> Option<&str>;
+
+    fn keys(&self) -> Vec<&str>;
+}
+
+impl Injector for HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        let name = HeaderName::from_bytes(key.as_bytes());
+        let value = HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl Extractor for HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        HeaderMap::get(self, key)?.to_str().ok()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        HeaderMap::keys(self).map(HeaderName::as_str).collect()
+    }
+}
+
+// Trace headers are ASCII, so binary (`-bin`) metadata is neither written nor read.
+#[cfg(feature = "tonic")]
+impl Injector for MetadataMap {
+    fn set(&mut self, key: &str, value: String) {
+        let key = MetadataKey::from_bytes(key.to_lowercase().as_bytes());
+        let value = MetadataValue::try_from(value);
+        if let (Ok(key), Ok(value)) = (key, value) {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(feature = "tonic")]
+impl Extractor for MetadataMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        MetadataMap::get(self, key.to_lowercase())?.to_str().ok()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        MetadataMap::keys(self)
+            .filter_map(|key| match key {
+                KeyRef::Ascii(key) => Some(key.as_str()),
+                KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+// Keys are stored lowercased to match the case-insensitive semantics of HTTP headers.
+impl Injector for HashMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_lowercase(), value);
+    }
+}
+
+impl Extractor for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, &key.to_lowercase()).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        HashMap::keys(self).map(String::as_str).collect()
+    }
+}
+
+/// A propagator that carries span context in text headers, implemented by the W3C, B3 and
+/// Jaeger propagators so they can be combined in a [`CompositePropagator`].
+pub trait TextMapPropagator {
+    /// Writes the context of `span` into `injector`.
+    fn inject(&self, span: &Span, injector: &mut dyn Injector);
+
+    /// Reads a remote span context from `extractor`, `None` if it holds no valid one.
+    fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext>;
+
+    /// The header names the propagator reads and writes, lowercase, e.g. to allow them in
+    /// `Access-Control-Allow-Headers`.
+    fn fields(&self) -> &[&'static str];
+
+    /// Like `extract`, also telling whether the carrier held other contexts that differ from
+    /// the one returned. Only [`CompositePropagator`] reads more than one.
+    fn extract_checked(&self, extractor: &dyn Extractor) -> Option<Extracted> {
+        self.extract(extractor).map(|context| Extracted {
+            context,
+            conflict: false,
+            trace_state: TraceState::default(),
+        })
+    }
+}
+
+/// A context read by `TextMapPropagator::extract_checked`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extracted {
+    pub context: SpanContext,
+    /// The carrier also held a context of another trace or span, e.g. in a header of another
+    /// format, which was ignored.
+    pub conflict: bool,
+    /// The W3C trace state sent along with the context, empty if there was none.
+    pub trace_state: TraceState,
+}
+
+/// Attribute set to `true` on spans whose parent was extracted from headers holding
+/// conflicting contexts, see [`Extracted::conflict`].
+pub const CONTEXT_CONFLICT: &str = "context.conflict";
+
+/// Reads the caller's context from the headers of `request` with the propagator of the
+/// installed `OpenTelemetrySdk`. `None` if the headers hold no valid context or no SDK is
+/// installed.
+pub fn extract_context<B>(request: &http::Request<B>) -> Option<RemoteSpanContext> {
+    default_propagator()?
+        .extract(request.headers())
+        .map(RemoteSpanContext::from)
+}
+
+/// Writes the context of `span` into the headers of `response` with the propagator of its
+/// `OpenTelemetrySdk`, e.g. for clients that log the trace of their requests.
+pub fn inject_context<B>(span: &Span, response: &mut http::Response<B>) {
+    span.inject_into(response.headers_mut());
+}
+
+const JAEGER_HEADER: &str = "uber-trace-id";
+const JAEGER_BAGGAGE_PREFIX: &str = "uberctx-";
+const BAGGAGE_HEADER: &str = "baggage";
+
+const TRACEPARENT_HEADER: &str = "traceparent";
+const TRACESTATE_HEADER: &str = "tracestate";
+const SUPPORTED_VERSION: u8 = 0;
+
+const B3_SINGLE_HEADER: &str = "b3";
+const B3_TRACE_ID_HEADER: &str = "x-b3-traceid";
+const B3_SPAN_ID_HEADER: &str = "x-b3-spanid";
+const B3_SAMPLED_HEADER: &str = "x-b3-sampled";
+const B3_PARENT_SPAN_ID_HEADER: &str = "x-b3-parentspanid";
+const B3_FLAGS_HEADER: &str = "x-b3-flags";
+
+/// Propagates span context using the W3C Trace Context `traceparent` header,
+/// `00-{32 hex trace id}-{16 hex span id}-{02 hex flags}`.
+#[derive(Debug, Default)]
+pub struct W3CTraceContextPropagator;
+
+impl W3CTraceContextPropagator {
+    pub fn new() -> W3CTraceContextPropagator {
+        W3CTraceContextPropagator
+    }
+
+    /// Builds the `traceparent` value for a span.
+    pub fn traceparent(&self, span: &OTelSpan) -> String {
+        format_traceparent(span.span_context())
+    }
+
+    /// Parses a `traceparent` value, returning `None` if it is malformed or carries invalid ids.
+    pub fn parse_traceparent(&self, value: &str) -> Option<(TraceId, SpanId, TraceFlags)> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        if parts.len() < 4 {
+            return None;
+        }
+
+        let version = parse_hex_field(parts[0], 2).map(|v| v as u8)?;
+        // Version ff is invalid, and version 00 must have exactly four fields. Later versions
+        // may append fields we don't understand, which are ignored.
+        if version == 0xff || (version == SUPPORTED_VERSION && parts.len() != 4) {
+            return None;
+        }
+
+        let trace_id: TraceId = parts[1].parse().ok()?;
+        let span_id: SpanId = parts[2].parse().ok()?;
+        let flags = parse_hex_field(parts[3], 2)? as u8;
+        if trace_id.0 == 0 || span_id.0 == 0 {
+            return None;
+        }
+
+        Some((trace_id, span_id, TraceFlags(flags)))
+    }
+
+    /// Writes the `traceparent` header for `span` into `injector`, followed by `tracestate` if
+    /// the span carries one. Spans without OTel data are skipped.
+    pub fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        let Some(context) = span.span_context() else {
+            return;
+        };
+        injector.set(TRACEPARENT_HEADER, format_traceparent(context));
+        let trace_state = span.trace_state();
+        if !trace_state.is_empty() {
+            injector.set(TRACESTATE_HEADER, trace_state.header_value());
+        }
+    }
+
+    /// Reads the remote span context from the `traceparent` header, if present and valid.
+    pub fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let (trace_id, span_id, flags) =
+            self.parse_traceparent(extractor.get(TRACEPARENT_HEADER)?)?;
+        Some(SpanContext::new_remote(trace_id, span_id, flags))
+    }
+
+    /// Reads the `tracestate` header, dropping invalid members. Only meaningful alongside a
+    /// valid `traceparent`; pass it to `OtelSpanExt::set_trace_state`.
+    pub fn extract_trace_state(&self, extractor: &dyn Extractor) -> TraceState {
+        extractor
+            .get(TRACESTATE_HEADER)
+            .map(TraceState::from_header)
+            .unwrap_or_default()
+    }
+}
+
+impl TextMapPropagator for W3CTraceContextPropagator {
+    fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        W3CTraceContextPropagator::inject(self, span, injector)
+    }
+
+    fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        W3CTraceContextPropagator::extract(self, extractor)
+    }
+
+    fn fields(&self) -> &[&'static str] {
+        &[TRACEPARENT_HEADER, TRACESTATE_HEADER]
+    }
+
+    fn extract_checked(&self, extractor: &dyn Extractor) -> Option<Extracted> {
+        self.extract(extractor).map(|context| Extracted {
+            context,
+            conflict: false,
+            trace_state: self.extract_trace_state(extractor),
+        })
+    }
+}
+
+/// Which B3 header style [`B3Propagator`] injects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum B3Encoding {
+    /// `b3: {trace id}-{span id}-{sampled}-{parent span id}`
+    SingleHeader,
+    /// `X-B3-TraceId`, `X-B3-SpanId`, `X-B3-Sampled` and `X-B3-ParentSpanId`
+    #[default]
+    MultipleHeader,
+    /// Both of the above.
+    SingleAndMultipleHeader,
+}
+
+/// Propagates span context using Zipkin's B3 headers. Extraction accepts either encoding,
+/// preferring the single header when both are present.
+#[derive(Debug, Default)]
+pub struct B3Propagator {
+    encoding: B3Encoding,
+}
+
+impl B3Propagator {
+    pub fn new() -> B3Propagator {
+        B3Propagator::default()
+    }
+
+    pub fn with_encoding(encoding: B3Encoding) -> B3Propagator {
+        B3Propagator { encoding }
+    }
+
+    /// Writes the B3 headers for `span` into `injector`. Spans without OTel data are skipped.
+    pub fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        let Some((trace_id, span_id, sampled, parent_span_id)) = span.span_context().map(b3_fields)
+        else {
+            return;
+        };
+
+        if self.encoding != B3Encoding::MultipleHeader {
+            let mut value = format!("{trace_id}-{span_id}-{sampled}");
+            if let Some(parent_span_id) = &parent_span_id {
+                value.push('-');
+                value.push_str(parent_span_id);
+            }
+            injector.set(B3_SINGLE_HEADER, value);
+        }
+        if self.encoding != B3Encoding::SingleHeader {
+            injector.set(B3_TRACE_ID_HEADER, trace_id);
+            injector.set(B3_SPAN_ID_HEADER, span_id);
+            injector.set(B3_SAMPLED_HEADER, sampled.to_string());
+            if let Some(parent_span_id) = parent_span_id {
+                injector.set(B3_PARENT_SPAN_ID_HEADER, parent_span_id);
+            }
+        }
+    }
+
+    /// Reads the remote span context from either B3 encoding, if present and valid.
+    pub fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        match extractor.get(B3_SINGLE_HEADER) {
+            Some(single) => self.extract_single_header(single),
+            None => self.extract_multiple_header(extractor),
+        }
+    }
+
+    fn extract_single_header(&self, value: &str) -> Option<SpanContext> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        // A lone sampling state carries no context to continue.
+        if parts.len() < 2 || parts.len() > 4 {
+            return None;
+        }
+        let trace_id = parse_b3_trace_id(parts[0])?;
+        let span_id = parse_b3_span_id(parts[1])?;
+        let flags = match parts.get(2) {
+            Some(sampled) => parse_b3_sampled(sampled)?,
+            None => TraceFlags::SAMPLED,
+        };
+        if let Some(parent_span_id) = parts.get(3) {
+            parse_b3_span_id(parent_span_id)?;
+        }
+        Some(SpanContext::new_remote(trace_id, span_id, flags))
+    }
+
+    fn extract_multiple_header(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let trace_id = parse_b3_trace_id(extractor.get(B3_TRACE_ID_HEADER)?)?;
+        let span_id = parse_b3_span_id(extractor.get(B3_SPAN_ID_HEADER)?)?;
+        // The debug flag implies sampling.
+        let flags = if extractor.get(B3_FLAGS_HEADER) == Some("1") {
+            TraceFlags::SAMPLED
+        } else {
+            match extractor.get(B3_SAMPLED_HEADER) {
+                Some(sampled) => parse_b3_sampled(sampled)?,
+                None => TraceFlags::SAMPLED,
+            }
+        };
+        Some(SpanContext::new_remote(trace_id, span_id, flags))
+    }
+}
+
+impl TextMapPropagator for B3Propagator {
+    fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        B3Propagator::inject(self, span, injector)
+    }
+
+    fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        B3Propagator::extract(self, extractor)
+    }
+
+    fn fields(&self) -> &[&'static str] {
+        &[
+            B3_SINGLE_HEADER,
+            B3_TRACE_ID_HEADER,
+            B3_SPAN_ID_HEADER,
+            B3_SAMPLED_HEADER,
+            B3_PARENT_SPAN_ID_HEADER,
+            B3_FLAGS_HEADER,
+        ]
+    }
+}
+
+/// Propagates span context using Jaeger's `uber-trace-id` header,
+/// `{trace id}:{span id}:{parent span id}:{flags}`.
+#[derive(Debug, Default)]
+pub struct JaegerPropagator;
+
+impl JaegerPropagator {
+    pub fn new() -> JaegerPropagator {
+        JaegerPropagator
+    }
+
+    /// Writes the `uber-trace-id` header for `span` into `injector`. Spans without OTel data are
+    /// skipped.
+    pub fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        if span.span_context().is_some() {
+            injector.set(JAEGER_HEADER, span.extract_jaeger_propagation());
+        }
+    }
+
+    /// Reads the remote span context from the `uber-trace-id` header, if present and valid.
+    pub fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let (trace_id, span_id, flags) =
+            parse_jaeger_trace_id(extractor.get(JAEGER_HEADER)?).ok()?;
+        Some(SpanContext::new_remote(trace_id, span_id, flags))
+    }
+}
+
+impl TextMapPropagator for JaegerPropagator {
+    fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        JaegerPropagator::inject(self, span, injector)
+    }
+
+    fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        JaegerPropagator::extract(self, extractor)
+    }
+
+    fn fields(&self) -> &[&'static str] {
+        &[JAEGER_HEADER]
+    }
+}
+
+/// Combines several propagators: every one of them injects, and extraction takes the context
+/// from the first that finds one, e.g. to accept both `traceparent` and `uber-trace-id`. Order
+/// them by precedence: with W3C first, a `traceparent` wins over an `uber-trace-id` for a
+/// different trace, which `extract_checked` reports as a conflict.
+#[derive(Default)]
+pub struct CompositePropagator {
+    propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>>,
+    // Every propagator's fields, without duplicates.
+    fields: Vec<&'static str>,
+}
+
+impl CompositePropagator {
+    pub fn new(propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>>) -> CompositePropagator {
+        let mut fields: Vec<&'static str> = Vec::new();
+        for field in propagators
+            .iter()
+            .flat_map(|propagator| propagator.fields())
+        {
+            if !fields.contains(field) {
+                fields.push(field);
+            }
+        }
+        CompositePropagator {
+            propagators,
+            fields,
+        }
+    }
+
+    /// Runs every propagator's `inject`, in order.
+    pub fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        for propagator in &self.propagators {
+            propagator.inject(span, injector);
+        }
+    }
+
+    /// The context found by the first propagator, in order, that finds one.
+    pub fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        self.propagators
+            .iter()
+            .find_map(|propagator| propagator.extract(extractor))
+    }
+}
+
+impl TextMapPropagator for CompositePropagator {
+    fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        CompositePropagator::inject(self, span, injector)
+    }
+
+    fn extract(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        CompositePropagator::extract(self, extractor)
+    }
+
+    fn fields(&self) -> &[&'static str] {
+        &self.fields
+    }
+
+    // Every propagator extracts, so contexts found by lower precedence ones can be compared.
+    fn extract_checked(&self, extractor: &dyn Extractor) -> Option<Extracted> {
+        let mut extracted = self
+            .propagators
+            .iter()
+            .filter_map(|propagator| propagator.extract_checked(extractor));
+        let mut first = extracted.next()?;
+        for other in extracted {
+            let same_trace = other.context.trace_id == first.context.trace_id;
+            first.conflict |=
+                other.conflict || !same_trace || other.context.span_id != first.context.span_id;
+            // e.g. the `tracestate` of a `traceparent` read after an `uber-trace-id`.
+            if first.trace_state.is_empty() && same_trace {
+                first.trace_state = other.trace_state;
+            }
+        }
+        Some(first)
+    }
+}
+
+/// Which header style [`BaggagePropagator`] injects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BaggageEncoding {
+    /// W3C `baggage: key1=value1,key2=value2`
+    #[default]
+    W3C,
+    /// Jaeger `uberctx-{key}: value`, one header per entry.
+    Jaeger,
+}
+
+/// Propagates span baggage using the W3C `baggage` header or Jaeger's `uberctx-` prefixed
+/// headers. Extraction reads both.
+#[derive(Debug, Default)]
+pub struct BaggagePropagator {
+    encoding: BaggageEncoding,
+}
+
+impl BaggagePropagator {
+    pub fn new() -> BaggagePropagator {
+        BaggagePropagator::default()
+    }
+
+    pub fn with_encoding(encoding: BaggageEncoding) -> BaggagePropagator {
+        BaggagePropagator { encoding }
+    }
+
+    /// Writes the baggage of `span` into `injector`. Nothing is written for empty baggage.
+    pub fn inject(&self, span: &Span, injector: &mut dyn Injector) {
+        let baggage = span.baggage();
+        if baggage.is_empty() {
+            return;
+        }
+
+        match self.encoding {
+            BaggageEncoding::W3C => {
+                let value = baggage
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key.trim(), percent_encode(value)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                injector.set(BAGGAGE_HEADER, value);
+            }
+            BaggageEncoding::Jaeger => {
+                for (key, value) in baggage.iter() {
+                    injector.set(&format!("{JAEGER_BAGGAGE_PREFIX}{key}"), percent_encode(value));
+                }
+            }
+        }
+    }
+
+    /// Reads baggage from the W3C `baggage` header and any `uberctx-` headers. Malformed
+    /// members are skipped.
+    pub fn extract(&self, extractor: &dyn Extractor) -> Baggage {
+        let mut baggage = Baggage::new();
+        if let Some(header) = extractor.get(BAGGAGE_HEADER) {
+            for member in header.split(',') {
+                // Properties after ';' are not supported and are dropped.
+                let entry = member.split(';').next().unwrap_or_default();
+                if let Some((key, value)) = entry.split_once('=') {
+                    let key = key.trim();
+                    if !key.is_empty() {
+                        baggage.insert(key, percent_decode(value.trim()));
+                    }
+                }
+            }
+        }
+        for key in extractor.keys() {
+            if let Some(baggage_key) = key.to_lowercase().strip_prefix(JAEGER_BAGGAGE_PREFIX) {
+                if let Some(value) = extractor.get(key) {
+                    baggage.insert(baggage_key, percent_decode(value.trim()));
+                }
+            }
+        }
+        baggage
+    }
+}
+
+// Percent-encodes everything outside the unreserved URL characters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+// Decodes %XX escapes, leaving malformed escapes as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn b3_fields(context: SpanContext) -> (String, String, &'static str, Option<String>) {
+    (
+        context.trace_id.to_string(),
+        context.span_id.to_string(),
+        if context.flags.is_sampled() { "1" } else { "0" },
+        context.parent_span_id.map(|parent| parent.to_string()),
+    )
+}
+
+// B3 trace ids are 64 or 128 bit; a 64-bit id occupies the lower half of our TraceId.
+fn parse_b3_trace_id(value: &str) -> Option<TraceId> {
+    let value = value.trim();
+    let trace_id = match value.len() {
+        16 => TraceId(u128::from(value.parse::<SpanId>().ok()?.0)),
+        _ => value.parse::<TraceId>().ok()?,
+    };
+    (trace_id.0 != 0).then_some(trace_id)
+}
+
+fn parse_b3_span_id(value: &str) -> Option<SpanId> {
+    let span_id: SpanId = value.trim().parse().ok()?;
+    (span_id.0 != 0).then_some(span_id)
+}
+
+fn parse_b3_sampled(value: &str) -> Option<TraceFlags> {
+    match value.trim() {
+        "1" | "d" | "true" => Some(TraceFlags::SAMPLED),
+        "0" | "false" => Some(TraceFlags::default()),
+        _ => None,
+    }
+}
+
+fn format_traceparent(context: SpanContext) -> String {
+    format!(
+        "{:02x}-{}-{}-{:02x}",
+        SUPPORTED_VERSION, context.trace_id, context.span_id, context.flags.0
+    )
+}
+
+// Parses a fixed-width lowercase hex field. Ids go through their `FromStr` impls instead.
+fn parse_hex_field(value: &str, len: usize) -> Option<u128> {
+    if value.len() != len || !value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    u128::from_str_radix(value, 16).ok()
+}
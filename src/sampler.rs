@@ -0,0 +1,548 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Instant,
+};
+
+use tracing::Level;
+
+use crate::clock::{Clock, SystemClock};
+use crate::opentelemetry_sdk::{AttributeValue, ConfigError, SpanContext, TraceId, TraceState};
+
+/// Everything a sampler can base its decision on.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplingContext<'a> {
+    /// The parent's context, with its sampled flag set when the parent is recording. `None`
+    /// for root spans.
+    pub parent: Option<SpanContext>,
+    pub trace_id: TraceId,
+    pub name: &'a str,
+    /// The `tracing` target the span was created under, its module path by default.
+    pub target: &'a str,
+    pub level: Level,
+    /// The attributes recorded when the span was created. Always empty for samplers whose
+    /// `uses_attributes` returns false.
+    pub attributes: &'a HashMap<String, AttributeValue>,
+    /// The trace state the span inherits from its parent, empty for root spans.
+    pub trace_state: &'a TraceState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The span is not recorded.
+    Drop,
+    /// The span is recorded and exported.
+    RecordAndSample,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplingResult {
+    pub decision: Decision,
+    /// Extra attributes the sampler wants attached to the span.
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// Replaces the trace state the span inherits, if set.
+    pub trace_state: Option<TraceState>,
+}
+
+impl SamplingResult {
+    pub fn new(decision: Decision) -> SamplingResult {
+        SamplingResult {
+            decision,
+            attributes: Vec::new(),
+            trace_state: None,
+        }
+    }
+
+    pub fn with_trace_state(mut self, trace_state: TraceState) -> Self {
+        self.trace_state = Some(trace_state);
+        self
+    }
+
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<AttributeValue>,
+    ) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.decision == Decision::RecordAndSample
+    }
+}
+
+impl From<Decision> for SamplingResult {
+    fn from(decision: Decision) -> Self {
+        SamplingResult::new(decision)
+    }
+}
+
+impl From<bool> for SamplingResult {
+    fn from(sampled: bool) -> Self {
+        let decision = if sampled {
+            Decision::RecordAndSample
+        } else {
+            Decision::Drop
+        };
+        SamplingResult::new(decision)
+    }
+}
+
+pub trait ShouldSample {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult;
+
+    /// Whether `should_sample` reads `SamplingContext::attributes`. Returning false spares
+    /// recording the attributes of spans the sampler drops.
+    fn uses_attributes(&self) -> bool {
+        true
+    }
+
+    /// Checks the sampler's configuration when the SDK is built, e.g. that its ratio is within
+    /// `0.0..=1.0`.
+    fn validate(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+fn validate_ratio(ratio: f64) -> Result<(), ConfigError> {
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidSamplingRatio(ratio))
+    }
+}
+
+/// Samples every span. The default.
+pub struct AlwaysOn;
+
+/// The previous name of [`AlwaysOn`].
+#[deprecated(note = "renamed to `AlwaysOn`")]
+pub type OTelSampler = AlwaysOn;
+
+impl ShouldSample for AlwaysOn {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        Decision::RecordAndSample.into()
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+}
+
+/// Drops every span.
+pub struct AlwaysOff;
+
+impl ShouldSample for AlwaysOff {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        Decision::Drop.into()
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+}
+
+/// Forces a decision for spans whose target or name starts with one of the configured
+/// prefixes, e.g. never sampling `health` checks, and asks the wrapped sampler for the rest.
+/// When several prefixes match, the longest wins.
+pub struct TargetOverrideSampler<S: ShouldSample> {
+    base: S,
+    // Longest prefix first.
+    overrides: Vec<(String, Decision)>,
+}
+
+impl<S: ShouldSample> TargetOverrideSampler<S> {
+    pub fn new(base: S) -> TargetOverrideSampler<S> {
+        TargetOverrideSampler {
+            base,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Makes `decision` for spans whose target or name starts with `prefix`.
+    pub fn with_override(mut self, prefix: impl Into<String>, decision: Decision) -> Self {
+        let prefix = prefix.into();
+        self.overrides.retain(|(existing, _)| *existing != prefix);
+        self.overrides.push((prefix, decision));
+        self.overrides
+            .sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        self
+    }
+}
+
+impl<S: ShouldSample> ShouldSample for TargetOverrideSampler<S> {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        let forced = self.overrides.iter().find(|(prefix, _)| {
+            ctx.target.starts_with(prefix.as_str()) || ctx.name.starts_with(prefix.as_str())
+        });
+        match forced {
+            Some((_, decision)) => (*decision).into(),
+            None => self.base.should_sample(ctx),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.base.validate()
+    }
+
+    fn uses_attributes(&self) -> bool {
+        self.base.uses_attributes()
+    }
+}
+
+/// Samples the given ratio of traces, deciding on the trace id so every span of a trace makes
+/// the same decision.
+pub struct TraceIdRatioBased {
+    ratio: f64,
+}
+
+impl TraceIdRatioBased {
+    pub fn new(ratio: f64) -> TraceIdRatioBased {
+        TraceIdRatioBased { ratio }
+    }
+}
+
+impl ShouldSample for TraceIdRatioBased {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        trace_id_ratio_sample(&ctx.trace_id, self.ratio).into()
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        validate_ratio(self.ratio)
+    }
+}
+
+/// Samples the given ratio of traces using OTel's consistent probability sampling, so services
+/// sampling at different ratios still keep whole traces. The decision compares the trace's
+/// randomness, the `rv` value in the `ot` member of `tracestate` or else the low 56 bits of the
+/// trace id, against a rejection threshold. Spans that inherit a threshold in `th` use it;
+/// others use the one for the ratio and, if sampled, record it in `th` for services downstream.
+pub struct ConsistentProbabilitySampler {
+    // `None` for a ratio of 0, which no threshold encodes.
+    threshold: Option<u64>,
+    // As given, before clamping, for `validate`.
+    ratio: f64,
+}
+
+// The `tracestate` member holding OTel's sampling values.
+const OT_TRACE_STATE_KEY: &str = "ot";
+// Thresholds and randomness values are 56 bit, written as up to 14 hex digits.
+const MAX_THRESHOLD: u64 = 1 << 56;
+const THRESHOLD_DIGITS: usize = 14;
+
+impl ConsistentProbabilitySampler {
+    pub fn new(ratio: f64) -> ConsistentProbabilitySampler {
+        let clamped = ratio.clamp(0.0, 1.0);
+        let threshold = (clamped > 0.0)
+            .then(|| ((1.0 - clamped) * MAX_THRESHOLD as f64).round() as u64)
+            .map(|threshold| threshold.min(MAX_THRESHOLD - 1));
+        ConsistentProbabilitySampler { threshold, ratio }
+    }
+}
+
+impl ShouldSample for ConsistentProbabilitySampler {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        let ot = ctx.trace_state.get(OT_TRACE_STATE_KEY).unwrap_or_default();
+        let randomness = ot_value(ot, "rv")
+            .filter(|rv| rv.len() == THRESHOLD_DIGITS)
+            .and_then(|rv| u64::from_str_radix(rv, 16).ok())
+            .unwrap_or(ctx.trace_id.0 as u64 & (MAX_THRESHOLD - 1));
+        let inherited = ot_value(ot, "th").and_then(parse_threshold);
+        let threshold = inherited.or(self.threshold);
+        let sampled = threshold.is_some_and(|threshold| randomness >= threshold);
+        // Only sampled spans carry a threshold; it describes the probability they were kept with.
+        let th = threshold.filter(|_| sampled).map(format_threshold);
+        let updated = with_ot_value(ot, "th", th.as_deref());
+        let result = SamplingResult::from(sampled);
+        if updated == ot {
+            return result;
+        }
+        let mut trace_state = ctx.trace_state.clone();
+        if updated.is_empty() {
+            trace_state.remove(OT_TRACE_STATE_KEY);
+        } else {
+            trace_state.insert(OT_TRACE_STATE_KEY, updated);
+        }
+        result.with_trace_state(trace_state)
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        validate_ratio(self.ratio)
+    }
+}
+
+// A sub-key's value in the `ot` member, `key:value` pairs separated by `;`.
+fn ot_value<'a>(ot: &'a str, key: &str) -> Option<&'a str> {
+    ot.split(';')
+        .filter_map(|field| field.split_once(':'))
+        .find_map(|(name, value)| (name == key).then_some(value))
+}
+
+// The `ot` member with `key` set to `value`, or removed for `None`, keeping the other sub-keys.
+fn with_ot_value(ot: &str, key: &str, value: Option<&str>) -> String {
+    let mut fields: Vec<String> = ot
+        .split(';')
+        .filter(|field| {
+            !field.is_empty() && field.split_once(':').map(|(name, _)| name) != Some(key)
+        })
+        .map(str::to_string)
+        .collect();
+    if let Some(value) = value {
+        fields.insert(0, format!("{key}:{value}"));
+    }
+    fields.join(";")
+}
+
+// `th` holds up to 14 hex digits, with trailing zeros removed.
+fn parse_threshold(th: &str) -> Option<u64> {
+    if th.is_empty() || th.len() > THRESHOLD_DIGITS {
+        return None;
+    }
+    let threshold = u64::from_str_radix(th, 16).ok()?;
+    Some(threshold << (4 * (THRESHOLD_DIGITS - th.len())))
+}
+
+fn format_threshold(threshold: u64) -> String {
+    let th = format!("{threshold:014x}");
+    match th.trim_end_matches('0') {
+        "" => "0".to_string(),
+        th => th.to_string(),
+    }
+}
+
+/// Follows the parent's sampling decision when there is a parent, and delegates to the wrapped
+/// root sampler for spans without one.
+pub struct ParentBased<S: ShouldSample> {
+    root: S,
+}
+
+impl<S: ShouldSample> ParentBased<S> {
+    pub fn new(root: S) -> ParentBased<S> {
+        ParentBased { root }
+    }
+}
+
+impl<S: ShouldSample> ShouldSample for ParentBased<S> {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        match ctx.parent {
+            Some(parent) => parent.flags.is_sampled().into(),
+            None => self.root.should_sample(ctx),
+        }
+    }
+
+    fn uses_attributes(&self) -> bool {
+        self.root.uses_attributes()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.root.validate()
+    }
+}
+
+/// Samples spans with a per-level ratio. ERROR and WARN spans are always sampled by default,
+/// everything below follows the ratio given to [`LevelBasedSampler::new`].
+pub struct LevelBasedSampler {
+    // Indexed from ERROR (0) down to TRACE (4).
+    ratios: [f64; 5],
+}
+
+impl LevelBasedSampler {
+    pub fn new(ratio: f64) -> LevelBasedSampler {
+        LevelBasedSampler {
+            ratios: [1.0, 1.0, ratio, ratio, ratio],
+        }
+    }
+
+    /// Overrides the ratio used for spans of `level`.
+    pub fn with_ratio(mut self, level: Level, ratio: f64) -> Self {
+        self.ratios[level_index(&level)] = ratio;
+        self
+    }
+}
+
+impl ShouldSample for LevelBasedSampler {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        trace_id_ratio_sample(&ctx.trace_id, self.ratios[level_index(&ctx.level)]).into()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.ratios
+            .iter()
+            .try_for_each(|ratio| validate_ratio(*ratio))
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+}
+
+/// Samples at most `max_per_second` spans per second using a token bucket, regardless of how
+/// many threads create spans. Wrap it in [`ParentBased`] to only spend tokens on root spans.
+pub struct RateLimitingSampler {
+    max_per_second: u64,
+    bucket: Mutex<TokenBucket>,
+    clock: Box<dyn Clock + Send + Sync>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimitingSampler {
+    pub fn new(max_per_second: u64) -> RateLimitingSampler {
+        RateLimitingSampler {
+            max_per_second,
+            bucket: Mutex::new(TokenBucket {
+                tokens: max_per_second as f64,
+                last_refill: Instant::now(),
+            }),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// The clock tokens are refilled by. `SystemClock` by default.
+    pub fn with_clock<T>(mut self, clock: T) -> Self
+    where
+        T: Clock + Send + Sync + 'static,
+    {
+        self.bucket.get_mut().unwrap().last_refill = clock.instant();
+        self.clock = Box::new(clock);
+        self
+    }
+}
+
+impl ShouldSample for RateLimitingSampler {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        let mut bucket = match self.bucket.lock() {
+            Ok(bucket) => bucket,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = self.clock.instant();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        let capacity = self.max_per_second as f64;
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::RecordAndSample.into()
+        } else {
+            Decision::Drop.into()
+        }
+    }
+
+    fn uses_attributes(&self) -> bool {
+        false
+    }
+}
+
+// The sampling decisions of the most recent traces, so every span of a trace shares the
+// decision made for its first one. Once full, the oldest trace is forgotten.
+pub(crate) struct DecisionCache {
+    capacity: usize,
+    decisions: Mutex<(HashMap<TraceId, bool>, VecDeque<TraceId>)>,
+}
+
+impl DecisionCache {
+    pub(crate) fn new(capacity: usize) -> DecisionCache {
+        DecisionCache {
+            capacity,
+            decisions: Mutex::new((HashMap::new(), VecDeque::new())),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn get_or_insert_with(
+        &self,
+        trace_id: TraceId,
+        decide: impl FnOnce() -> SamplingResult,
+    ) -> SamplingResult {
+        if let Some(sampled) = self.decisions.lock().unwrap().0.get(&trace_id) {
+            return (*sampled).into();
+        }
+        // Decide without holding the lock; a racing span of the same trace keeps the first
+        // decision stored.
+        let result = decide();
+        let mut decisions = self.decisions.lock().unwrap();
+        let (cached, order) = &mut *decisions;
+        if let Some(sampled) = cached.get(&trace_id) {
+            return (*sampled).into();
+        }
+        if cached.len() >= self.capacity.max(1) {
+            if let Some(oldest) = order.pop_front() {
+                cached.remove(&oldest);
+            }
+        }
+        cached.insert(trace_id, result.is_sampled());
+        order.push_back(trace_id);
+        result
+    }
+}
+
+// The sampler named by `OTEL_TRACES_SAMPLER`, with `arg` from `OTEL_TRACES_SAMPLER_ARG` as the
+// ratio. Unknown names and invalid ratios warn and fall back to the spec's defaults.
+pub(crate) fn sampler_from_env(
+    name: Option<&str>,
+    arg: Option<&str>,
+) -> Box<dyn ShouldSample + Send + Sync> {
+    let ratio = || match arg.map(|arg| arg.trim().parse::<f64>()) {
+        None => 1.0,
+        Some(Ok(ratio)) if (0.0..=1.0).contains(&ratio) => ratio,
+        Some(_) => {
+            eprintln!(
+                "OTEL_TRACES_SAMPLER_ARG: invalid ratio {:?}, using 1.0",
+                arg.unwrap_or_default()
+            );
+            1.0
+        }
+    };
+    match name.map(str::trim) {
+        None | Some("parentbased_always_on") => Box::new(ParentBased::new(AlwaysOn)),
+        Some("always_on") => Box::new(AlwaysOn),
+        Some("always_off") => Box::new(AlwaysOff),
+        Some("parentbased_always_off") => Box::new(ParentBased::new(AlwaysOff)),
+        Some("traceidratio") => Box::new(TraceIdRatioBased::new(ratio())),
+        Some("parentbased_traceidratio") => {
+            Box::new(ParentBased::new(TraceIdRatioBased::new(ratio())))
+        }
+        Some(other) => {
+            eprintln!(
+                "OTEL_TRACES_SAMPLER: unknown sampler {other:?}, using parentbased_always_on"
+            );
+            Box::new(ParentBased::new(AlwaysOn))
+        }
+    }
+}
+
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+// Deterministic ratio decision on the lower 64 bits of the trace id, so every span of a trace
+// makes the same decision. Matches the TraceIdRatioBased sampler from the OTel spec.
+pub(crate) fn trace_id_ratio_sample(trace_id: &TraceId, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    let upper_bound = (ratio.max(0.0) * (1u64 << 63) as f64) as u64;
+    ((trace_id.0 as u64) >> 1) < upper_bound
+}
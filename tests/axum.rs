@@ -0,0 +1,84 @@
+#![cfg(feature = "axum")]
+
+use axum::body::Body;
+use axum::routing::get;
+use axum::Router;
+use http::{Request, StatusCode};
+use opentelemetry_tracing::axum::{OtelSpanContext, Trace};
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{AttributeValue, OpenTelemetrySdk};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::propagator::W3CTraceContextPropagator;
+use tower::ServiceExt;
+use tracing_subscriber::prelude::*;
+
+const TRACE_ID: &str = "0af7651916cd43dd8448eb211c80319c";
+const SPAN_ID: &str = "b7ad6b7169203331";
+
+async fn user(OtelSpanContext(context): OtelSpanContext) -> String {
+    context.trace_id.to_string()
+}
+
+// Sends `request` through a traced router, returning the response status and body.
+fn call(router: Router, request: Request<Body>) -> (StatusCode, String) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    })
+}
+
+#[test]
+fn routed_requests_continue_the_callers_trace() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_propagator(W3CTraceContextPropagator::new())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let request = Request::get("/users/42")
+        .header("traceparent", format!("00-{TRACE_ID}-{SPAN_ID}-01"))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) =
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+            let router = Router::new()
+                .route("/users/:id", get(user))
+                .layer(Trace::default());
+            call(router, request)
+        });
+    assert_eq!(status, StatusCode::OK);
+    // The handler saw the server span's context.
+    assert_eq!(body, TRACE_ID);
+
+    let span = &exporter.spans_named("GET /users/:id")[0];
+    assert_eq!(span.trace_id.to_string(), TRACE_ID);
+    assert_eq!(
+        span.parent_span_id.map(|id| id.to_string()).as_deref(),
+        Some(SPAN_ID)
+    );
+    assert_eq!(
+        span.attributes["http.route"],
+        AttributeValue::from("/users/:id")
+    );
+    assert_eq!(
+        span.attributes["http.status_code"],
+        AttributeValue::I64(200)
+    );
+}
+
+#[test]
+fn the_extractor_rejects_requests_without_a_span() {
+    let router = Router::new().route("/users/:id", get(user));
+    let (status, _) = call(
+        router,
+        Request::get("/users/42").body(Body::empty()).unwrap(),
+    );
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "opentelemetry-compat")]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry::trace as otel;
+use opentelemetry::{Key, KeyValue, Value};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource as OtelResource;
+use opentelemetry_tracing::compat::{CompatSpanExporter, InvalidSpanContext};
+use opentelemetry_tracing::opentelemetry_sdk::{
+    OpenTelemetrySdk, OtelSpanExt, SpanContext, SpanId, SpanKind, TraceFlags, TraceId,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing_subscriber::prelude::*;
+
+// An upstream exporter keeping what it is given.
+#[derive(Clone, Debug, Default)]
+struct Collected {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+    resource: Arc<Mutex<Option<OtelResource>>>,
+}
+
+impl SpanExporter for Collected {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.spans.lock().unwrap().extend(batch);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn set_resource(&mut self, resource: &OtelResource) {
+        *self.resource.lock().unwrap() = Some(resource.clone());
+    }
+}
+
+#[test]
+fn ids_and_contexts_convert_both_ways() {
+    let trace_id = TraceId::from(0x0af7651916cd43dd8448eb211c80319c);
+    let span_id = SpanId::from(0xb7ad6b7169203331);
+    let context = SpanContext::new_remote(trace_id, span_id, TraceFlags::SAMPLED);
+
+    let converted = otel::SpanContext::from(context);
+    assert_eq!(
+        converted.trace_id(),
+        otel::TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+    );
+    assert_eq!(
+        converted.span_id(),
+        otel::SpanId::from_hex("b7ad6b7169203331").unwrap()
+    );
+    assert!(converted.is_sampled());
+    assert!(converted.is_remote());
+    assert_eq!(SpanContext::try_from(&converted), Ok(context));
+
+    assert_eq!(
+        SpanContext::try_from(&otel::SpanContext::empty_context()),
+        Err(InvalidSpanContext)
+    );
+}
+
+#[test]
+fn compat_exporter_hands_spans_to_an_upstream_exporter() {
+    let upstream = Collected::default();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_service_name("compat")
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(CompatSpanExporter::new(
+            upstream.clone(),
+        ))))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("parent").in_scope(|| {
+            let child = tracing::info_span!("child", rows = 3);
+            child.set_span_kind(SpanKind::Client);
+            child.in_scope(|| {});
+        });
+    });
+
+    let spans = upstream.spans.lock().unwrap();
+    let (child, parent) = (&spans[0], &spans[1]);
+    assert_eq!(child.name, "child");
+    assert_eq!(child.span_kind, otel::SpanKind::Client);
+    assert_eq!(child.parent_span_id, parent.span_context.span_id());
+    assert_eq!(
+        child.span_context.trace_id(),
+        parent.span_context.trace_id()
+    );
+    assert!(child.attributes.contains(&KeyValue::new("rows", 3)));
+    assert_eq!(parent.parent_span_id, otel::SpanId::INVALID);
+
+    let resource = upstream.resource.lock().unwrap().clone().unwrap();
+    assert_eq!(
+        resource.get(Key::new("service.name")),
+        Some(Value::from("compat"))
+    );
+}
@@ -0,0 +1,103 @@
+use opentelemetry_tracing::context::{OtelContext, OtelFutureExt};
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    OpenTelemetrySdk, RemoteSpanContext, SpanId, TraceFlags, TraceId,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing::Dispatch;
+use tracing_subscriber::prelude::*;
+
+fn dispatch(exporter: &InMemorySpanExporter) -> Dispatch {
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    Dispatch::new(tracing_subscriber::registry().with(sdk))
+}
+
+#[test]
+fn spawned_tasks_continue_the_captured_context() {
+    let exporter = InMemorySpanExporter::new();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    tracing::dispatcher::with_default(&dispatch(&exporter), || {
+        runtime.block_on(async {
+            // Spawned inside the span but polled after it was exited, like a connection task.
+            let (with_context, without) = tracing::info_span!("parent").in_scope(|| {
+                (
+                    tokio::spawn(
+                        async { tracing::info_span!("with_context").in_scope(|| {}) }
+                            .with_current_otel_context(),
+                    ),
+                    tokio::spawn(async { tracing::info_span!("without").in_scope(|| {}) }),
+                )
+            });
+            with_context.await.unwrap();
+            without.await.unwrap();
+        });
+    });
+    let parent = &exporter.spans_named("parent")[0];
+    let with_context = &exporter.spans_named("with_context")[0];
+    assert_eq!(with_context.trace_id, parent.trace_id);
+    assert_eq!(with_context.parent_span_id, Some(parent.span_id));
+    // Tasks start with no span, so without the context they start a new trace.
+    let without = &exporter.spans_named("without")[0];
+    assert_ne!(without.trace_id, parent.trace_id);
+}
+
+#[test]
+fn attached_contexts_carry_the_remote_parent_to_other_threads() {
+    let exporter = InMemorySpanExporter::new();
+    let dispatch = dispatch(&exporter);
+    let remote = RemoteSpanContext::new(
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+        SpanId::from(0xb7ad6b7169203331),
+        TraceFlags::SAMPLED,
+    );
+    let context = {
+        let _guard = remote.attach();
+        OtelContext::current()
+    };
+    std::thread::spawn(move || {
+        tracing::dispatcher::with_default(&dispatch, || {
+            let _attached = context.attach();
+            tracing::info_span!("worker").in_scope(|| {});
+        })
+    })
+    .join()
+    .unwrap();
+    let worker = &exporter.spans_named("worker")[0];
+    assert_eq!(worker.trace_id, remote.trace_id);
+    assert_eq!(worker.parent_span_id, Some(remote.span_id));
+}
+
+#[test]
+fn otel_instrument_keeps_the_span_and_remote_context_across_awaits() {
+    let exporter = InMemorySpanExporter::new();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let remote = RemoteSpanContext::new(
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+        SpanId::from(0xb7ad6b7169203331),
+        TraceFlags::SAMPLED,
+    );
+    tracing::dispatcher::with_default(&dispatch(&exporter), || {
+        let future = {
+            let _guard = remote.attach();
+            let request = tracing::info_span!("request");
+            async {
+                tokio::task::yield_now().await;
+                tracing::info_span!("after_yield").in_scope(|| {});
+            }
+            .otel_instrument(request)
+        };
+        runtime.block_on(future);
+    });
+    let request = &exporter.spans_named("request")[0];
+    let after_yield = &exporter.spans_named("after_yield")[0];
+    assert_eq!(request.trace_id, remote.trace_id);
+    assert_eq!(after_yield.trace_id, remote.trace_id);
+    assert_eq!(after_yield.parent_span_id, Some(request.span_id));
+}
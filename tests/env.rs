@@ -0,0 +1,102 @@
+// Environment variables are process-wide, so every case runs in this one test.
+
+use std::env;
+
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OpenTelemetrySdk, RemoteSpanContext, SpanId, TraceFlags, TraceId,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing_subscriber::prelude::*;
+
+const VARS: [&str; 4] = [
+    "OTEL_TRACES_SAMPLER",
+    "OTEL_TRACES_SAMPLER_ARG",
+    "OTEL_SERVICE_NAME",
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+];
+
+// Builds the SDK from `vars` and creates ten root spans and one under a sampled remote parent,
+// returning the exporter.
+fn from_env(vars: &[(&str, &str)]) -> InMemorySpanExporter {
+    for name in VARS {
+        env::remove_var(name);
+    }
+    for (name, value) in vars {
+        env::set_var(name, value);
+    }
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::from_env()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let remote = RemoteSpanContext::new(
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+        SpanId::from(0xb7ad6b7169203331),
+        TraceFlags::SAMPLED,
+    );
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        for _ in 0..10 {
+            tracing::info_span!("root").in_scope(|| {});
+        }
+        let _guard = remote.attach();
+        tracing::info_span!("remote_child").in_scope(|| {});
+    });
+    exporter
+}
+
+fn exported(exporter: &InMemorySpanExporter) -> (usize, usize) {
+    (
+        exporter.spans_named("root").len(),
+        exporter.spans_named("remote_child").len(),
+    )
+}
+
+#[test]
+fn sdk_is_configured_from_otel_env_vars() {
+    // Unset: parent-based always on, and the default service name.
+    let exporter = from_env(&[]);
+    assert_eq!(exported(&exporter), (10, 1));
+    let service_name = exporter
+        .resource()
+        .unwrap()
+        .get("service.name")
+        .unwrap()
+        .to_string();
+    assert!(service_name.starts_with("env"), "{service_name}");
+
+    let exporter = from_env(&[("OTEL_SERVICE_NAME", "checkout")]);
+    assert_eq!(
+        exporter.resource().unwrap().get("service.name"),
+        Some(&AttributeValue::from("checkout"))
+    );
+
+    let exporter = from_env(&[("OTEL_TRACES_SAMPLER", "always_off")]);
+    assert_eq!(exported(&exporter), (0, 0));
+
+    let exporter = from_env(&[
+        ("OTEL_TRACES_SAMPLER", "traceidratio"),
+        ("OTEL_TRACES_SAMPLER_ARG", "0"),
+    ]);
+    assert_eq!(exported(&exporter), (0, 0));
+
+    // Follows the sampled remote parent despite the zero ratio.
+    let exporter = from_env(&[
+        ("OTEL_TRACES_SAMPLER", "parentbased_traceidratio"),
+        ("OTEL_TRACES_SAMPLER_ARG", "0"),
+    ]);
+    assert_eq!(exported(&exporter), (0, 1));
+
+    // Invalid values fall back instead of failing.
+    let exporter = from_env(&[
+        ("OTEL_TRACES_SAMPLER", "traceidratio"),
+        ("OTEL_TRACES_SAMPLER_ARG", "2"),
+    ]);
+    assert_eq!(exported(&exporter), (10, 1));
+    let exporter = from_env(&[("OTEL_TRACES_SAMPLER", "sometimes")]);
+    assert_eq!(exported(&exporter), (10, 1));
+
+    for name in VARS {
+        env::remove_var(name);
+    }
+}
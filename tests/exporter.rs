@@ -0,0 +1,288 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use opentelemetry_tracing::clock::ManualClock;
+use opentelemetry_tracing::exporter::{
+    system_time_to_unix_nanos, InMemorySpanExporter, JsonFormatter, LogfmtFormatter, SpanExporter,
+    SpanFormatter, StdoutExporter, ZipkinExporter,
+};
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OTelSpanData, OpenTelemetrySdk, Resource, SpanId, SpanKind,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing_subscriber::prelude::*;
+
+fn with_exporter(f: impl FnOnce()) -> InMemorySpanExporter {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+    exporter
+}
+
+#[test]
+fn in_memory_exporter_finds_spans_by_name_trace_and_attribute() {
+    let exporter = with_exporter(|| {
+        for user in ["alice", "bob"] {
+            tracing::info_span!("request", user).in_scope(|| {
+                tracing::info_span!("query", rows = 3).in_scope(|| {});
+            });
+        }
+    });
+
+    let requests = exporter.spans_named("request");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].attributes.get("user"),
+        Some(&AttributeValue::from("alice"))
+    );
+    assert!(exporter.spans_named("missing").is_empty());
+
+    let query = exporter
+        .span_by_trace_and_name(requests[1].trace_id, "query")
+        .unwrap();
+    assert_eq!(query.parent_span_id, Some(requests[1].span_id));
+    assert_eq!(query.attributes.get("rows"), Some(&AttributeValue::I64(3)));
+
+    let bob = exporter.spans_with_attribute("user", "bob");
+    assert_eq!(bob.len(), 1);
+    assert_eq!(bob[0].span_id, requests[1].span_id);
+    assert_eq!(exporter.spans_with_attribute("rows", 3i64).len(), 2);
+}
+
+// A writer whose output stays readable after the exporter took it.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+}
+
+// A `checkout` span, started at 1s past the epoch and open for 250ms.
+fn checkout_span() -> OTelSpanData {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_clock(clock.clone())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("checkout", cart = "two items", items = 2);
+        clock.advance(Duration::from_millis(250));
+        drop(span);
+    });
+    exporter.get_finished_spans().remove(0)
+}
+
+fn export_with<T>(formatter: T, span: OTelSpanData) -> Vec<String>
+where
+    T: SpanFormatter + Send + Sync + 'static,
+{
+    let output = Output::default();
+    let mut exporter =
+        StdoutExporter::with_writer(Box::new(output.clone())).with_formatter(formatter);
+    exporter.set_resource(&Resource::empty().with_attribute("service.name", "shop"));
+    exporter.export(vec![span]).unwrap();
+    output.lines()
+}
+
+#[test]
+fn stdout_exporter_renders_spans_as_json() {
+    let span = checkout_span();
+    let lines = export_with(JsonFormatter, span.clone());
+    assert_eq!(lines.len(), 1);
+    let json: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(json["name"], "checkout");
+    assert_eq!(json["trace_id"], span.trace_id.to_string());
+    assert_eq!(json["start_time"], 1_000_000_000u64);
+    assert_eq!(json["end_time"], 1_250_000_000u64);
+    assert_eq!(json["attributes"]["cart"], "two items");
+    assert_eq!(json["attributes"]["items"], 2);
+    assert_eq!(json["resource"]["service.name"], "shop");
+}
+
+#[test]
+fn stdout_exporter_renders_spans_as_logfmt() {
+    let span = checkout_span();
+    let lines = export_with(LogfmtFormatter, span.clone());
+    assert_eq!(lines.len(), 1);
+    let expected = format!(
+        "name=checkout trace_id={} span_id={} kind=internal status=unset \
+         start_time=1000000000 duration_ns=250000000 cart=\"two items\" items=2",
+        span.trace_id, span.span_id
+    );
+    assert!(lines[0].starts_with(&expected), "{}", lines[0]);
+}
+
+#[test]
+fn stdout_exporter_writes_a_json_line_per_span_by_default() {
+    let spans = with_exporter(|| {
+        tracing::info_span!("parent").in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+    })
+    .get_finished_spans();
+    let output = Output::default();
+    let exporter = StdoutExporter::with_writer(Box::new(output.clone()));
+    exporter.export(spans.clone()).unwrap();
+
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    let (child, parent) = (&lines[0], &lines[1]);
+    assert_eq!(child["name"], "child");
+    assert_eq!(parent["trace_id"].as_str().unwrap().len(), 32);
+    assert_eq!(parent["span_id"].as_str().unwrap().len(), 16);
+    assert_eq!(child["parent_span_id"], parent["span_id"]);
+    assert_eq!(parent["span_id"], spans[1].span_id.to_string());
+}
+
+#[test]
+fn in_memory_exporter_shows_children_inheriting_their_parent_trace() {
+    let exporter = with_exporter(|| {
+        tracing::info_span!("first").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {
+                tracing::info_span!("grandchild").in_scope(|| {});
+            });
+        });
+        tracing::info_span!("second").in_scope(|| {});
+    });
+    let span = |name| exporter.spans_named(name).remove(0);
+    let (first, child, grandchild) = (span("first"), span("child"), span("grandchild"));
+    assert_eq!(first.parent_span_id, None);
+    assert_eq!(child.trace_id, first.trace_id);
+    assert_eq!(child.parent_span_id, Some(first.span_id));
+    assert_eq!(grandchild.trace_id, first.trace_id);
+    assert_eq!(grandchild.parent_span_id, Some(child.span_id));
+    assert_ne!(span("second").trace_id, first.trace_id);
+
+    exporter.reset();
+    assert!(exporter.get_finished_spans().is_empty());
+}
+
+#[test]
+fn exporters_get_the_sdk_resource() {
+    let (before, after) = (InMemorySpanExporter::new(), InMemorySpanExporter::new());
+    let _sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(before.clone())))
+        .with_service_name("checkout")
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(after.clone())))
+        .build()
+        .unwrap();
+    for exporter in [before, after] {
+        let resource = exporter.resource().unwrap();
+        assert_eq!(resource.get("service.name"), Some(&"checkout".into()));
+    }
+
+    // Without one, the service is named after the executable.
+    let exporter = with_exporter(|| {});
+    let service_name = exporter
+        .resource()
+        .unwrap()
+        .get("service.name")
+        .unwrap()
+        .to_string();
+    assert!(service_name.starts_with("exporter"), "{service_name}");
+}
+
+// The headers of a request, lowercased, and its body.
+type Received = (Vec<String>, Vec<u8>);
+
+// Accepts one HTTP request on a local port, answering `202 Accepted`. Returns the URL to send
+// it to and a handle yielding the request.
+fn receive_one() -> (String, JoinHandle<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers.push(line.trim().to_lowercase());
+        }
+        let length = headers
+            .iter()
+            .find_map(|header| header.strip_prefix("content-length: "))
+            .map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        (headers, body)
+    });
+    (url, handle)
+}
+
+#[test]
+fn zipkin_exporter_posts_v2_json() {
+    let mut span = checkout_span();
+    span.kind = SpanKind::Client;
+    span.parent_span_id = Some(SpanId::from(0xab));
+    let (url, request) = receive_one();
+    let mut exporter = ZipkinExporter::new(format!("{url}/api/v2/spans")).unwrap();
+    exporter.set_resource(&Resource::empty().with_attribute("service.name", "shop"));
+    exporter.export(vec![span.clone()]).unwrap();
+
+    let (headers, body) = request.join().unwrap();
+    assert_eq!(headers[0], "post /api/v2/spans http/1.1");
+    assert!(headers.contains(&"content-type: application/json".to_string()));
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let zipkin = &body[0];
+    assert_eq!(zipkin["traceId"], span.trace_id.to_string());
+    assert_eq!(zipkin["id"], span.span_id.to_string());
+    assert_eq!(zipkin["parentId"], "00000000000000ab");
+    assert_eq!(zipkin["name"], "checkout");
+    // Microseconds: started 1s past the epoch, open for 250ms.
+    assert_eq!(zipkin["timestamp"], 1_000_000);
+    assert_eq!(zipkin["duration"], 250_000);
+    assert_eq!(zipkin["kind"], "CLIENT");
+    assert_eq!(zipkin["localEndpoint"]["serviceName"], "shop");
+    // Tags are always strings.
+    assert_eq!(zipkin["tags"]["items"], "2");
+    assert_eq!(zipkin["tags"]["cart"], "two items");
+}
+
+#[test]
+fn timestamps_convert_to_unix_nanos_without_losing_precision() {
+    let known = SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+    assert_eq!(system_time_to_unix_nanos(known), 1_700_000_000_123_456_789);
+    assert_eq!(system_time_to_unix_nanos(SystemTime::UNIX_EPOCH), 0);
+    assert_eq!(
+        system_time_to_unix_nanos(SystemTime::UNIX_EPOCH + Duration::from_nanos(1)),
+        1
+    );
+    assert_eq!(
+        system_time_to_unix_nanos(SystemTime::UNIX_EPOCH - Duration::from_secs(1)),
+        0
+    );
+    // u64 nanoseconds run out in 2554.
+    let far_future = SystemTime::UNIX_EPOCH + Duration::from_secs(600 * 365 * 24 * 60 * 60);
+    assert_eq!(system_time_to_unix_nanos(far_future), u64::MAX);
+}
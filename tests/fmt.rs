@@ -0,0 +1,90 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry_tracing::fmt::OtelFormat;
+use opentelemetry_tracing::opentelemetry_sdk::{AlwaysOff, OpenTelemetrySdk, OtelSpanExt};
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::prelude::*;
+
+// Collects what the `fmt` layer writes.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Output {
+    type Writer = Output;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// Runs `f` under `sdk` with a `fmt` layer using `OtelFormat`, returning the lines it logged.
+fn logged(sdk: OpenTelemetrySdk, f: impl FnOnce()) -> Vec<String> {
+    let output = Output::default();
+    let format = OtelFormat::new(fmt::format().without_time().with_ansi(false));
+    let subscriber = tracing_subscriber::registry().with(sdk).with(
+        fmt::layer()
+            .event_format(format)
+            .with_writer(output.clone()),
+    );
+    tracing::subscriber::with_default(subscriber, f);
+    output.lines()
+}
+
+#[test]
+fn log_lines_start_with_the_ids_of_their_span() {
+    let mut ids = Vec::new();
+    let lines = logged(OpenTelemetrySdk::new(), || {
+        tracing::info!("outside");
+        let parent = tracing::info_span!("parent");
+        parent.in_scope(|| {
+            tracing::info!("in parent");
+            let child = tracing::info_span!("child");
+            child.in_scope(|| tracing::info!("in child"));
+            ids.push(child.span_context().unwrap());
+        });
+        ids.insert(0, parent.span_context().unwrap());
+    });
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(" INFO"), "{}", lines[0]);
+    for (line, context) in lines[1..].iter().zip(&ids) {
+        let prefix = format!("trace_id={} span_id={} ", context.trace_id, context.span_id);
+        assert!(line.starts_with(&prefix), "{line}");
+    }
+    assert_eq!(ids[0].trace_id, ids[1].trace_id);
+}
+
+#[test]
+fn unsampled_spans_still_give_their_ids() {
+    let lines = logged(
+        OpenTelemetrySdk::builder()
+            .with_sampler(AlwaysOff)
+            .build()
+            .unwrap(),
+        || {
+            tracing::info_span!("dropped").in_scope(|| tracing::info!("event"));
+        },
+    );
+    assert!(lines[0].starts_with("trace_id="), "{}", lines[0]);
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::id_generator::{FixedIdGenerator, IdGenerator, RandomIdGenerator};
+use opentelemetry_tracing::opentelemetry_sdk::{OpenTelemetrySdk, SpanId, TraceId};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::sampler::{SamplingContext, SamplingResult, ShouldSample};
+use tracing::Dispatch;
+use tracing_subscriber::prelude::*;
+
+const THREADS: usize = 16;
+const SPANS_PER_THREAD: usize = 5_000;
+
+#[test]
+fn random_ids_are_unique_across_threads() {
+    let ids: Vec<_> = (0..THREADS)
+        .map(|_| {
+            thread::spawn(|| {
+                (0..SPANS_PER_THREAD)
+                    .map(|_| {
+                        (
+                            RandomIdGenerator.new_trace_id(),
+                            RandomIdGenerator.new_span_id(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .flat_map(|handle| handle.join().unwrap())
+        .collect();
+    let trace_ids: HashSet<_> = ids.iter().map(|(trace_id, _)| *trace_id).collect();
+    let span_ids: HashSet<_> = ids.iter().map(|(_, span_id)| *span_id).collect();
+    assert_eq!(trace_ids.len(), THREADS * SPANS_PER_THREAD);
+    assert_eq!(span_ids.len(), THREADS * SPANS_PER_THREAD);
+}
+
+#[test]
+fn concurrent_root_spans_get_unique_trace_ids() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let dispatch = Dispatch::new(tracing_subscriber::registry().with(sdk));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let dispatch = dispatch.clone();
+            thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    for _ in 0..SPANS_PER_THREAD {
+                        tracing::info_span!("root").in_scope(|| {});
+                    }
+                })
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let spans = exporter.get_finished_spans();
+    assert_eq!(spans.len(), THREADS * SPANS_PER_THREAD);
+    let trace_ids: HashSet<_> = spans.iter().map(|span| span.trace_id).collect();
+    assert_eq!(trace_ids.len(), spans.len());
+}
+
+// Creates a span and draws ids of its own while deciding, like a sampler that logs or calls
+// into instrumented code.
+struct SpanCreatingSampler(Arc<AtomicUsize>);
+
+impl ShouldSample for SpanCreatingSampler {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        tracing::info_span!("sampling").in_scope(|| {
+            RandomIdGenerator.new_trace_id();
+            RandomIdGenerator.new_span_id();
+        });
+        true.into()
+    }
+}
+
+// Creates a span while generating each id.
+struct SpanCreatingIdGenerator;
+
+impl IdGenerator for SpanCreatingIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        tracing::info_span!("new_trace_id").in_scope(|| RandomIdGenerator.new_trace_id())
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        tracing::info_span!("new_span_id").in_scope(|| RandomIdGenerator.new_span_id())
+    }
+}
+
+#[test]
+fn samplers_and_id_generators_may_create_spans() {
+    let decisions = Arc::new(AtomicUsize::new(0));
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(SpanCreatingSampler(decisions.clone()))
+        .with_id_generator(SpanCreatingIdGenerator)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("root").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {});
+        });
+    });
+    assert_eq!(decisions.load(Ordering::Relaxed), 2);
+    let spans = exporter.spans_named("child");
+    assert_eq!(spans.len(), 1);
+    let root = &exporter.spans_named("root")[0];
+    assert_eq!(spans[0].trace_id, root.trace_id);
+    assert_eq!(spans[0].parent_span_id, Some(root.span_id));
+}
+
+#[test]
+fn spans_get_their_ids_from_the_sdk_id_generator() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_id_generator(FixedIdGenerator::new(TraceId::from(7), SpanId::from(100)))
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("first").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {});
+        });
+        tracing::info_span!("second").in_scope(|| {});
+    });
+    let ids: Vec<(String, TraceId, SpanId)> = exporter
+        .get_finished_spans()
+        .into_iter()
+        .map(|span| (span.name, span.trace_id, span.span_id))
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            ("child".to_string(), TraceId::from(7), SpanId::from(101)),
+            ("first".to_string(), TraceId::from(7), SpanId::from(100)),
+            ("second".to_string(), TraceId::from(7), SpanId::from(102)),
+        ]
+    );
+}
@@ -0,0 +1,148 @@
+use std::env;
+use std::process::Command;
+
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{EventExportMode, OpenTelemetrySdk, OtelSpanExt};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing_subscriber::prelude::*;
+
+const CHILD_TEST: &str = "LOG_RECORD_CHILD_TEST";
+
+// Log records are printed to stdout, so `test` reruns itself in a child process that runs `f`
+// under an SDK exporting events in `mode`. Returns the child's log record lines, along with any
+// `SpanContext` lines `f` printed, or `None` in the child itself.
+fn log_records(test: &str, mode: EventExportMode, f: impl FnOnce()) -> Option<Vec<String>> {
+    if env::var(CHILD_TEST).as_deref() == Ok(test) {
+        let sdk = OpenTelemetrySdk::builder()
+            .with_event_export_mode(mode)
+            .with_span_processor(SimpleSpanProcessor::new(Box::new(
+                InMemorySpanExporter::new(),
+            )))
+            .build()
+            .unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+        return None;
+    }
+    let output = Command::new(env::current_exe().unwrap())
+        .args([test, "--exact", "--nocapture"])
+        .env(CHILD_TEST, test)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    Some(
+        stdout
+            .lines()
+            // The harness prints the test name on the same line as the first record.
+            .filter_map(|line| {
+                line.find("LogRecord ")
+                    .or_else(|| line.find("SpanContext "))
+                    .map(|start| line[start..].to_string())
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn events_outside_any_span_are_still_log_records() {
+    let Some(records) = log_records(
+        "events_outside_any_span_are_still_log_records",
+        EventExportMode::LogRecord,
+        || tracing::warn!("no span here"),
+    ) else {
+        return;
+    };
+    assert_eq!(records.len(), 1);
+    assert!(records[0].contains(&format!(
+        "TraceId {} SpanId {}:",
+        "0".repeat(32),
+        "0".repeat(16)
+    )));
+    assert!(records[0].contains("message=no span here"));
+}
+
+#[test]
+fn events_in_a_span_carry_its_context() {
+    let Some(records) = log_records(
+        "events_in_a_span_carry_its_context",
+        EventExportMode::LogRecord,
+        || tracing::info_span!("request").in_scope(|| tracing::info!("inside")),
+    ) else {
+        return;
+    };
+    assert_eq!(records.len(), 1);
+    assert!(!records[0].contains(&"0".repeat(32)));
+}
+
+#[test]
+fn log_records_carry_the_event_fields() {
+    let Some(records) = log_records(
+        "log_records_carry_the_event_fields",
+        EventExportMode::LogRecord,
+        || tracing::warn!(name: "x", event_id = 10, user = "otel"),
+    ) else {
+        return;
+    };
+    assert_eq!(records.len(), 1);
+    assert!(records[0].starts_with("LogRecord x "));
+    assert!(records[0].ends_with(": event_id=10 level=WARN target=log_record user=otel"));
+}
+
+#[test]
+fn no_log_records_when_event_export_is_off() {
+    let Some(records) = log_records(
+        "no_log_records_when_event_export_is_off",
+        EventExportMode::None,
+        || {
+            tracing::warn!("outside");
+            tracing::info_span!("request").in_scope(|| tracing::info!("inside"));
+        },
+    ) else {
+        return;
+    };
+    assert!(records.is_empty());
+}
+
+#[test]
+fn log_records_carry_the_otel_severity_of_the_level() {
+    let Some(records) = log_records(
+        "log_records_carry_the_otel_severity_of_the_level",
+        EventExportMode::LogRecord,
+        || {
+            tracing::error!("e");
+            tracing::warn!("w");
+            tracing::info!("i");
+        },
+    ) else {
+        return;
+    };
+    assert_eq!(records.len(), 3);
+    assert!(records[0].contains(" severity 17 (ERROR) "));
+    assert!(records[1].contains(" severity 13 (WARN) "));
+    assert!(records[2].contains(" severity 9 (INFO) "));
+}
+
+#[test]
+fn both_mode_keeps_the_span_event_and_logs_it_with_the_span_ids() {
+    let Some(records) = log_records(
+        "both_mode_keeps_the_span_event_and_logs_it_with_the_span_ids",
+        EventExportMode::Both,
+        || {
+            let span = tracing::info_span!("request");
+            span.in_scope(|| tracing::info!("inside"));
+            let events = span.with_otel_span(|span| Some(span.events.len()));
+            assert_eq!(events, 1);
+            let context = span.span_context().unwrap();
+            println!(
+                "SpanContext TraceId {} SpanId {}",
+                context.trace_id, context.span_id
+            );
+        },
+    ) else {
+        return;
+    };
+    assert_eq!(records.len(), 2);
+    let ids = records[1].strip_prefix("SpanContext ").unwrap();
+    assert!(records[0].contains(&format!("{ids}:")), "{}", records[0]);
+    assert!(records[0].contains("message=inside"));
+}
@@ -0,0 +1,52 @@
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OpenTelemetrySdk, RemoteSpanContext, SpanId, TraceFlags, TraceId,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::span_with_links;
+use tracing::{Level, Span};
+use tracing_subscriber::prelude::*;
+
+fn with_exporter(f: impl FnOnce()) -> InMemorySpanExporter {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+    exporter
+}
+
+#[test]
+fn span_with_links_expands_for_every_kind_of_link() {
+    // Header values, owned or borrowed, contexts, and no links at all.
+    let headers = vec!["00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string()];
+    let _: Span = span_with_links!(headers.iter(), Level::INFO, "borrowed",);
+    let _: Span = span_with_links!(headers, Level::INFO, "owned", batch = 1);
+    let _: Span = span_with_links!(Vec::<&str>::new(), Level::DEBUG, "empty",);
+    let context = RemoteSpanContext::new(TraceId::from(1), SpanId::from(2), TraceFlags::SAMPLED);
+    let _: Span = span_with_links!([context], Level::INFO, "contexts",);
+}
+
+#[test]
+fn span_with_links_links_each_valid_header() {
+    let exporter = with_exporter(|| {
+        let headers = [
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "not a header",
+            "00000000000000000000000000000abc:0000000000000def:0:1",
+        ];
+        span_with_links!(headers, Level::INFO, "consume", batch_size = 3).in_scope(|| {});
+    });
+    let span = &exporter.spans_named("consume")[0];
+    // The malformed header was skipped; the W3C and Jaeger ones were linked, in order.
+    assert_eq!(span.links.len(), 2);
+    assert_eq!(
+        span.links[0].trace_id,
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c)
+    );
+    assert_eq!(span.links[0].span_id, SpanId::from(0xb7ad6b7169203331));
+    assert_eq!(span.links[1].trace_id, TraceId::from(0xabc));
+    assert_eq!(span.links[1].span_id, SpanId::from(0xdef));
+    assert_eq!(span.attributes["batch_size"], AttributeValue::I64(3));
+}
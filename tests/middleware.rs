@@ -0,0 +1,154 @@
+use std::convert::Infallible;
+use std::future::Future;
+
+use http::{Request, Response, StatusCode};
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::middleware::OtelLayer;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OTelSpanData, OpenTelemetrySdk, SpanKind, SpanStatus,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::propagator::{
+    JaegerPropagator, TextMapPropagator, W3CTraceContextPropagator,
+};
+use tower::{service_fn, Layer, ServiceExt};
+use tracing_subscriber::prelude::*;
+
+const TRACE_ID: &str = "0af7651916cd43dd8448eb211c80319c";
+const SPAN_ID: &str = "b7ad6b7169203331";
+
+// Sends `request` through `layer` with an SDK using `propagator`, returning the server span.
+fn serve<P>(propagator: P, layer: OtelLayer, request: Request<()>) -> OTelSpanData
+where
+    P: TextMapPropagator + Send + Sync + 'static,
+{
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_propagator(propagator)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let service = layer.layer(service_fn(|_: Request<()>| async {
+            Ok::<_, Infallible>(Response::new(()))
+        }));
+        runtime.block_on(service.oneshot(request)).unwrap();
+    });
+    exporter.spans_named("GET /").remove(0)
+}
+
+#[test]
+fn default_layer_extracts_with_the_sdk_propagator() {
+    let request = Request::get("/")
+        .header("uber-trace-id", format!("{TRACE_ID}:{SPAN_ID}:0:1"))
+        .body(())
+        .unwrap();
+    let span = serve(JaegerPropagator::new(), OtelLayer::default(), request);
+    assert_eq!(span.trace_id.to_string(), TRACE_ID);
+    assert_eq!(
+        span.parent_span_id.map(|id| id.to_string()).as_deref(),
+        Some(SPAN_ID)
+    );
+    assert!(span.parent_is_remote);
+}
+
+#[test]
+fn trace_state_comes_from_the_same_extraction() {
+    let request = Request::get("/")
+        .header("traceparent", format!("00-{TRACE_ID}-{SPAN_ID}-01"))
+        .header("tracestate", "vendor=value")
+        .body(())
+        .unwrap();
+    let span = serve(JaegerPropagator::new(), OtelLayer::default(), request);
+    // The SDK's Jaeger propagator ignores W3C headers, tracestate included.
+    assert_ne!(span.trace_id.to_string(), TRACE_ID);
+    assert!(span.trace_state.is_empty());
+
+    let request = Request::get("/")
+        .header("traceparent", format!("00-{TRACE_ID}-{SPAN_ID}-01"))
+        .header("tracestate", "vendor=value")
+        .body(())
+        .unwrap();
+    let span = serve(
+        JaegerPropagator::new(),
+        OtelLayer::new(W3CTraceContextPropagator::new()),
+        request,
+    );
+    assert_eq!(span.trace_id.to_string(), TRACE_ID);
+    assert_eq!(span.trace_state.header_value(), "vendor=value");
+}
+
+// Sends `request` to `handler` through the default layer, returning the only exported span.
+fn handle<F, Fut>(request: Request<()>, handler: F) -> OTelSpanData
+where
+    F: FnMut(Request<()>) -> Fut,
+    Fut: Future<Output = Result<Response<()>, &'static str>>,
+{
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let service = OtelLayer::default().layer(service_fn(handler));
+        let _ = runtime.block_on(service.oneshot(request));
+    });
+    let mut spans = exporter.get_finished_spans();
+    assert_eq!(spans.len(), 1);
+    spans.remove(0)
+}
+
+#[test]
+fn server_span_records_the_request_and_response() {
+    let request = Request::post("/users").body(()).unwrap();
+    let span = handle(request, |_| async {
+        let mut response = Response::new(());
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    });
+    assert_eq!(span.name, "POST /users");
+    assert_eq!(span.kind, SpanKind::Server);
+    assert_eq!(
+        span.attributes["http.method"],
+        AttributeValue::String("POST".into())
+    );
+    assert_eq!(
+        span.attributes["http.route"],
+        AttributeValue::String("/users".into())
+    );
+    assert_eq!(
+        span.attributes["http.status_code"],
+        AttributeValue::I64(201)
+    );
+    assert_eq!(span.status, SpanStatus::Unset);
+}
+
+#[test]
+fn server_errors_and_failed_services_set_an_error_status() {
+    let span = handle(Request::get("/").body(()).unwrap(), |_| async {
+        let mut response = Response::new(());
+        *response.status_mut() = StatusCode::BAD_GATEWAY;
+        Ok(response)
+    });
+    assert_eq!(
+        span.attributes["http.status_code"],
+        AttributeValue::I64(502)
+    );
+    assert!(matches!(span.status, SpanStatus::Error { .. }));
+
+    let span = handle(Request::get("/").body(()).unwrap(), |_| async {
+        Err("connection reset")
+    });
+    assert_eq!(
+        span.status,
+        SpanStatus::Error {
+            description: "connection reset".into()
+        }
+    );
+}
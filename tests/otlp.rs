@@ -0,0 +1,386 @@
+#![cfg(feature = "otlp")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    trace_service_server::{TraceService, TraceServiceServer},
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::trace::v1::Span;
+use opentelemetry_tracing::exporter::{
+    ExportError, FileExporter, InMemorySpanExporter, OtlpGrpcExporter, OtlpHttpEncoding,
+    OtlpHttpExporter, SpanExporter,
+};
+use opentelemetry_tracing::opentelemetry_sdk::{OTelSpanData, OpenTelemetrySdk, Resource};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use prost::Message;
+use tokio::net::TcpListener;
+use tonic::transport::{server::TcpIncoming, Server};
+use tracing_subscriber::prelude::*;
+
+// Records the requests it receives, rejecting the first `fail_first`.
+#[derive(Clone, Default)]
+struct MockCollector {
+    requests: Arc<Mutex<Vec<ExportTraceServiceRequest>>>,
+    fail_first: usize,
+}
+
+impl MockCollector {
+    // The number of spans in each request.
+    fn span_counts(&self) -> Vec<usize> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(spans)
+            .map(|spans| spans.len())
+            .collect()
+    }
+}
+
+fn spans(request: &ExportTraceServiceRequest) -> Vec<&Span> {
+    request
+        .resource_spans
+        .iter()
+        .flat_map(|resource_spans| &resource_spans.scope_spans)
+        .flat_map(|scope_spans| &scope_spans.spans)
+        .collect()
+}
+
+#[tonic::async_trait]
+impl TraceService for MockCollector {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportTraceServiceRequest>,
+    ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push(request.into_inner());
+        if requests.len() <= self.fail_first {
+            return Err(tonic::Status::resource_exhausted("too large"));
+        }
+        Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+// Serves `collector` on a runtime of its own and returns its endpoint.
+fn serve(collector: MockCollector) -> String {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            sender.send(listener.local_addr().unwrap()).unwrap();
+            let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+            Server::builder()
+                .add_service(TraceServiceServer::new(collector))
+                .serve_with_incoming(incoming)
+                .await
+                .unwrap();
+        });
+    });
+    format!("http://{}", receiver.recv().unwrap())
+}
+
+// Ten spans of roughly 1 KiB each.
+fn large_spans() -> Vec<OTelSpanData> {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        for _ in 0..10 {
+            tracing::info_span!("large", payload = "x".repeat(1024)).in_scope(|| {});
+        }
+    });
+    exporter.get_finished_spans()
+}
+
+#[test]
+fn large_batches_are_split_into_several_requests() {
+    let collector = MockCollector::default();
+    let exporter = OtlpGrpcExporter::new(serve(collector.clone()))
+        .unwrap()
+        .with_max_request_size(4 * 1024);
+    exporter.export(large_spans()).unwrap();
+    let requests = collector.span_counts();
+    assert!(requests.len() > 1, "{requests:?}");
+    assert!(requests.iter().all(|spans| *spans <= 4), "{requests:?}");
+    assert_eq!(requests.iter().sum::<usize>(), 10);
+}
+
+#[test]
+fn a_failed_request_does_not_stop_the_rest_of_the_batch() {
+    let collector = MockCollector {
+        fail_first: 1,
+        ..MockCollector::default()
+    };
+    let exporter = OtlpGrpcExporter::new(serve(collector.clone()))
+        .unwrap()
+        .with_max_request_size(4 * 1024);
+    let err = exporter.export(large_spans()).unwrap_err();
+    let requests = collector.span_counts();
+    assert!(requests.len() > 1, "{requests:?}");
+    assert_eq!(requests.iter().sum::<usize>(), 10);
+    assert!(
+        err.to_string()
+            .contains(&format!("1 of {} requests failed", requests.len())),
+        "{err}"
+    );
+}
+
+#[test]
+fn small_batches_are_sent_in_one_request() {
+    let collector = MockCollector::default();
+    let exporter = OtlpGrpcExporter::new(serve(collector.clone())).unwrap();
+    exporter.export(large_spans()).unwrap();
+    assert_eq!(collector.span_counts(), [10]);
+}
+
+// Exports the spans `f` creates to a mock collector, returning the single request it got.
+fn export(f: impl FnOnce()) -> ExportTraceServiceRequest {
+    let collector = MockCollector::default();
+    let exporter = OtlpGrpcExporter::new(serve(collector.clone())).unwrap();
+    let spans = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(spans.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+    exporter.export(spans.get_finished_spans()).unwrap();
+    let mut requests = collector.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    requests.remove(0)
+}
+
+#[test]
+fn ids_are_encoded_as_fixed_width_bytes() {
+    let request = export(|| {
+        tracing::info_span!("parent").in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+    });
+    let spans = spans(&request);
+    let (child, parent) = (spans[0], spans[1]);
+    assert_eq!(parent.trace_id.len(), 16);
+    assert_eq!(parent.span_id.len(), 8);
+    assert!(parent.parent_span_id.is_empty());
+    assert_eq!(child.trace_id, parent.trace_id);
+    assert_eq!(child.parent_span_id, parent.span_id);
+}
+
+#[test]
+fn nothing_is_sent_after_shutdown() {
+    let collector = MockCollector::default();
+    let exporter = OtlpGrpcExporter::new(serve(collector.clone())).unwrap();
+    exporter.shutdown();
+    assert!(matches!(
+        exporter.export(large_spans()),
+        Err(ExportError::Shutdown)
+    ));
+    assert!(collector.span_counts().is_empty());
+}
+
+#[test]
+fn attributes_are_sent_with_their_types() {
+    let request = export(|| {
+        tracing::info_span!(
+            "typed",
+            count = 3,
+            retry = true,
+            ratio = 0.5,
+            user = "alice"
+        )
+        .in_scope(|| {});
+    });
+    let attributes: HashMap<&str, &Value> = spans(&request)[0]
+        .attributes
+        .iter()
+        .map(|kv| {
+            (
+                kv.key.as_str(),
+                kv.value.as_ref().unwrap().value.as_ref().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(attributes["count"], &Value::IntValue(3));
+    assert_eq!(attributes["retry"], &Value::BoolValue(true));
+    assert_eq!(attributes["ratio"], &Value::DoubleValue(0.5));
+    assert_eq!(attributes["user"], &Value::StringValue("alice".to_string()));
+}
+
+#[test]
+fn the_resource_is_sent_once_per_request() {
+    let collector = MockCollector::default();
+    let mut exporter = OtlpGrpcExporter::new(serve(collector.clone())).unwrap();
+    exporter.set_resource(&Resource::empty().with_attribute("service.name", "checkout"));
+    exporter.export(large_spans()).unwrap();
+    let requests = collector.requests.lock().unwrap();
+    assert_eq!(requests[0].resource_spans.len(), 1);
+    let resource = requests[0].resource_spans[0].resource.as_ref().unwrap();
+    assert_eq!(resource.attributes.len(), 1);
+    assert_eq!(resource.attributes[0].key, "service.name");
+    assert_eq!(
+        resource.attributes[0].value.as_ref().unwrap().value,
+        Some(Value::StringValue("checkout".to_string()))
+    );
+}
+
+#[test]
+fn spans_are_grouped_by_scope() {
+    let request = export(|| {
+        tracing::info_span!(target: "app::db", "query").in_scope(|| {});
+        tracing::info_span!(target: "app::http", "request").in_scope(|| {});
+        tracing::info_span!(target: "app::db", "commit").in_scope(|| {});
+    });
+    let scopes: Vec<(&str, Vec<&str>)> = request.resource_spans[0]
+        .scope_spans
+        .iter()
+        .map(|scope_spans| {
+            let names = scope_spans.spans.iter().map(|span| span.name.as_str());
+            (
+                scope_spans.scope.as_ref().unwrap().name.as_str(),
+                names.collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        scopes,
+        [
+            ("app::db", vec!["query", "commit"]),
+            ("app::http", vec!["request"])
+        ]
+    );
+}
+
+// The headers of a request, lowercased, and its body.
+type Received = (Vec<String>, Vec<u8>);
+
+// Accepts one HTTP request on a local port, answering `200 OK`. Returns the URL to send it to
+// and a handle yielding the request.
+fn receive_one() -> (String, JoinHandle<Received>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers.push(line.trim().to_lowercase());
+        }
+        let length = headers
+            .iter()
+            .find_map(|header| header.strip_prefix("content-length: "))
+            .map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        (headers, body)
+    });
+    (url, handle)
+}
+
+// Exports two spans over OTLP/HTTP with `encoding`, returning them, the content type and body.
+fn export_http(encoding: OtlpHttpEncoding) -> (Vec<OTelSpanData>, String, Vec<u8>) {
+    let spans = large_spans().into_iter().take(2).collect::<Vec<_>>();
+    let (url, request) = receive_one();
+    let exporter = OtlpHttpExporter::new(format!("{url}/v1/traces"))
+        .unwrap()
+        .with_encoding(encoding);
+    exporter.export(spans.clone()).unwrap();
+    let (headers, body) = request.join().unwrap();
+    assert_eq!(headers[0], "post /v1/traces http/1.1");
+    let content_type = headers
+        .iter()
+        .find_map(|header| header.strip_prefix("content-type: "))
+        .unwrap()
+        .to_string();
+    (spans, content_type, body)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+fn http_exporter_posts_protobuf() {
+    let (spans, content_type, body) = export_http(OtlpHttpEncoding::Protobuf);
+    assert_eq!(content_type, "application/x-protobuf");
+    let request = ExportTraceServiceRequest::decode(body.as_slice()).unwrap();
+    let sent = self::spans(&request);
+    assert_eq!(sent.len(), 2);
+    for (sent, span) in sent.iter().zip(&spans) {
+        assert_eq!(hex(&sent.trace_id), span.trace_id.to_string());
+        assert_eq!(hex(&sent.span_id), span.span_id.to_string());
+        assert_eq!(sent.name, span.name);
+    }
+}
+
+#[test]
+fn http_exporter_posts_json() {
+    let (spans, content_type, body) = export_http(OtlpHttpEncoding::Json);
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let sent = &json["resourceSpans"][0]["scopeSpans"][0]["spans"];
+    assert_eq!(sent.as_array().unwrap().len(), 2);
+    // OTLP/JSON writes ids as hex.
+    assert_eq!(sent[0]["traceId"], spans[0].trace_id.to_string());
+    assert_eq!(sent[0]["spanId"], spans[0].span_id.to_string());
+}
+
+// The length-delimited requests in the file at `path`.
+fn read_requests(path: &std::path::Path) -> Vec<ExportTraceServiceRequest> {
+    let bytes = std::fs::read(path).unwrap();
+    let mut buf = bytes.as_slice();
+    let mut requests = Vec::new();
+    while !buf.is_empty() {
+        requests.push(ExportTraceServiceRequest::decode_length_delimited(&mut buf).unwrap());
+    }
+    requests
+}
+
+#[test]
+fn file_exporter_appends_batches_and_rolls_over() {
+    let dir = std::env::temp_dir().join(format!("otel-file-exporter-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("spans.otlp");
+    let mut first = large_spans();
+    let second = first.split_off(4);
+
+    let exporter = FileExporter::new(&path).unwrap();
+    exporter.export(first.clone()).unwrap();
+    exporter.export(second.clone()).unwrap();
+    exporter.shutdown();
+    assert!(matches!(
+        exporter.export(first.clone()),
+        Err(ExportError::Shutdown)
+    ));
+    let span_counts: Vec<usize> = read_requests(&path)
+        .iter()
+        .map(|request| spans(request).len())
+        .collect();
+    assert_eq!(span_counts, [4, 6]);
+
+    // Together the batches exceed 8 KiB, so the second one starts a new file.
+    let path = dir.join("rolled.otlp");
+    let exporter = FileExporter::new(&path)
+        .unwrap()
+        .with_max_file_size(8 * 1024);
+    exporter.export(first).unwrap();
+    exporter.export(second).unwrap();
+    exporter.shutdown();
+    assert_eq!(read_requests(&path).len(), 1);
+    let rolled = read_requests(&dir.join("rolled.otlp.1"));
+    assert_eq!(spans(&rolled[0]).len(), 6);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use opentelemetry_tracing::clock::ManualClock;
+use opentelemetry_tracing::exporter::{ExportError, InMemorySpanExporter, SpanExporter};
+use opentelemetry_tracing::opentelemetry_sdk::AlwaysOff;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OTelSpanData, OpenTelemetrySdk, OtelSpanExt, RemoteSpanContext, SpanContext,
+    SpanId, SpanStatus, TraceFlags, TraceId,
+};
+use opentelemetry_tracing::processor::{
+    BatchConfig, BatchSpanProcessor, DeduplicatingProcessor, MaxDurationSpanProcessor,
+    RootSpanProcessor, SimpleSpanProcessor, SpanProcessor, TailSamplingProcessor, REPEAT_COUNT,
+    TIMED_OUT,
+};
+use tracing_subscriber::prelude::*;
+
+fn run<P>(processor: P, f: impl FnOnce())
+where
+    P: SpanProcessor + Send + Sync + 'static,
+{
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(processor)
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+}
+
+#[test]
+fn root_span_processor_exports_only_local_roots() {
+    let exporter = InMemorySpanExporter::new();
+    let processor = RootSpanProcessor::new(SimpleSpanProcessor::new(Box::new(exporter.clone())));
+    run(processor, || {
+        tracing::info_span!("root").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {
+                tracing::info_span!("grandchild").in_scope(|| {});
+            });
+        });
+        // A span continuing a trace from another service is this service's entry point.
+        let carrier = HashMap::from([(
+            "traceparent".to_string(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+        )]);
+        let server = tracing::info_span!("server");
+        server.set_parent_from(&carrier);
+        server.in_scope(|| tracing::info_span!("handler").in_scope(|| {}));
+    });
+    let names: Vec<String> = exporter
+        .get_finished_spans()
+        .into_iter()
+        .map(|span| span.name)
+        .collect();
+    assert_eq!(names, ["root", "server"]);
+}
+
+// Spans recorded by the SDK, one per name, for driving processors directly.
+fn finished_spans(names: &[&'static str]) -> Vec<OTelSpanData> {
+    let exporter = InMemorySpanExporter::new();
+    run(SimpleSpanProcessor::new(Box::new(exporter.clone())), || {
+        for name in names {
+            tracing::info_span!("span", otel.name = *name).in_scope(|| {});
+        }
+    });
+    exporter.get_finished_spans()
+}
+
+#[test]
+fn deduplicating_processor_collapses_identical_consecutive_spans() {
+    let exporter = InMemorySpanExporter::new();
+    run(
+        DeduplicatingProcessor::new(SimpleSpanProcessor::new(Box::new(exporter.clone()))),
+        || {
+            tracing::info_span!("request").in_scope(|| {
+                for _ in 0..3 {
+                    tracing::info_span!("query", table = "users").in_scope(|| {});
+                }
+                tracing::info_span!("query", table = "orders").in_scope(|| {});
+            });
+        },
+    );
+    let spans = exporter.get_finished_spans();
+    let summary: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            (
+                span.name.as_str(),
+                span.attributes.get(REPEAT_COUNT).cloned(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("query", Some(AttributeValue::I64(3))),
+            ("query", None),
+            ("request", None),
+        ]
+    );
+    assert_eq!(spans[0].attributes["table"], "users".into());
+}
+
+#[test]
+fn deduplicating_processor_passes_held_spans_on_when_flushed() {
+    let exporter = InMemorySpanExporter::new();
+    let processor =
+        DeduplicatingProcessor::new(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+            .with_capacity(1);
+    let mut spans = finished_spans(&["a", "b"]);
+    // Children of different traces, so neither is passed on as a local root.
+    for (i, span) in spans.iter_mut().enumerate() {
+        span.trace_id = TraceId::from(i as u128 + 1);
+        span.parent_span_id = Some(SpanId::from(1));
+    }
+    processor.on_end(&spans[0]);
+    assert!(exporter.get_finished_spans().is_empty());
+    processor.on_end(&spans[1]);
+    assert_eq!(exporter.get_finished_spans(), spans[..1]);
+    processor.force_flush();
+    assert_eq!(exporter.get_finished_spans(), spans);
+}
+
+#[test]
+fn max_duration_watchdog_exports_spans_that_never_end() {
+    let exporter = InMemorySpanExporter::new();
+    let span = finished_spans(&["leaked"]).remove(0);
+    let clock = ManualClock::new(span.start_time);
+    let processor =
+        MaxDurationSpanProcessor::new(Box::new(exporter.clone()), Duration::from_secs(1))
+            .with_clock(clock.clone())
+            .with_check_interval(Duration::from_millis(5));
+    processor.on_start(&span);
+    assert!(exporter.get_finished_spans().is_empty());
+
+    // No other span starts or ends; only the watchdog can notice.
+    clock.advance(Duration::from_secs(2));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while exporter.get_finished_spans().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(
+        exporter.spans_with_attribute("otel.timed_out", true).len(),
+        1
+    );
+
+    // Closing it later doesn't export it again.
+    processor.on_end(&span);
+    processor.shutdown();
+    assert_eq!(exporter.get_finished_spans().len(), 1);
+}
+
+#[test]
+fn max_duration_processor_forgets_the_oldest_timed_out_spans() {
+    let exporter = InMemorySpanExporter::new();
+    let span = finished_spans(&["leaked"]).remove(0);
+    let clock = ManualClock::new(span.start_time);
+    let processor =
+        MaxDurationSpanProcessor::new(Box::new(exporter.clone()), Duration::from_secs(1))
+            .with_clock(clock.clone());
+    let spans: Vec<OTelSpanData> = (1..=4097)
+        .map(|id| OTelSpanData {
+            span_id: SpanId::from(id),
+            ..span.clone()
+        })
+        .collect();
+    // Each span has timed out by the time it starts, so they time out in order.
+    clock.advance(Duration::from_secs(2));
+    for span in &spans {
+        processor.on_start(span);
+    }
+    assert_eq!(exporter.get_finished_spans().len(), 4097);
+
+    // The first span to time out is no longer remembered; the last one is.
+    processor.on_end(&spans[4096]);
+    assert_eq!(exporter.get_finished_spans().len(), 4097);
+    processor.on_end(&spans[0]);
+    assert_eq!(exporter.get_finished_spans().len(), 4098);
+}
+
+#[test]
+fn max_duration_processor_forgets_spans_that_stop_recording() {
+    let exporter = InMemorySpanExporter::new();
+    let clock = ManualClock::new(SystemTime::now());
+    let processor =
+        MaxDurationSpanProcessor::new(Box::new(exporter.clone()), Duration::from_secs(1))
+            .with_clock(clock.clone());
+    run(processor, || {
+        let unsampled = TraceFlags::new(0);
+        tracing::info_span!("reparented").set_parent_context(SpanContext::new_remote(
+            TraceId::from(1),
+            SpanId::from(2),
+            unsampled,
+        ));
+        tracing::info_span!("replaced").set_remote_parent(RemoteSpanContext::new(
+            TraceId::from(3),
+            SpanId::from(4),
+            unsampled,
+        ));
+        let probe = tracing::info_span!("probe");
+        // Ending the probe checks the open spans for any that timed out.
+        clock.advance(Duration::from_secs(2));
+        drop(probe);
+    });
+    let spans = exporter.get_finished_spans();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name, "probe");
+    assert!(exporter.spans_with_attribute(TIMED_OUT, true).is_empty());
+}
+
+fn has_error(spans: &[OTelSpanData]) -> bool {
+    spans
+        .iter()
+        .any(|span| matches!(span.status, SpanStatus::Error { .. }))
+}
+
+// The spans of one trace, child first, with the given status codes.
+fn finished_trace(root_status: &'static str, child_status: &'static str) -> Vec<OTelSpanData> {
+    let exporter = InMemorySpanExporter::new();
+    run(SimpleSpanProcessor::new(Box::new(exporter.clone())), || {
+        tracing::info_span!("root", otel.status_code = root_status).in_scope(|| {
+            tracing::info_span!("child", otel.status_code = child_status).in_scope(|| {});
+        });
+    });
+    exporter.get_finished_spans()
+}
+
+#[test]
+fn tail_sampling_keeps_only_traces_the_policy_accepts() {
+    let exporter = InMemorySpanExporter::new();
+    run(
+        TailSamplingProcessor::new(Box::new(exporter.clone()), has_error),
+        || {
+            for child_status in ["ok", "error"] {
+                tracing::info_span!("root", child_status).in_scope(|| {
+                    tracing::info_span!("child", otel.status_code = child_status).in_scope(|| {});
+                });
+            }
+        },
+    );
+    let spans = exporter.get_finished_spans();
+    assert_eq!(spans.len(), 2);
+    assert!(spans.iter().all(|span| span.trace_id == spans[0].trace_id));
+    assert!(has_error(&spans));
+}
+
+#[test]
+fn tail_sampling_decides_on_timed_out_traces_on_force_flush() {
+    let exporter = InMemorySpanExporter::new();
+    let clock = ManualClock::new(SystemTime::now());
+    let processor = TailSamplingProcessor::new(Box::new(exporter.clone()), |_: &[_]| true)
+        .with_timeout(Duration::from_secs(1))
+        .with_clock(clock.clone());
+    let trace = finished_trace("ok", "ok");
+    processor.on_end(&trace[0]);
+    processor.force_flush();
+    assert!(exporter.get_finished_spans().is_empty());
+
+    clock.advance(Duration::from_secs(2));
+    processor.force_flush();
+    assert_eq!(exporter.get_finished_spans(), &trace[..1]);
+}
+
+#[test]
+fn tail_sampling_late_spans_follow_their_trace_decision() {
+    let exporter = InMemorySpanExporter::new();
+    let clock = ManualClock::new(SystemTime::now());
+    let processor = TailSamplingProcessor::new(Box::new(exporter.clone()), has_error)
+        .with_timeout(Duration::from_secs(1))
+        .with_clock(clock.clone());
+    // Roots closing before one of their children, e.g. one spawned without waiting for it.
+    let kept = finished_trace("error", "ok");
+    processor.on_end(&kept[1]);
+    processor.on_end(&kept[0]);
+    let dropped = finished_trace("ok", "error");
+    processor.on_end(&dropped[1]);
+    processor.on_end(&dropped[0]);
+
+    clock.advance(Duration::from_secs(2));
+    processor.force_flush();
+    assert_eq!(
+        exporter.get_finished_spans(),
+        [kept[1].clone(), kept[0].clone()]
+    );
+}
+
+// Records the calls the SDK makes, by span name, each prefixed with `label`.
+#[derive(Clone, Default)]
+struct RecordingProcessor {
+    calls: Arc<Mutex<Vec<String>>>,
+    label: &'static str,
+}
+
+impl RecordingProcessor {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{}{call}", self.label));
+    }
+}
+
+impl SpanProcessor for RecordingProcessor {
+    fn on_start(&self, span: &OTelSpanData) {
+        self.record(format!("start {}", span.name));
+    }
+
+    fn on_end(&self, span: &OTelSpanData) {
+        self.record(format!("end {}", span.name));
+    }
+
+    fn force_flush(&self) {
+        self.record("force_flush".to_string());
+    }
+
+    fn shutdown(&self) {
+        self.record("shutdown".to_string());
+    }
+}
+
+#[test]
+fn processors_see_spans_start_and_end() {
+    let processor = RecordingProcessor::default();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(processor.clone())
+        .build()
+        .unwrap();
+    let handle = sdk.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("parent").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {});
+        });
+    });
+    handle.force_flush();
+    handle.shutdown();
+    assert_eq!(
+        processor.calls(),
+        [
+            "start parent",
+            "start child",
+            "end child",
+            "end parent",
+            "force_flush",
+            "shutdown"
+        ]
+    );
+}
+
+#[test]
+fn processors_do_not_see_unsampled_spans() {
+    let processor = RecordingProcessor::default();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(AlwaysOff)
+        .with_span_processor(processor.clone())
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("dropped").in_scope(|| {});
+    });
+    assert!(processor.calls().is_empty());
+}
+
+#[test]
+fn simple_span_processor_exports_each_recording_span() {
+    let exporter = InMemorySpanExporter::new();
+    let processor = SimpleSpanProcessor::new(Box::new(exporter.clone()));
+    let span = finished_spans(&["work"]).remove(0);
+    processor.on_end(&span);
+    assert_eq!(exporter.get_finished_spans(), std::slice::from_ref(&span));
+
+    processor.on_end(&OTelSpanData {
+        is_recording: false,
+        ..span.clone()
+    });
+    processor.shutdown();
+    processor.on_end(&span);
+    assert_eq!(exporter.get_finished_spans().len(), 1);
+}
+
+// Records the size of each batch it's handed.
+#[derive(Clone, Default)]
+struct BatchSizes(Arc<Mutex<Vec<usize>>>);
+
+impl SpanExporter for BatchSizes {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        self.0.lock().unwrap().push(spans.len());
+        Ok(())
+    }
+
+    fn shutdown(&self) {}
+}
+
+#[test]
+fn batch_span_processor_exports_full_batches_and_the_rest_on_force_flush() {
+    let sizes = BatchSizes::default();
+    let config = BatchConfig::default()
+        .with_max_export_batch_size(2)
+        .with_scheduled_delay(Duration::from_secs(3600));
+    let processor = BatchSpanProcessor::new(Box::new(sizes.clone()), config);
+    for span in finished_spans(&["a", "b", "c", "d", "e"]) {
+        processor.on_end(&span);
+    }
+    processor.force_flush();
+    assert_eq!(*sizes.0.lock().unwrap(), [2, 2, 1]);
+    processor.shutdown();
+}
+
+#[test]
+fn batch_span_processor_exports_partial_batches_after_the_scheduled_delay() {
+    let exporter = InMemorySpanExporter::new();
+    let config = BatchConfig::default().with_scheduled_delay(Duration::from_millis(10));
+    let processor = BatchSpanProcessor::new(Box::new(exporter.clone()), config);
+    processor.on_end(&finished_spans(&["work"])[0]);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while exporter.get_finished_spans().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(exporter.get_finished_spans().len(), 1);
+}
+
+#[test]
+fn batch_span_processor_can_sort_batches_by_start_time() {
+    let exporter = InMemorySpanExporter::new();
+    let config = BatchConfig::default()
+        .with_scheduled_delay(Duration::from_secs(3600))
+        .with_sort_by_start_time(true);
+    let processor = BatchSpanProcessor::new(Box::new(exporter.clone()), config);
+    let mut spans = finished_spans(&["late", "early", "tie_first", "tie_second"]);
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let starts = [2, 0, 1, 1];
+    for (span, offset) in spans.iter_mut().zip(starts) {
+        span.start_time = start + Duration::from_secs(offset);
+        processor.on_end(span);
+    }
+    processor.force_flush();
+    let names: Vec<String> = exporter
+        .get_finished_spans()
+        .into_iter()
+        .map(|span| span.name)
+        .collect();
+    // Spans starting together keep the order they ended in.
+    assert_eq!(names, ["early", "tie_first", "tie_second", "late"]);
+    processor.shutdown();
+}
+
+// Blocks in `export` until released, reporting when it got there.
+struct BlockingExporter {
+    inner: InMemorySpanExporter,
+    exporting: Mutex<Sender<()>>,
+    release: Mutex<Receiver<()>>,
+}
+
+impl SpanExporter for BlockingExporter {
+    fn export(&self, spans: Vec<OTelSpanData>) -> Result<(), ExportError> {
+        self.exporting.lock().unwrap().send(()).unwrap();
+        self.release.lock().unwrap().recv().unwrap();
+        self.inner.export(spans)
+    }
+
+    fn shutdown(&self) {}
+}
+
+#[test]
+fn batch_span_processor_drops_spans_when_the_queue_is_full() {
+    let exporter = InMemorySpanExporter::new();
+    let (exporting_sender, exporting) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel();
+    let blocking = BlockingExporter {
+        inner: exporter.clone(),
+        exporting: Mutex::new(exporting_sender),
+        release: Mutex::new(release_receiver),
+    };
+    let config = BatchConfig::default()
+        .with_max_queue_size(1)
+        .with_max_export_batch_size(1);
+    let processor = BatchSpanProcessor::new(Box::new(blocking), config);
+    let spans = finished_spans(&["a", "b", "c"]);
+
+    // The worker holds on to the first span while it exports it, so the second fills the queue.
+    processor.on_end(&spans[0]);
+    exporting.recv().unwrap();
+    processor.on_end(&spans[1]);
+    processor.on_end(&spans[2]);
+    assert_eq!(processor.dropped_spans(), 1);
+
+    release.send(()).unwrap();
+    release.send(()).unwrap();
+    processor.shutdown();
+    assert_eq!(exporter.get_finished_spans(), &spans[..2]);
+}
+
+#[test]
+fn every_processor_sees_every_span_in_registration_order() {
+    let (first, second) = (InMemorySpanExporter::new(), InMemorySpanExporter::new());
+    let log = RecordingProcessor {
+        label: "a: ",
+        ..RecordingProcessor::default()
+    };
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(log.clone())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(first.clone())))
+        .with_span_processor(RecordingProcessor {
+            label: "b: ",
+            ..log.clone()
+        })
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(second.clone())))
+        .build()
+        .unwrap();
+    let handle = sdk.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("parent").in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+    });
+    handle.force_flush();
+    handle.shutdown();
+
+    let names = |exporter: &InMemorySpanExporter| -> Vec<String> {
+        exporter
+            .get_finished_spans()
+            .into_iter()
+            .map(|span| span.name)
+            .collect()
+    };
+    assert_eq!(names(&first), ["child", "parent"]);
+    assert_eq!(names(&second), ["child", "parent"]);
+    assert_eq!(
+        log.calls(),
+        [
+            "a: start parent",
+            "b: start parent",
+            "a: start child",
+            "b: start child",
+            "a: end child",
+            "b: end child",
+            "a: end parent",
+            "b: end parent",
+            "a: force_flush",
+            "b: force_flush",
+            "a: shutdown",
+            "b: shutdown",
+        ]
+    );
+}
+
+// Keeps the snapshots `on_start` is given.
+#[derive(Clone, Default)]
+struct StartSnapshots(Arc<Mutex<Vec<OTelSpanData>>>);
+
+impl SpanProcessor for StartSnapshots {
+    fn on_start(&self, span: &OTelSpanData) {
+        self.0.lock().unwrap().push(span.clone());
+    }
+
+    fn on_end(&self, _span: &OTelSpanData) {}
+
+    fn force_flush(&self) {}
+
+    fn shutdown(&self) {}
+}
+
+#[test]
+fn start_snapshots_are_not_changed_by_the_live_span() {
+    let snapshots = StartSnapshots::default();
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(snapshots.clone())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("work", step = 1);
+        span.record("step", 2);
+        span.set_attribute("late", true);
+        span.set_status(SpanStatus::Ok);
+    });
+    let started = snapshots.0.lock().unwrap()[0].clone();
+    assert_eq!(started.attributes.get("step"), Some(&1i64.into()));
+    assert_eq!(started.attributes.get("late"), None);
+    assert_eq!(started.status, SpanStatus::Unset);
+    assert_eq!(started.end_time, None);
+
+    let ended = &exporter.spans_named("work")[0];
+    assert_eq!(ended.attributes.get("step"), Some(&2i64.into()));
+    assert_eq!(ended.attributes.get("late"), Some(&true.into()));
+    assert_eq!(ended.status, SpanStatus::Ok);
+    assert_eq!(started.span_id, ended.span_id);
+}
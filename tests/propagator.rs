@@ -0,0 +1,578 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::id_generator::FixedIdGenerator;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    parse_jaeger_trace_id, OpenTelemetrySdk, OtelSpanExt, ParseError, RemoteSpanContext,
+    SpanContext, SpanId, TraceFlags, TraceId, TraceState,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::propagator::{
+    extract_context, inject_context, B3Propagator, CompositePropagator, JaegerPropagator,
+    TextMapPropagator, W3CTraceContextPropagator,
+};
+use opentelemetry_tracing::sampler::{SamplingContext, SamplingResult, ShouldSample};
+use tracing_subscriber::prelude::*;
+
+fn with_exporter(f: impl FnOnce()) -> InMemorySpanExporter {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+    exporter
+}
+
+fn carrier(headers: &[(&str, &str)]) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn set_parent_from_applies_trace_state_and_baggage() {
+    let carrier = carrier(&[
+        (
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        ),
+        ("tracestate", "vendor=value"),
+        ("baggage", "user=alice"),
+    ]);
+    let mut baggage = None;
+    let exporter = with_exporter(|| {
+        let span = tracing::info_span!("server");
+        span.set_parent_from(&carrier);
+        baggage = span.baggage().get("user").map(str::to_string);
+        span.in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+    });
+    assert_eq!(baggage.as_deref(), Some("alice"));
+    let server = &exporter.spans_named("server")[0];
+    assert_eq!(
+        server.trace_id.to_string(),
+        "0af7651916cd43dd8448eb211c80319c"
+    );
+    assert_eq!(server.trace_state.header_value(), "vendor=value");
+    // Inherited by spans created under it.
+    let child = &exporter.spans_named("child")[0];
+    assert_eq!(child.trace_state.header_value(), "vendor=value");
+}
+
+#[test]
+fn set_parent_from_without_a_context_keeps_the_span_a_root() {
+    let carrier = carrier(&[("baggage", "user=bob")]);
+    let mut baggage = None;
+    let exporter = with_exporter(|| {
+        let span = tracing::info_span!("server");
+        span.set_parent_from(&carrier);
+        baggage = span.baggage().get("user").map(str::to_string);
+    });
+    assert_eq!(baggage.as_deref(), Some("bob"));
+    let server = &exporter.spans_named("server")[0];
+    assert_eq!(server.parent_span_id, None);
+    assert!(server.trace_state.is_empty());
+}
+
+fn remote_context() -> RemoteSpanContext {
+    RemoteSpanContext::new(
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+        SpanId::from(0xb7ad6b7169203331),
+        TraceFlags::SAMPLED,
+    )
+}
+
+#[test]
+fn a_remote_parent_stand_in_is_not_exported_but_parents_its_children() {
+    let exporter = with_exporter(|| {
+        let remote = tracing::info_span!("remote");
+        remote.set_remote_parent(remote_context());
+        tracing::info_span!(parent: &remote, "server").in_scope(|| {
+            tracing::info_span!("handler").in_scope(|| {});
+        });
+    });
+    assert!(exporter.spans_named("remote").is_empty());
+    let server = &exporter.spans_named("server")[0];
+    assert_eq!(server.trace_id, remote_context().trace_id);
+    assert_eq!(server.parent_span_id, Some(remote_context().span_id));
+    assert!(server.parent_is_remote);
+    let handler = &exporter.spans_named("handler")[0];
+    assert_eq!(handler.parent_span_id, Some(server.span_id));
+    assert!(!handler.parent_is_remote);
+}
+
+#[test]
+fn root_spans_created_under_an_attached_remote_context_continue_it() {
+    let exporter = with_exporter(|| {
+        {
+            let _guard = remote_context().attach();
+            tracing::info_span!("attached").in_scope(|| {
+                tracing::info_span!("child").in_scope(|| {});
+            });
+        }
+        tracing::info_span!("detached").in_scope(|| {});
+    });
+    let attached = &exporter.spans_named("attached")[0];
+    assert_eq!(attached.trace_id, remote_context().trace_id);
+    assert_eq!(attached.parent_span_id, Some(remote_context().span_id));
+    assert!(attached.parent_is_remote);
+    // Spans with a local parent keep it.
+    let child = &exporter.spans_named("child")[0];
+    assert_eq!(child.parent_span_id, Some(attached.span_id));
+    let detached = &exporter.spans_named("detached")[0];
+    assert_ne!(detached.trace_id, remote_context().trace_id);
+    assert_eq!(detached.parent_span_id, None);
+}
+
+#[test]
+fn the_sampled_flag_is_encoded_in_both_formats() {
+    let mut headers = HashMap::new();
+    with_exporter(|| {
+        let span = tracing::info_span!("sampled");
+        W3CTraceContextPropagator::new().inject(&span, &mut headers);
+        JaegerPropagator::new().inject(&span, &mut headers);
+    });
+    // Two hex chars in W3C, the trailing hex field in Jaeger.
+    assert!(headers["traceparent"].ends_with("-01"));
+    assert!(headers["uber-trace-id"].ends_with(":1"));
+
+    let unsampled = carrier(&[
+        (
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00",
+        ),
+        (
+            "uber-trace-id",
+            "0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331:0:0",
+        ),
+    ]);
+    let w3c = W3CTraceContextPropagator::new()
+        .extract(&unsampled)
+        .unwrap();
+    let jaeger = JaegerPropagator::new().extract(&unsampled).unwrap();
+    assert_eq!(w3c.flags, TraceFlags::new(0));
+    assert_eq!(jaeger.flags, TraceFlags::new(0));
+    assert!(!TraceFlags::new(0).is_sampled());
+    assert_eq!(TraceFlags::new(0).with_sampled(true), TraceFlags::SAMPLED);
+    assert_eq!(TraceFlags::new(0x03).with_sampled(false).to_u8(), 0x02);
+}
+
+#[test]
+fn an_unknown_trace_state_reaches_downstream_calls_untouched() {
+    let incoming = carrier(&[
+        (
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        ),
+        ("tracestate", "vendorx=abc"),
+    ]);
+    let mut outgoing = HashMap::new();
+    with_exporter(|| {
+        let server = tracing::info_span!("server");
+        server.set_parent_from(&incoming);
+        let client = tracing::info_span!(parent: &server, "client");
+        W3CTraceContextPropagator::new().inject(&client, &mut outgoing);
+    });
+    assert_eq!(outgoing["tracestate"], "vendorx=abc");
+    assert!(outgoing["traceparent"].starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+}
+
+#[test]
+fn invalid_trace_state_members_are_dropped() {
+    let trace_state = TraceState::from_header("a=1, Upper=2,b=x=y,c=3,a=4, tenant@sys=5");
+    assert_eq!(trace_state.header_value(), "a=1,c=3,tenant@sys=5");
+
+    let many = (0..40)
+        .map(|i| format!("k{i}=v"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let trace_state = TraceState::from_header(&many);
+    assert_eq!(trace_state.len(), TraceState::MAX_ENTRIES);
+    assert_eq!(trace_state.iter().next(), Some(("k0", "v")));
+}
+
+fn composite() -> CompositePropagator {
+    CompositePropagator::new(vec![
+        Box::new(W3CTraceContextPropagator::new()),
+        Box::new(B3Propagator::new()),
+    ])
+}
+
+#[test]
+fn a_composite_propagator_extracts_from_any_of_its_formats() {
+    let b3_only = carrier(&[("b3", "0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-1")]);
+    let context = composite().extract(&b3_only).unwrap();
+    assert_eq!(context.trace_id, remote_context().trace_id);
+    assert_eq!(context.span_id, remote_context().span_id);
+    assert!(context.flags.is_sampled());
+    assert!(context.is_remote);
+
+    assert_eq!(composite().extract(&carrier(&[])), None);
+    assert_eq!(composite().fields()[0], "traceparent");
+}
+
+#[test]
+fn a_composite_propagator_injects_every_format() {
+    let mut headers = HashMap::new();
+    with_exporter(|| composite().inject(&tracing::info_span!("client"), &mut headers));
+    assert!(headers.contains_key("traceparent"));
+    assert_eq!(headers["x-b3-traceid"], headers["traceparent"][3..35]);
+}
+
+#[test]
+fn inject_into_writes_the_headers_of_the_configured_propagator() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_propagator(CompositePropagator::new(vec![
+            Box::new(W3CTraceContextPropagator::new()),
+            Box::new(JaegerPropagator::new()),
+        ]))
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let mut headers = HashMap::new();
+    let mut request = None;
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("client");
+        span.inject_into(&mut headers);
+        request = Some(
+            span.inject_into_request(http::Request::get("/"))
+                .body(())
+                .unwrap(),
+        );
+    });
+    let mut keys: Vec<_> = headers.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["traceparent", "uber-trace-id"]);
+    let request = request.unwrap();
+    assert_eq!(
+        request.headers()["traceparent"],
+        headers["traceparent"].as_str()
+    );
+    assert_eq!(
+        request.headers()["uber-trace-id"],
+        headers["uber-trace-id"].as_str()
+    );
+    let client = &exporter.spans_named("client")[0];
+    assert!(headers["uber-trace-id"].starts_with(&client.trace_id.to_string()));
+}
+
+#[test]
+fn parse_jaeger_trace_id_decodes_hex_fields() {
+    assert_eq!(
+        parse_jaeger_trace_id("0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331:0:1"),
+        Ok((
+            TraceId::from(0x0af7651916cd43dd8448eb211c80319c),
+            SpanId::from(0xb7ad6b7169203331),
+            TraceFlags::SAMPLED
+        ))
+    );
+    // Senders may leave out leading zeros, and the flags are hex as well.
+    assert_eq!(
+        parse_jaeger_trace_id("abc:1f:0:a"),
+        Ok((
+            TraceId::from(0xabc),
+            SpanId::from(0x1f),
+            TraceFlags::new(0x0a)
+        ))
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn jaeger_headers_zero_pad_small_ids() {
+    assert_eq!(TraceId::from(5).to_string(), format!("{}5", "0".repeat(31)));
+    assert_eq!(SpanId::from(5).to_string(), format!("{}5", "0".repeat(15)));
+    let sdk = OpenTelemetrySdk::builder()
+        .with_id_generator(FixedIdGenerator::new(TraceId::from(5), SpanId::from(5)))
+        .build()
+        .unwrap();
+    let header =
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+            tracing::info_span!("small").extract_jaeger_propagation()
+        });
+    assert_eq!(
+        header,
+        format!(
+            "{}5:{}5:{}:1",
+            "0".repeat(31),
+            "0".repeat(15),
+            "0".repeat(16)
+        )
+    );
+}
+
+#[test]
+fn parse_jaeger_trace_id_reports_each_malformed_field() {
+    assert_eq!(
+        parse_jaeger_trace_id("0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331:0:1"),
+        Ok((
+            remote_context().trace_id,
+            remote_context().span_id,
+            TraceFlags::SAMPLED
+        ))
+    );
+    assert_eq!(
+        parse_jaeger_trace_id("0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331"),
+        Err(ParseError::FieldCount(2))
+    );
+    assert_eq!(
+        parse_jaeger_trace_id("xyz:b7ad6b7169203331:0:1"),
+        Err(ParseError::InvalidHex { field: "trace id" })
+    );
+    assert_eq!(
+        parse_jaeger_trace_id("0af7651916cd43dd8448eb211c80319c:xyz:0:1"),
+        Err(ParseError::InvalidHex { field: "span id" })
+    );
+    assert_eq!(
+        parse_jaeger_trace_id("0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331:0:xyz"),
+        Err(ParseError::InvalidHex { field: "flags" })
+    );
+    assert_eq!(
+        parse_jaeger_trace_id("0:b7ad6b7169203331:0:1"),
+        Err(ParseError::ZeroId)
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn set_parent_ignores_a_malformed_header() {
+    let exporter = with_exporter(|| {
+        let span = tracing::info_span!("server");
+        span.set_parent("not-a-header".to_string());
+        span.in_scope(|| {});
+    });
+    let server = &exporter.spans_named("server")[0];
+    assert_eq!(server.parent_span_id, None);
+    assert_ne!(server.trace_id, TraceId::default());
+}
+
+// A layer that does nothing, stacked so the subscriber is no longer a bare `Registry`.
+struct NoopLayer;
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for NoopLayer {}
+
+#[test]
+fn set_parent_and_accessors_work_under_other_layers() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let subscriber = tracing_subscriber::registry()
+        .with(NoopLayer)
+        .with(sdk)
+        .with(NoopLayer);
+    let mut ids = None;
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("server");
+        span.set_parent_from(&carrier(&[(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        )]));
+        ids = Some((span.trace_id(), span.span_id()));
+        span.in_scope(|| {});
+    });
+    let server = &exporter.spans_named("server")[0];
+    assert_eq!(server.trace_id, remote_context().trace_id);
+    assert_eq!(server.parent_span_id, Some(remote_context().span_id));
+    assert_eq!(ids, Some((server.trace_id, server.span_id)));
+}
+
+#[test]
+fn all_zero_contexts_are_not_used_as_parents() {
+    assert!(!SpanContext::new(TraceId::default(), SpanId::from(1), TraceFlags::SAMPLED).is_valid());
+    assert!(!SpanContext::new(TraceId::from(1), SpanId::default(), TraceFlags::SAMPLED).is_valid());
+    assert!(SpanContext::new(TraceId::from(1), SpanId::from(1), TraceFlags::default()).is_valid());
+
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_propagator(CompositePropagator::new(vec![
+            Box::new(W3CTraceContextPropagator::new()),
+            Box::new(JaegerPropagator::new()),
+        ]))
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let jaeger = tracing::info_span!("jaeger");
+        jaeger.set_parent_from(&carrier(&[("uber-trace-id", "0:0:0:1")]));
+        jaeger.in_scope(|| {});
+        let w3c = tracing::info_span!("w3c");
+        w3c.set_parent_from(&carrier(&[(
+            "traceparent",
+            "00-00000000000000000000000000000000-0000000000000000-01",
+        )]));
+        w3c.in_scope(|| {});
+    });
+    for name in ["jaeger", "w3c"] {
+        let span = &exporter.spans_named(name)[0];
+        assert_eq!(span.parent_span_id, None);
+        assert!(!span.parent_is_remote);
+        assert_ne!(span.trace_id, TraceId::default());
+    }
+}
+
+// Samples everything, noting for each span in turn whether its parent is remote.
+#[derive(Clone, Default)]
+struct ParentRecorder(Arc<Mutex<Vec<Option<bool>>>>);
+
+impl ShouldSample for ParentRecorder {
+    fn should_sample(&self, ctx: SamplingContext<'_>) -> SamplingResult {
+        let is_remote = ctx.parent.map(|parent| parent.is_remote);
+        self.0.lock().unwrap().push(is_remote);
+        true.into()
+    }
+}
+
+#[test]
+fn remote_parents_are_told_apart_from_local_ones() {
+    let sampler = ParentRecorder::default();
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(sampler.clone())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let incoming = carrier(&[(
+        "traceparent",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+    )]);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let server = tracing::info_span!("server");
+        server.set_parent_from(&incoming);
+        server.in_scope(|| tracing::info_span!("handler").in_scope(|| {}));
+        let _guard = remote_context().attach();
+        tracing::info_span!("attached").in_scope(|| {});
+    });
+    assert!(exporter.spans_named("server")[0].parent_is_remote);
+    assert!(!exporter.spans_named("handler")[0].parent_is_remote);
+    assert!(exporter.spans_named("attached")[0].parent_is_remote);
+    // Samplers see it too; `server` had no parent yet when it was sampled.
+    // `server`, `handler`, then `attached`.
+    assert_eq!(*sampler.0.lock().unwrap(), [None, Some(false), Some(true)]);
+}
+
+#[test]
+fn propagators_list_the_headers_they_use() {
+    assert_eq!(
+        W3CTraceContextPropagator::new().fields(),
+        ["traceparent", "tracestate"]
+    );
+    assert_eq!(JaegerPropagator::new().fields(), ["uber-trace-id"]);
+    assert_eq!(B3Propagator::new().fields().len(), 6);
+
+    // A composite lists each header once, in the order of its propagators.
+    let composite = CompositePropagator::new(vec![
+        Box::new(W3CTraceContextPropagator::new()),
+        Box::new(JaegerPropagator::new()),
+        Box::new(W3CTraceContextPropagator::new()),
+    ]);
+    assert_eq!(
+        composite.fields(),
+        ["traceparent", "tracestate", "uber-trace-id"]
+    );
+}
+
+#[test]
+fn the_first_propagator_wins_a_conflict_which_is_flagged() {
+    let w3c_then_jaeger = || -> CompositePropagator {
+        CompositePropagator::new(vec![
+            Box::new(W3CTraceContextPropagator::new()),
+            Box::new(JaegerPropagator::new()),
+        ])
+    };
+    let both = carrier(&[
+        (
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        ),
+        ("uber-trace-id", "1234:5678:0:1"),
+    ]);
+    let extracted = w3c_then_jaeger().extract_checked(&both).unwrap();
+    assert_eq!(extracted.context.trace_id, remote_context().trace_id);
+    assert!(extracted.conflict);
+    let jaeger_then_w3c = CompositePropagator::new(vec![
+        Box::new(JaegerPropagator::new()),
+        Box::new(W3CTraceContextPropagator::new()),
+    ]);
+    let extracted = jaeger_then_w3c.extract_checked(&both).unwrap();
+    assert_eq!(extracted.context.trace_id, TraceId::from(0x1234));
+    assert!(extracted.conflict);
+
+    // The same context in both formats is no conflict.
+    let agreeing = carrier(&[
+        (
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        ),
+        (
+            "uber-trace-id",
+            "0af7651916cd43dd8448eb211c80319c:b7ad6b7169203331:0:1",
+        ),
+    ]);
+    assert!(
+        !w3c_then_jaeger()
+            .extract_checked(&agreeing)
+            .unwrap()
+            .conflict
+    );
+
+    // Spans record the conflict as an attribute.
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_propagator(w3c_then_jaeger())
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let conflicting = tracing::info_span!("conflicting");
+        conflicting.set_parent_from(&both);
+        drop(conflicting);
+        let agreed = tracing::info_span!("agreed");
+        agreed.set_parent_from(&agreeing);
+    });
+    let conflicting = &exporter.spans_named("conflicting")[0];
+    assert_eq!(conflicting.trace_id, remote_context().trace_id);
+    assert_eq!(
+        conflicting.attributes.get("context.conflict"),
+        Some(&true.into())
+    );
+    assert_eq!(
+        exporter.spans_named("agreed")[0]
+            .attributes
+            .get("context.conflict"),
+        None
+    );
+}
+
+#[test]
+fn contexts_are_read_from_requests_and_written_to_responses() {
+    let request = http::Request::builder()
+        .header(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        )
+        .body(())
+        .unwrap();
+    let bare = http::Request::new(());
+    // Without an SDK there is no propagator to read them with.
+    assert_eq!(extract_context(&request), None);
+
+    let mut response = http::Response::new(());
+    let mut span_context = None;
+    with_exporter(|| {
+        assert_eq!(extract_context(&request), Some(remote_context()));
+        assert_eq!(extract_context(&bare), None);
+        let span = tracing::info_span!("client");
+        inject_context(&span, &mut response);
+        span_context = span.span_context();
+    });
+    let span_context = span_context.unwrap();
+    assert_eq!(
+        response.headers()["traceparent"],
+        format!("00-{}-{}-01", span_context.trace_id, span_context.span_id)
+    );
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use opentelemetry_tracing::clock::ManualClock;
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    OpenTelemetrySdk, OtelSpanExt, TraceId, TraceState,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::sampler::{
+    AlwaysOff, AlwaysOn, ConsistentProbabilitySampler, Decision, RateLimitingSampler,
+    SamplingContext, SamplingResult, ShouldSample, TargetOverrideSampler, TraceIdRatioBased,
+};
+use tracing::Level;
+use tracing_subscriber::prelude::*;
+
+fn with_sampler<T>(sampler: T, f: impl FnOnce()) -> InMemorySpanExporter
+where
+    T: ShouldSample + Send + Sync + 'static,
+{
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(sampler)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), f);
+    exporter
+}
+
+fn decide(sampler: &dyn ShouldSample, target: &str, name: &str, trace_id: TraceId) -> Decision {
+    let attributes = HashMap::new();
+    let trace_state = TraceState::default();
+    sampler
+        .should_sample(SamplingContext {
+            parent: None,
+            trace_id,
+            name,
+            target,
+            level: Level::INFO,
+            attributes: &attributes,
+            trace_state: &trace_state,
+        })
+        .decision
+}
+
+#[test]
+fn always_off_drops_and_always_on_records() {
+    assert_eq!(
+        decide(&AlwaysOff, "app", "span", TraceId::from(1)),
+        Decision::Drop
+    );
+    assert_eq!(
+        decide(&AlwaysOn, "app", "span", TraceId::from(1)),
+        Decision::RecordAndSample
+    );
+
+    let mut recording = None;
+    let exporter = with_sampler(AlwaysOff, || {
+        let span = tracing::info_span!("off");
+        recording = Some(span.with_otel_span(|otel_span| Some(otel_span.is_recording)));
+        span.in_scope(|| {});
+    });
+    assert_eq!(recording, Some(false));
+    assert!(exporter.get_finished_spans().is_empty());
+
+    let exporter = with_sampler(AlwaysOn, || tracing::info_span!("on").in_scope(|| {}));
+    assert_eq!(exporter.spans_named("on").len(), 1);
+}
+
+#[test]
+fn target_overrides_win_over_the_base_sampler() {
+    let base = TraceIdRatioBased::new(0.5);
+    let sampler = TargetOverrideSampler::new(TraceIdRatioBased::new(0.5))
+        .with_override("health", Decision::Drop)
+        .with_override("health::deep", Decision::RecordAndSample)
+        .with_override("GET /healthz", Decision::Drop);
+    let mut base_decisions = Vec::new();
+    for i in 0..64u128 {
+        let trace_id = TraceId::from(i.wrapping_mul(0x9e3779b97f4a7c15f39cc0605cedc834));
+        assert_eq!(decide(&sampler, "health", "span", trace_id), Decision::Drop);
+        assert_eq!(
+            decide(&sampler, "health::checks", "span", trace_id),
+            Decision::Drop
+        );
+        // The longest matching prefix wins.
+        assert_eq!(
+            decide(&sampler, "health::deep", "span", trace_id),
+            Decision::RecordAndSample
+        );
+        // Names match too.
+        assert_eq!(
+            decide(&sampler, "api", "GET /healthz", trace_id),
+            Decision::Drop
+        );
+        let api = decide(&sampler, "api", "GET /users", trace_id);
+        assert_eq!(api, decide(&base, "api", "GET /users", trace_id));
+        base_decisions.push(api);
+    }
+    // The base sampler really decides both ways for `api`.
+    assert!(base_decisions.contains(&Decision::Drop));
+    assert!(base_decisions.contains(&Decision::RecordAndSample));
+}
+
+// Samples every other span, counting how often it is asked.
+#[derive(Clone, Default)]
+struct Alternating(Arc<AtomicUsize>);
+
+impl Alternating {
+    fn calls(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl ShouldSample for Alternating {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        self.0
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(2)
+            .into()
+    }
+}
+
+// Samples every span, marking it as high priority.
+struct Prioritizing;
+
+impl ShouldSample for Prioritizing {
+    fn should_sample(&self, _ctx: SamplingContext<'_>) -> SamplingResult {
+        SamplingResult {
+            attributes: vec![("sampling.priority".to_string(), "high".into())],
+            ..Decision::RecordAndSample.into()
+        }
+    }
+}
+
+#[test]
+fn sampler_attributes_are_not_overwritten_by_recorded_fields() {
+    let exporter = with_sampler(Prioritizing, || {
+        let span = tracing::info_span!("request", sampling.priority = "low");
+        span.record("sampling.priority", "lowest");
+    });
+    let span = &exporter.spans_named("request")[0];
+    assert_eq!(span.attributes["sampling.priority"], "high".into());
+}
+
+#[test]
+fn the_decision_cache_asks_the_sampler_once_per_trace() {
+    let sampler = Alternating::default();
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(sampler.clone())
+        .with_trace_decision_cache(1)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let first = tracing::info_span!("first");
+        for _ in 0..3 {
+            tracing::info_span!(parent: &first, "first_child").in_scope(|| {});
+        }
+        assert_eq!(sampler.calls(), 1);
+        // A second trace evicts the first one's decision, which is then asked for again.
+        tracing::info_span!("second").in_scope(|| {});
+        assert_eq!(sampler.calls(), 2);
+        tracing::info_span!(parent: &first, "first_child").in_scope(|| {});
+        assert_eq!(sampler.calls(), 3);
+    });
+    // The three cached children followed `first`, the re-asked one got the third (sampling)
+    // answer and `second` got the dropping one.
+    assert_eq!(exporter.spans_named("first").len(), 1);
+    assert_eq!(exporter.spans_named("first_child").len(), 4);
+    assert!(exporter.spans_named("second").is_empty());
+}
+
+#[test]
+fn otel_force_sample_overrides_the_sampler() {
+    let exporter = with_sampler(TraceIdRatioBased::new(0.01), || {
+        for _ in 0..100 {
+            tracing::info_span!("forced", otel.force_sample = true).in_scope(|| {});
+            tracing::info_span!("not_forced", otel.force_sample = false).in_scope(|| {});
+        }
+    });
+    let forced = exporter.spans_named("forced");
+    assert_eq!(forced.len(), 100);
+    for span in &forced {
+        assert_eq!(span.attributes.get("sampling.forced"), Some(&true.into()));
+        assert_eq!(span.attributes.get("otel.force_sample"), None);
+    }
+    assert!(exporter.spans_named("not_forced").len() < 20);
+
+    let exporter = with_sampler(AlwaysOff, || {
+        tracing::info_span!("forced", otel.force_sample = true).in_scope(|| {
+            // Only the flagged span is forced, its children are left to the sampler.
+            tracing::info_span!("child").in_scope(|| {});
+        });
+    });
+    assert_eq!(exporter.spans_named("forced").len(), 1);
+    assert!(exporter.spans_named("child").is_empty());
+}
+
+// The decision and the `ot` tracestate member it leaves, if it changes it.
+fn consistent(ratio: f64, ot: &str) -> (Decision, Option<String>) {
+    let attributes = HashMap::new();
+    let trace_state = TraceState::from_header(&format!("vendor=x,ot={ot}"));
+    let result = ConsistentProbabilitySampler::new(ratio).should_sample(SamplingContext {
+        parent: None,
+        trace_id: TraceId::from(1),
+        name: "span",
+        target: "app",
+        level: Level::INFO,
+        attributes: &attributes,
+        trace_state: &trace_state,
+    });
+    let ot = result
+        .trace_state
+        .map(|trace_state| trace_state.get("ot").unwrap_or_default().to_string());
+    (result.decision, ot)
+}
+
+#[test]
+fn consistent_probability_sampling_follows_the_tracestate() {
+    // A ratio of 0.5 rejects randomness below 0x80000000000000, and records that as `th:8`.
+    assert_eq!(
+        consistent(0.5, "rv:c0000000000000"),
+        (
+            Decision::RecordAndSample,
+            Some("th:8;rv:c0000000000000".to_string())
+        )
+    );
+    assert_eq!(consistent(0.5, "rv:40000000000000"), (Decision::Drop, None));
+    // An inherited threshold wins over the sampler's ratio, and is passed on when sampled.
+    assert_eq!(
+        consistent(0.0, "th:4;rv:80000000000000"),
+        (Decision::RecordAndSample, None)
+    );
+    assert_eq!(
+        consistent(1.0, "th:c;rv:80000000000000"),
+        (Decision::Drop, Some("rv:80000000000000".to_string()))
+    );
+
+    // Root spans without `rv` use the trace id's randomness and start the trace with `th`.
+    let exporter = with_sampler(ConsistentProbabilitySampler::new(1.0), || {
+        tracing::info_span!("root").in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+    });
+    for name in ["root", "child"] {
+        let span = &exporter.spans_named(name)[0];
+        assert_eq!(span.trace_state.get("ot"), Some("th:0"));
+    }
+}
+
+#[test]
+fn rate_limiting_sampler_caps_a_burst_at_its_rate() {
+    let clock = ManualClock::new(SystemTime::now());
+    let sampler = RateLimitingSampler::new(100).with_clock(clock.clone());
+    let exporter = with_sampler(sampler, || {
+        for _ in 0..1000 {
+            tracing::info_span!("burst").in_scope(|| {});
+        }
+        // Half a second later, half the rate is available again.
+        clock.advance(Duration::from_millis(500));
+        for _ in 0..1000 {
+            tracing::info_span!("burst").in_scope(|| {});
+        }
+    });
+    assert_eq!(exporter.get_finished_spans().len(), 150);
+}
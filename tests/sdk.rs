@@ -0,0 +1,1661 @@
+use std::time::{Duration, SystemTime};
+
+use opentelemetry_tracing::clock::ManualClock;
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::id_generator::FixedIdGenerator;
+use opentelemetry_tracing::opentelemetry_sdk::{
+    new_span_with_contexts, AlwaysOff, AttributeValue, ConfigError, DisabledSdk, EventExportMode,
+    OpenTelemetrySdk, OpenTelemetrySdkBuilder, OtelSpanExt, ParseError, RemoteSpanContext,
+    Resource, Severity, SpanContext, SpanId, SpanKind, SpanLimits, SpanLink, SpanStatus,
+    TraceFlags, TraceId, Violation, BYTES_IN, BYTES_OUT, MESSAGING_MESSAGE_INDEX, ROWS_PROCESSED,
+    TRUNCATION_MARKER,
+};
+use opentelemetry_tracing::processor::{
+    BatchConfig, BatchSpanProcessor, RootSpanProcessor, SimpleSpanProcessor,
+};
+use opentelemetry_tracing::sampler::{
+    ConsistentProbabilitySampler, LevelBasedSampler, ParentBased, TraceIdRatioBased,
+};
+use tracing::Level;
+use tracing_subscriber::prelude::*;
+
+fn builder(exporter: &InMemorySpanExporter) -> OpenTelemetrySdkBuilder {
+    OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+}
+
+fn sdk(exporter: &InMemorySpanExporter) -> OpenTelemetrySdk {
+    builder(exporter).build().unwrap()
+}
+
+#[test]
+fn elapsed_follows_the_monotonic_clock() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work");
+        assert_eq!(span.elapsed(), Some(Duration::ZERO));
+        clock.advance(Duration::from_millis(1_500));
+        assert_eq!(span.elapsed(), Some(Duration::from_millis(1_500)));
+        // A wall clock adjustment doesn't change how long the span has been open.
+        clock.set(SystemTime::UNIX_EPOCH);
+        assert_eq!(span.elapsed(), Some(Duration::from_millis(1_500)));
+    });
+    assert_eq!(tracing::info_span!("no_subscriber").elapsed(), None);
+}
+
+#[test]
+fn duration_ignores_wall_clock_adjustments() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let clock = ManualClock::new(start);
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    let mut elapsed = None;
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work");
+        clock.advance(Duration::from_secs(2));
+        clock.set(start - Duration::from_secs(60));
+        elapsed = span.elapsed();
+    });
+    let spans = exporter.get_finished_spans();
+    assert_eq!(spans[0].start_time, start);
+    assert_eq!(spans[0].end_time, Some(start + Duration::from_secs(2)));
+    assert_eq!(spans[0].duration(), Duration::from_secs(2));
+    assert_eq!(elapsed, Some(spans[0].duration()));
+}
+
+#[test]
+fn build_rejects_sampling_ratios_outside_zero_to_one() {
+    let builder = OpenTelemetrySdk::builder;
+    assert_eq!(
+        builder()
+            .with_sampler(TraceIdRatioBased::new(1.5))
+            .build()
+            .err(),
+        Some(ConfigError::InvalidSamplingRatio(1.5))
+    );
+    assert_eq!(
+        builder()
+            .with_sampler(ParentBased::new(ConsistentProbabilitySampler::new(-0.1)))
+            .build()
+            .err(),
+        Some(ConfigError::InvalidSamplingRatio(-0.1))
+    );
+    assert_eq!(
+        builder()
+            .with_sampler(LevelBasedSampler::new(0.5).with_ratio(Level::DEBUG, 2.0))
+            .build()
+            .err(),
+        Some(ConfigError::InvalidSamplingRatio(2.0))
+    );
+    assert!(matches!(
+        builder().with_sampler(TraceIdRatioBased::new(f64::NAN)).build().err(),
+        Some(ConfigError::InvalidSamplingRatio(ratio)) if ratio.is_nan()
+    ));
+    assert!(builder()
+        .with_sampler(ParentBased::new(TraceIdRatioBased::new(0.25)))
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn build_rejects_event_sampling_without_events() {
+    let limits = SpanLimits {
+        max_events: 0,
+        sample_events: true,
+        ..SpanLimits::default()
+    };
+    assert_eq!(
+        OpenTelemetrySdk::builder()
+            .with_span_limits(limits)
+            .build()
+            .err(),
+        Some(ConfigError::EventSamplingWithoutEvents)
+    );
+}
+
+#[test]
+fn build_rejects_batch_limits_that_would_be_coerced() {
+    let build = |config: BatchConfig| {
+        let processor = BatchSpanProcessor::new(Box::new(InMemorySpanExporter::new()), config);
+        OpenTelemetrySdk::builder()
+            .with_span_processor(RootSpanProcessor::new(processor))
+            .build()
+            .err()
+    };
+    assert_eq!(
+        build(BatchConfig::default().with_max_queue_size(0)),
+        Some(ConfigError::ZeroBatchLimit)
+    );
+    assert_eq!(
+        build(BatchConfig::default().with_max_export_batch_size(0)),
+        Some(ConfigError::ZeroBatchLimit)
+    );
+    assert_eq!(
+        build(
+            BatchConfig::default()
+                .with_max_queue_size(10)
+                .with_max_export_batch_size(20)
+        ),
+        Some(ConfigError::ExportBatchLargerThanQueue {
+            max_export_batch_size: 20,
+            max_queue_size: 10,
+        })
+    );
+    assert_eq!(build(BatchConfig::default()), None);
+}
+
+#[test]
+fn build_rejects_an_empty_trace_decision_cache() {
+    assert_eq!(
+        OpenTelemetrySdk::builder()
+            .with_trace_decision_cache(0)
+            .build()
+            .err(),
+        Some(ConfigError::ZeroDecisionCacheCapacity)
+    );
+    let result = OpenTelemetrySdk::builder()
+        .with_trace_decision_cache(0)
+        .build_with_handle();
+    assert_eq!(result.err(), Some(ConfigError::ZeroDecisionCacheCapacity));
+}
+
+#[tracing::instrument(fields(otel.kind = "server", otel.status_code = "error"))]
+fn handle_request() {}
+
+#[tracing::instrument(fields(otel.kind = "client", otel.status_code = tracing::field::Empty))]
+fn call_backend() {
+    tracing::Span::current().record("otel.status_code", "ok");
+}
+
+#[test]
+fn instrument_fields_set_kind_and_status() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        handle_request();
+        call_backend();
+    });
+    let server = &exporter.spans_named("handle_request")[0];
+    assert_eq!(server.kind, SpanKind::Server);
+    assert!(matches!(server.status, SpanStatus::Error { .. }));
+    let client = &exporter.spans_named("call_backend")[0];
+    assert_eq!(client.kind, SpanKind::Client);
+    assert_eq!(client.status, SpanStatus::Ok);
+}
+
+#[test]
+fn debug_formatted_kind_and_status_are_unquoted() {
+    let exporter = InMemorySpanExporter::new();
+    let (kind, status, name) = ("consumer", "error", "process");
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!(
+            "span",
+            otel.kind = ?kind,
+            otel.status_code = ?status,
+            otel.status_message = ?"failed",
+            otel.name = ?name,
+        )
+        .in_scope(|| {});
+    });
+    let span = &exporter.spans_named("process")[0];
+    assert_eq!(span.kind, SpanKind::Consumer);
+    assert_eq!(
+        span.status,
+        SpanStatus::Error {
+            description: "failed".to_string()
+        }
+    );
+}
+
+#[test]
+fn stats_count_spans_dropped_by_depth() {
+    let (sdk, handle) = OpenTelemetrySdk::builder()
+        .with_max_trace_depth(1)
+        .build_with_handle()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("root").in_scope(|| {
+            tracing::info_span!("child").in_scope(|| {
+                tracing::info_span!("grandchild").in_scope(|| {});
+            });
+        });
+    });
+    assert_eq!(handle.stats().spans_dropped_limits, 2);
+}
+
+#[test]
+fn stats_count_attributes_events_and_links_dropped_by_limits() {
+    let exporter = InMemorySpanExporter::new();
+    let (sdk, handle) = OpenTelemetrySdk::builder()
+        .with_span_limits(SpanLimits {
+            max_attributes: 1,
+            max_events: 1,
+            max_links: 1,
+            ..SpanLimits::default()
+        })
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build_with_handle()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("limited", a = 1, b = 2);
+        for span_id in 1..=3 {
+            span.add_link(SpanLink::new(TraceId::from(1), SpanId::from(span_id)));
+        }
+        span.in_scope(|| {
+            tracing::info!("first");
+            tracing::info!("second");
+        });
+    });
+    let span = &exporter.get_finished_spans()[0];
+    let dropped_attributes = span.dropped_attributes_count
+        + span
+            .events
+            .iter()
+            .map(|event| event.dropped_attributes_count)
+            .sum::<u32>();
+    assert!(span.dropped_attributes_count >= 1);
+    let stats = handle.stats();
+    assert_eq!(stats.spans_dropped_limits, 0);
+    assert_eq!(
+        stats.attributes_dropped_limits,
+        u64::from(dropped_attributes)
+    );
+    assert_eq!(stats.events_dropped_limits, 1);
+    assert_eq!(stats.links_dropped_limits, 2);
+}
+
+#[test]
+fn span_level_attribute_is_opt_in() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(sdk(&exporter));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::warn_span!("default").in_scope(|| {});
+    });
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_span_level_attribute(true)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::warn_span!("with_level").in_scope(|| {});
+        tracing::warn_span!("with_field", level = "custom").in_scope(|| {});
+    });
+    let level = |name| {
+        exporter.spans_named(name)[0]
+            .attributes
+            .get("level")
+            .cloned()
+    };
+    assert_eq!(level("default"), None);
+    assert_eq!(level("with_level"), Some("WARN".into()));
+    assert_eq!(level("with_field"), Some("custom".into()));
+}
+
+#[test]
+fn target_filter_disables_spans_at_the_callsite() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_target_filter("sqlx", false)
+            .with_target_filter("sqlx::pool", true)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request").in_scope(|| {
+            let query = tracing::info_span!(target: "sqlx::query", "query");
+            assert!(query.is_disabled());
+            query.in_scope(|| {
+                tracing::info_span!("decode").in_scope(|| {});
+            });
+            tracing::info_span!(target: "sqlx::pool", "acquire").in_scope(|| {});
+        });
+    });
+    let request = &exporter.spans_named("request")[0];
+    assert!(exporter.spans_named("query").is_empty());
+    assert_eq!(
+        exporter.spans_named("decode")[0].parent_span_id,
+        Some(request.span_id)
+    );
+    assert_eq!(exporter.spans_named("acquire").len(), 1);
+}
+
+#[test]
+fn processors_registered_after_the_handle_was_taken_are_flushed_by_it() {
+    let (first, second) = (InMemorySpanExporter::new(), InMemorySpanExporter::new());
+    let builder = builder(&first);
+    let handle = builder.handle();
+    let sdk = builder
+        .with_span_processor(BatchSpanProcessor::new(
+            Box::new(second.clone()),
+            BatchConfig::default(),
+        ))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("work").in_scope(|| {});
+    });
+    handle.force_flush();
+    assert_eq!(first.spans_named("work").len(), 1);
+    assert_eq!(second.spans_named("work").len(), 1);
+    handle.shutdown();
+}
+
+#[test]
+fn span_kind_defaults_to_internal_and_can_be_set() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("default").in_scope(|| {});
+        tracing::info_span!("field", otel.kind = "PRODUCER").in_scope(|| {});
+        tracing::info_span!("unknown", otel.kind = "sideways").in_scope(|| {});
+        let span = tracing::info_span!("method", otel.kind = "client");
+        span.set_span_kind(SpanKind::Server);
+    });
+    let span = |name| exporter.spans_named(name).remove(0);
+    assert_eq!(span("default").kind, SpanKind::Internal);
+    assert_eq!(span("field").kind, SpanKind::Producer);
+    assert!(!span("field").attributes.contains_key("otel.kind"));
+    // An unrecognized kind is kept as an attribute instead.
+    assert_eq!(span("unknown").kind, SpanKind::Internal);
+    assert_eq!(
+        span("unknown").attributes.get("otel.kind"),
+        Some(&"sideways".into())
+    );
+    assert_eq!(span("method").kind, SpanKind::Server);
+}
+
+#[test]
+fn span_status_survives_export() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("default").in_scope(|| {});
+        tracing::info_span!(
+            "declared",
+            otel.status_message = "timed out",
+            otel.status_code = "ERROR"
+        )
+        .in_scope(|| {});
+        let span = tracing::info_span!("set");
+        span.set_status(SpanStatus::Error {
+            description: "disk full".to_string(),
+        });
+    });
+    let status = |name| exporter.spans_named(name).remove(0).status;
+    assert_eq!(status("default"), SpanStatus::Unset);
+    assert_eq!(
+        status("declared"),
+        SpanStatus::Error {
+            description: "timed out".to_string()
+        }
+    );
+    assert_eq!(
+        status("set"),
+        SpanStatus::Error {
+            description: "disk full".to_string()
+        }
+    );
+}
+
+#[test]
+fn links_added_to_a_span_are_exported_with_it() {
+    let exporter = InMemorySpanExporter::new();
+    let w3c = SpanLink::from_header("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+        .unwrap()
+        .with_attribute("messaging.message.id", "a");
+    let jaeger =
+        SpanLink::from_header("4bf92f3577b34da6a3ce929d0e0e4736:00f067aa0ba902b7:0:1").unwrap();
+    assert!(SpanLink::from_header("not a header").is_none());
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let span = tracing::info_span!("batch");
+        span.add_link(w3c.clone());
+        span.add_link(jaeger.clone());
+    });
+    let links = &exporter.spans_named("batch")[0].links;
+    assert_eq!(links, &[w3c.clone(), jaeger.clone()]);
+    assert_eq!(
+        w3c.trace_id,
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c)
+    );
+    assert_eq!(jaeger.span_id, SpanId::from(0x00f067aa0ba902b7));
+}
+
+#[test]
+fn events_are_recorded_on_their_span_in_order() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request").in_scope(|| {
+            tracing::info!(user = "alice", "logged in");
+            clock.advance(Duration::from_millis(5));
+            tracing::warn!(attempts = 3, "slow");
+        });
+    });
+    let events = &exporter.spans_named("request")[0].events;
+    assert_eq!(events.len(), 2);
+    assert!(events[0].name.starts_with("event tests/sdk.rs:"));
+    assert_eq!(
+        events[0].attributes.get("message"),
+        Some(&"logged in".into())
+    );
+    assert_eq!(events[0].attributes.get("user"), Some(&"alice".into()));
+    assert_eq!(events[1].attributes.get("attempts"), Some(&3i64.into()));
+    assert_eq!(events[1].attributes.get("level"), Some(&"WARN".into()));
+    assert_eq!(
+        events[1].timestamp,
+        events[0].timestamp + Duration::from_millis(5)
+    );
+}
+
+#[test]
+fn attributes_keep_their_types() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!(
+            "typed",
+            event_id = 20,
+            retry = true,
+            ratio = 0.5,
+            user = "alice",
+            items = ?[1, 2],
+        )
+        .in_scope(|| {});
+    });
+    let attributes = &exporter.spans_named("typed")[0].attributes;
+    assert_eq!(attributes.get("event_id"), Some(&AttributeValue::I64(20)));
+    assert_eq!(attributes.get("retry"), Some(&AttributeValue::Bool(true)));
+    assert_eq!(attributes.get("ratio"), Some(&AttributeValue::F64(0.5)));
+    assert_eq!(
+        attributes.get("user"),
+        Some(&AttributeValue::String("alice".to_string()))
+    );
+    assert_eq!(
+        attributes.get("items"),
+        Some(&AttributeValue::String("[1, 2]".to_string()))
+    );
+}
+
+#[test]
+fn span_limits_drop_and_count_the_excess() {
+    let exporter = InMemorySpanExporter::new();
+    let limits = SpanLimits {
+        max_events: 2,
+        max_links: 1,
+        ..SpanLimits::default()
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(builder(&exporter).with_span_limits(limits).build().unwrap());
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("limited");
+        for i in 0..200i64 {
+            span.set_attribute(format!("key{i}"), i);
+        }
+        // Overwriting a stored attribute is always allowed.
+        span.set_attribute("key0", "updated");
+        for _ in 0..3 {
+            span.add_link(SpanLink::new(TraceId::from(1), SpanId::from(1)));
+        }
+        for _ in 0..5 {
+            tracing::info!(parent: &span, "event");
+        }
+    });
+    let span = &exporter.spans_named("limited")[0];
+    assert_eq!(span.attributes.len(), 128);
+    assert_eq!(span.dropped_attributes_count, 72);
+    assert_eq!(span.attributes.get("key0"), Some(&"updated".into()));
+    assert_eq!(span.events.len(), 2);
+    assert_eq!(span.dropped_events_count, 3);
+    assert_eq!(span.links.len(), 1);
+    assert_eq!(span.dropped_links_count, 2);
+}
+
+#[test]
+fn spans_record_their_target_as_scope() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!(target: "app::db", "query").in_scope(|| {});
+        tracing::info_span!("local").in_scope(|| {});
+    });
+    assert_eq!(exporter.spans_named("query")[0].scope_name, "app::db");
+    assert_eq!(exporter.spans_named("local")[0].scope_name, "sdk");
+}
+
+#[test]
+fn error_events_become_exceptions_when_enabled() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_exception_events(true)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("failing").in_scope(|| {
+            tracing::info!("starting");
+            tracing::error!(exception.r#type = "io::Error", "connection reset");
+        });
+    });
+    let span = &exporter.spans_named("failing")[0];
+    assert_eq!(
+        span.status,
+        SpanStatus::Error {
+            description: "connection reset".to_string()
+        }
+    );
+    let exception = &span.events[1];
+    assert_eq!(exception.name, "exception");
+    assert_eq!(
+        exception.attributes.get("exception.type"),
+        Some(&"io::Error".into())
+    );
+    assert_eq!(
+        exception.attributes.get("exception.message"),
+        Some(&"connection reset".into())
+    );
+    assert_ne!(span.events[0].name, "exception");
+
+    // Off by default.
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("failing").in_scope(|| tracing::error!("connection reset"));
+    });
+    let span = &exporter.spans_named("failing")[0];
+    assert_eq!(span.status, SpanStatus::Unset);
+    assert_ne!(span.events[0].name, "exception");
+}
+
+#[test]
+fn code_location_is_recorded_when_enabled() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry()
+        .with(builder(&exporter).with_code_location(true).build().unwrap());
+    let line = tracing::subscriber::with_default(subscriber, || {
+        let (span, line) = (tracing::info_span!("located"), line!());
+        span.in_scope(|| tracing::info!("inside"));
+        line
+    });
+    let span = &exporter.spans_named("located")[0];
+    assert_eq!(
+        span.attributes.get("code.lineno"),
+        Some(&i64::from(line).into())
+    );
+    assert_eq!(
+        span.attributes.get("code.filepath"),
+        Some(&"tests/sdk.rs".into())
+    );
+    assert_eq!(span.attributes.get("code.namespace"), Some(&"sdk".into()));
+    assert_eq!(
+        span.events[0].attributes.get("code.lineno"),
+        Some(&i64::from(line + 1).into())
+    );
+
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("located").in_scope(|| {});
+    });
+    assert!(!exporter.spans_named("located")[0]
+        .attributes
+        .contains_key("code.lineno"));
+}
+
+#[test]
+fn spans_are_timed_by_the_sdk_clock() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let clock = ManualClock::new(start);
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    let mut open = None;
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work");
+        clock.advance(Duration::from_millis(1_250));
+        open = span.with_otel_span(|span| Some(Some((span.start_time, span.end_time))));
+    });
+    // The end time is only set once the span closes.
+    assert_eq!(open, Some((start, None)));
+    let span = &exporter.spans_named("work")[0];
+    assert_eq!(span.start_time, start);
+    assert_eq!(span.end_time, Some(start + Duration::from_millis(1_250)));
+}
+
+#[test]
+fn builder_applies_its_configuration() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_event_export_mode(EventExportMode::None)
+        .with_id_generator(FixedIdGenerator::new(TraceId::from(1), SpanId::from(1)))
+        .with_resource(Resource::empty().with_attribute("service.name", "checkout"))
+        .with_span_limits(SpanLimits {
+            max_attributes: 1,
+            ..SpanLimits::default()
+        })
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("configured", a = 1, b = 2);
+        tracing::info!(parent: &span, "ignored");
+    });
+    let span = &exporter.spans_named("configured")[0];
+    assert_eq!(
+        (span.trace_id, span.span_id),
+        (TraceId::from(1), SpanId::from(1))
+    );
+    assert!(span.events.is_empty());
+    assert_eq!(span.attributes.len(), 1);
+    assert_eq!(
+        exporter.resource().unwrap().get("service.name"),
+        Some(&"checkout".into())
+    );
+
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(AlwaysOff)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("unsampled").in_scope(|| {});
+    });
+    assert!(exporter.get_finished_spans().is_empty());
+}
+
+#[test]
+fn batched_spans_are_exported_on_force_flush() {
+    let exporter = InMemorySpanExporter::new();
+    let config = BatchConfig::default().with_scheduled_delay(Duration::from_secs(3600));
+    let (sdk, handle) = OpenTelemetrySdk::builder()
+        .with_span_processor(BatchSpanProcessor::new(Box::new(exporter.clone()), config))
+        .build_with_handle()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("buffered").in_scope(|| {});
+    });
+    assert!(exporter.get_finished_spans().is_empty());
+    handle.force_flush();
+    assert_eq!(exporter.spans_named("buffered").len(), 1);
+    handle.shutdown();
+}
+
+#[test]
+fn span_context_reads_the_ids_together() {
+    let exporter = InMemorySpanExporter::new();
+    let mut contexts = None;
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let parent = tracing::info_span!("parent");
+        let child = tracing::info_span!(parent: &parent, "child");
+        contexts = Some((parent.span_context(), child.span_context()));
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_eq!(child.parent_span_id(), parent.span_id());
+    });
+    let (parent, child) = contexts.unwrap();
+    let (parent, child) = (parent.unwrap(), child.unwrap());
+    let exported = &exporter.spans_named("child")[0];
+    assert_eq!(child.trace_id, exported.trace_id);
+    assert_eq!(child.span_id, exported.span_id);
+    assert_eq!(child.parent_span_id, Some(parent.span_id));
+    assert!(child.flags.is_sampled());
+    assert!(!child.is_remote);
+    assert_eq!(parent.parent_span_id, None);
+
+    assert_eq!(tracing::info_span!("no_subscriber").span_context(), None);
+}
+
+#[test]
+fn with_otel_span_reads_while_other_readers_hold_the_span() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let span = tracing::info_span!("read");
+        // A read inside a read only works if neither takes the extensions mutably.
+        let nested = span.with_otel_span(|outer| {
+            span.with_otel_span(|inner| Some(Some((outer.span_id, inner.name.clone()))))
+        });
+        assert_eq!(nested, (span.span_id(), "read".to_string()));
+
+        span.with_otel_span_mut(|otel_span| {
+            otel_span.name = "renamed".to_string();
+            Some(())
+        });
+        assert_eq!(
+            span.with_otel_span(|otel_span| Some(otel_span.name.clone())),
+            "renamed"
+        );
+    });
+    assert_eq!(exporter.spans_named("renamed").len(), 1);
+}
+
+#[test]
+fn children_created_concurrently_all_find_their_parent() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(sdk(&exporter));
+    let dispatch = tracing::Dispatch::new(subscriber);
+    tracing::dispatcher::with_default(&dispatch, || {
+        let parent = tracing::info_span!("parent");
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for _ in 0..50 {
+                            tracing::info_span!(parent: &parent, "child").in_scope(|| {});
+                        }
+                    })
+                });
+            }
+        });
+    });
+    let parent = &exporter.spans_named("parent")[0];
+    let children = exporter.spans_named("child");
+    assert_eq!(children.len(), 200);
+    assert!(children
+        .iter()
+        .all(|child| child.trace_id == parent.trace_id
+            && child.parent_span_id == Some(parent.span_id)));
+}
+
+#[test]
+fn unsampled_spans_keep_only_their_context() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_sampler(AlwaysOff)
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let parent = tracing::info_span!("parent", user = "alice");
+        let child = tracing::info_span!(parent: &parent, "child");
+        let (parent_context, child_context) = (
+            parent.span_context().unwrap(),
+            child.span_context().unwrap(),
+        );
+        // Ids are still there to propagate, but nothing is recorded.
+        assert!(parent_context.is_valid());
+        assert!(!parent_context.flags.is_sampled());
+        assert_eq!(child_context.trace_id, parent_context.trace_id);
+        assert_eq!(child_context.parent_span_id, Some(parent_context.span_id));
+        assert!(!parent.with_otel_span(|_| Some(true)));
+    });
+    assert!(exporter.get_finished_spans().is_empty());
+}
+
+#[test]
+fn ids_display_and_parse_as_padded_hex() {
+    assert_eq!(
+        TraceId::from(0xab).to_string(),
+        "000000000000000000000000000000ab"
+    );
+    assert_eq!(SpanId::from(0xab).to_string(), "00000000000000ab");
+    assert_eq!(
+        format!("{:?}", SpanId::from(0xab)),
+        "SpanId(00000000000000ab)"
+    );
+    assert_eq!(
+        "000000000000000000000000000000ab".parse::<TraceId>(),
+        Ok(TraceId::from(0xab))
+    );
+    assert_eq!("00000000000000ab".parse::<SpanId>(), Ok(SpanId::from(0xab)));
+
+    assert_eq!(
+        "00000000000000000000000000000ab".parse::<TraceId>(),
+        Err(ParseError::InvalidLength {
+            field: "trace id",
+            expected: 32
+        })
+    );
+    assert_eq!(
+        "00000000000000AB".parse::<SpanId>(),
+        Err(ParseError::InvalidHex { field: "span id" })
+    );
+    assert_eq!(
+        "+000000000000000".parse::<SpanId>(),
+        Err(ParseError::InvalidHex { field: "span id" })
+    );
+}
+
+#[test]
+fn integers_beyond_i64_are_kept_as_strings() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let span = tracing::info_span!(
+            "numbers",
+            small = 5u64,
+            big = u64::MAX,
+            negative = -3i64,
+            wide = i128::MIN,
+            cached = false,
+            late = tracing::field::Empty,
+        );
+        span.record("late", 7i64);
+        span.in_scope(|| {});
+    });
+    let attributes = &exporter.spans_named("numbers")[0].attributes;
+    assert_eq!(attributes["small"], AttributeValue::I64(5));
+    assert_eq!(
+        attributes["big"],
+        AttributeValue::String(u64::MAX.to_string())
+    );
+    assert_eq!(attributes["negative"], AttributeValue::I64(-3));
+    assert_eq!(
+        attributes["wide"],
+        AttributeValue::String(i128::MIN.to_string())
+    );
+    assert_eq!(attributes["cached"], AttributeValue::Bool(false));
+    assert_eq!(attributes["late"], AttributeValue::I64(7));
+    // Displayed without `Debug` quoting.
+    assert_eq!(attributes["cached"].to_string(), "false");
+    assert_eq!(attributes["small"].to_string(), "5");
+}
+
+#[test]
+fn span_events_carry_the_event_fields() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("request").in_scope(|| {
+            tracing::warn!(name: "x", event_id = 10, user = "otel");
+        });
+    });
+    let event = &exporter.spans_named("request")[0].events[0];
+    assert_eq!(event.name, "x");
+    assert_eq!(event.attributes["event_id"], AttributeValue::I64(10));
+    assert_eq!(event.attributes["user"], AttributeValue::from("otel"));
+    assert_eq!(event.attributes["level"], AttributeValue::from("WARN"));
+    assert_eq!(event.attributes["target"], AttributeValue::from("sdk"));
+}
+
+#[test]
+fn end_time_is_set_only_when_the_span_closes() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work");
+        let open =
+            span.with_otel_span(|otel_span| Some(Some((otel_span.end_time, otel_span.duration()))));
+        assert_eq!(open, Some((None, Duration::ZERO)));
+        clock.advance(Duration::from_millis(50));
+    });
+    let span = &exporter.spans_named("work")[0];
+    assert_eq!(span.duration(), Duration::from_millis(50));
+    assert_eq!(
+        span.end_time,
+        Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_050))
+    );
+}
+
+#[test]
+fn long_string_values_are_truncated() {
+    let exporter = InMemorySpanExporter::new();
+    let (sdk, handle) = OpenTelemetrySdk::builder()
+        .with_span_limits(SpanLimits {
+            max_attribute_value_len: 256,
+            ..SpanLimits::default()
+        })
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build_with_handle()
+        .unwrap();
+    let payload = "é".repeat(10 * 1024);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("upload", payload = payload.as_str(), size = 10_240);
+        tracing::info!(parent: &span, body = payload.as_str());
+    });
+    let span = &exporter.spans_named("upload")[0];
+    let AttributeValue::String(stored) = &span.attributes["payload"] else {
+        panic!("payload is not a string");
+    };
+    // Cut on characters, not bytes.
+    assert_eq!(stored.chars().count(), 256 + TRUNCATION_MARKER.len());
+    assert!(stored.ends_with(TRUNCATION_MARKER));
+    assert_eq!(span.attributes["size"], AttributeValue::I64(10_240));
+    let AttributeValue::String(body) = &span.events[0].attributes["body"] else {
+        panic!("body is not a string");
+    };
+    assert!(body.ends_with(TRUNCATION_MARKER));
+    assert_eq!(handle.stats().attributes_truncated, 2);
+}
+
+#[test]
+fn add_event_appends_manual_events_within_the_limit() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_limits(SpanLimits {
+            max_events: 2,
+            ..SpanLimits::default()
+        })
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("glue");
+        span.add_event(
+            "cache_miss",
+            vec![("key".to_string(), AttributeValue::from("user:1"))],
+        );
+        span.add_event(
+            "retry",
+            vec![("attempt".to_string(), AttributeValue::I64(2))],
+        );
+        span.add_event("dropped", Vec::new());
+    });
+    let span = &exporter.spans_named("glue")[0];
+    let names: Vec<_> = span
+        .events
+        .iter()
+        .map(|event| event.name.as_str())
+        .collect();
+    assert_eq!(names, ["cache_miss", "retry"]);
+    assert_eq!(
+        span.events[0].attributes["key"],
+        AttributeValue::from("user:1")
+    );
+    assert_eq!(span.events[1].attributes["attempt"], AttributeValue::I64(2));
+    assert!(span.events[0].timestamp <= span.events[1].timestamp);
+    assert_eq!(span.dropped_events_count, 1);
+}
+
+#[test]
+fn spans_created_before_the_sdk_was_added_close_quietly() {
+    let exporter = InMemorySpanExporter::new();
+    let (layer, reload) = tracing_subscriber::reload::Layer::new(None::<OpenTelemetrySdk>);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+        let unknown = tracing::info_span!("unknown", late = tracing::field::Empty);
+        reload.reload(Some(sdk(&exporter))).unwrap();
+        // None of the lifecycle callbacks find OTel data for it.
+        unknown.in_scope(|| tracing::info!("inside"));
+        unknown.record("late", 1);
+        tracing::info_span!(parent: &unknown, "child").in_scope(|| {});
+        drop(unknown);
+        tracing::info_span!("known").in_scope(|| {});
+    });
+    assert!(exporter.spans_named("unknown").is_empty());
+    assert_eq!(exporter.spans_named("known").len(), 1);
+}
+
+#[test]
+fn many_attributes_are_all_kept() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!(
+            "wide",
+            a1 = "v1",
+            a2 = "v2",
+            a3 = "v3",
+            a4 = "v4",
+            a5 = "v5",
+            a6 = "v6",
+            a7 = "v7",
+            a8 = "v8",
+            a9 = "v9",
+            a10 = "v10",
+            a11 = "v11",
+            a12 = "v12",
+            a13 = "v13",
+            a14 = "v14",
+            a15 = "v15",
+            a16 = "v16",
+            a17 = "v17",
+            a18 = "v18",
+            a19 = "v19",
+            a20 = "v20",
+        )
+        .in_scope(|| {});
+    });
+    let span = &exporter.spans_named("wide")[0];
+    for i in 1..=20 {
+        assert_eq!(
+            span.attributes.get(&format!("a{i}")),
+            Some(&AttributeValue::String(format!("v{i}")))
+        );
+    }
+    assert_eq!(span.dropped_attributes_count, 0);
+}
+
+#[test]
+fn set_attribute_adds_undeclared_keys() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let span = tracing::info_span!("lookup", declared = 1);
+        span.set_attribute("cache.hit", true);
+        span.set_attribute("db.rows", 3i64);
+        span.set_attribute("declared", "replaced");
+        // `record` only reaches declared fields, `set_attribute` doesn't need one.
+        span.record("undeclared", 1);
+    });
+    let attributes = &exporter.spans_named("lookup")[0].attributes;
+    assert_eq!(
+        attributes.get("cache.hit"),
+        Some(&AttributeValue::Bool(true))
+    );
+    assert_eq!(attributes.get("db.rows"), Some(&AttributeValue::I64(3)));
+    assert_eq!(attributes.get("declared"), Some(&"replaced".into()));
+    assert_eq!(attributes.get("undeclared"), None);
+}
+
+#[test]
+fn http_status_codes_set_the_span_status() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("ok", otel.kind = "server", http.status_code = 200).in_scope(|| {});
+        tracing::info_span!("not_found", otel.kind = "server", http.status_code = 404)
+            .in_scope(|| {});
+        tracing::info_span!(
+            "client_not_found",
+            otel.kind = "client",
+            http.status_code = 404
+        )
+        .in_scope(|| {});
+        tracing::info_span!("unavailable", otel.kind = "server", http.status_code = 503)
+            .in_scope(|| {});
+        // A status the span set itself wins.
+        tracing::info_span!(
+            "explicit",
+            otel.kind = "server",
+            otel.status_code = "ok",
+            http.status_code = 503
+        )
+        .in_scope(|| {});
+    });
+    let status = |name| exporter.spans_named(name)[0].status.clone();
+    assert_eq!(status("ok"), SpanStatus::Unset);
+    assert_eq!(status("not_found"), SpanStatus::Unset);
+    assert_eq!(
+        status("client_not_found"),
+        SpanStatus::Error {
+            description: "404 Not Found".to_string()
+        }
+    );
+    assert_eq!(
+        status("unavailable"),
+        SpanStatus::Error {
+            description: "503 Service Unavailable".to_string()
+        }
+    );
+    assert_eq!(status("explicit"), SpanStatus::Ok);
+
+    let exporter = InMemorySpanExporter::new();
+    let sdk = builder(&exporter)
+        .with_http_status_mapping(|_, code| {
+            if code == 429 {
+                SpanStatus::Error {
+                    description: "throttled".to_string(),
+                }
+            } else {
+                SpanStatus::Unset
+            }
+        })
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        tracing::info_span!("throttled", otel.kind = "server", http.status_code = 429)
+            .in_scope(|| {});
+        tracing::info_span!("unavailable", otel.kind = "server", http.status_code = 503)
+            .in_scope(|| {});
+    });
+    assert_eq!(
+        exporter.spans_named("throttled")[0].status,
+        SpanStatus::Error {
+            description: "throttled".to_string()
+        }
+    );
+    assert_eq!(
+        exporter.spans_named("unavailable")[0].status,
+        SpanStatus::Unset
+    );
+}
+
+#[test]
+fn open_span_tracking_lists_the_open_spans_of_a_trace() {
+    let exporter = InMemorySpanExporter::new();
+    let tracked = builder(&exporter)
+        .with_open_span_tracking()
+        .build()
+        .unwrap();
+    let handle = tracked.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(tracked), || {
+        let parent = tracing::info_span!("parent");
+        let trace_id = parent.trace_id();
+        let names = || {
+            let mut names: Vec<String> = handle
+                .open_spans(trace_id)
+                .into_iter()
+                .map(|span| span.name)
+                .collect();
+            names.sort();
+            names
+        };
+        let child = tracing::info_span!(parent: &parent, "child");
+        tracing::info_span!("other_trace").in_scope(|| {
+            assert_eq!(names(), ["child", "parent"]);
+        });
+        drop(child);
+        assert_eq!(names(), ["parent"]);
+        drop(parent);
+        assert!(names().is_empty());
+    });
+
+    // Without tracking nothing is kept.
+    let sdk = sdk(&exporter);
+    let handle = sdk.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("untracked");
+        assert!(handle.open_spans(span.trace_id()).is_empty());
+    });
+}
+
+#[test]
+fn severities_follow_the_otel_log_data_model() {
+    let numbers: Vec<i32> = [
+        Level::TRACE,
+        Level::DEBUG,
+        Level::INFO,
+        Level::WARN,
+        Level::ERROR,
+    ]
+    .into_iter()
+    .map(|level| Severity::from(level).number())
+    .collect();
+    assert_eq!(numbers, [1, 5, 9, 13, 17]);
+}
+
+#[test]
+fn spans_without_fields_leave_their_attributes_unallocated() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_span_level_attribute(true)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        let capacity = |span: &tracing::Span| {
+            span.with_otel_span(|span| Some(Some(span.attributes.capacity())))
+        };
+        let bare = tracing::span!(Level::TRACE, "bare");
+        assert_eq!(capacity(&bare), Some(0));
+        let with_field = tracing::span!(Level::TRACE, "with_field", a = 1);
+        assert!(capacity(&with_field) > Some(0));
+    });
+    // The level is only added once the span closes.
+    assert_eq!(
+        exporter.spans_named("bare")[0].attributes.get("level"),
+        Some(&"TRACE".into())
+    );
+}
+
+#[test]
+fn spans_can_be_named_after_an_attribute() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_name_from_attribute("http.route")
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request", http.route = "/users/:id").in_scope(|| {});
+        let span = tracing::info_span!("routed_late", http.route = tracing::field::Empty);
+        span.record("http.route", "/orders");
+        drop(span);
+        tracing::info_span!("unrouted").in_scope(|| {});
+    });
+    let names: Vec<String> = exporter
+        .get_finished_spans()
+        .into_iter()
+        .map(|span| span.name)
+        .collect();
+    assert_eq!(names, ["/users/:id", "/orders", "unrouted"]);
+}
+
+#[test]
+fn fan_in_spans_are_roots_linking_every_producer() {
+    let exporter = InMemorySpanExporter::new();
+    let producers: Vec<RemoteSpanContext> = (1..=3u64)
+        .map(|i| {
+            RemoteSpanContext::new(
+                TraceId::from(i as u128),
+                SpanId::from(i),
+                TraceFlags::SAMPLED,
+            )
+        })
+        .collect();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        // An entered span doesn't become the parent.
+        tracing::info_span!("poll").in_scope(|| {
+            new_span_with_contexts("process orders", producers.clone()).in_scope(|| {});
+        });
+    });
+    let poll = &exporter.spans_named("poll")[0];
+    let span = &exporter.spans_named("process orders")[0];
+    assert_eq!(span.parent_span_id, None);
+    assert_ne!(span.trace_id, poll.trace_id);
+    assert_eq!(span.kind, SpanKind::Consumer);
+    assert_eq!(
+        span.attributes.get("messaging.batch.message_count"),
+        Some(&3i64.into())
+    );
+    assert_eq!(span.links.len(), 3);
+    for (index, (link, producer)) in span.links.iter().zip(&producers).enumerate() {
+        assert_eq!(
+            (link.trace_id, link.span_id),
+            (producer.trace_id, producer.span_id)
+        );
+        assert_eq!(
+            link.attributes.get(MESSAGING_MESSAGE_INDEX),
+            Some(&(index as i64).into())
+        );
+    }
+}
+
+#[test]
+fn queue_time_runs_from_creation_to_the_first_enter() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("queued");
+        assert_eq!(
+            span.with_otel_span(|span| Some(Some(span.queue_time()))),
+            Some(Duration::ZERO)
+        );
+        clock.advance(Duration::from_millis(40));
+        span.in_scope(|| clock.advance(Duration::from_millis(10)));
+        // Only the first enter counts.
+        clock.advance(Duration::from_millis(30));
+        span.in_scope(|| {});
+
+        tracing::info_span!("immediate").in_scope(|| {});
+    });
+    let queued = &exporter.spans_named("queued")[0];
+    assert_eq!(
+        queued.attributes.get("queue_time_ns"),
+        Some(&40_000_000i64.into())
+    );
+    assert_eq!(
+        exporter.spans_named("immediate")[0]
+            .attributes
+            .get("queue_time_ns"),
+        None
+    );
+}
+
+#[test]
+fn strict_mode_reports_misuses_that_are_otherwise_ignored() {
+    let misuse = || {
+        let span = tracing::info_span!("declared", field = tracing::field::Empty);
+        let metadata = span.metadata().unwrap();
+        let field = metadata.fields().field("field").unwrap();
+        let values = [(&field, Some(&1 as &dyn tracing::Value))];
+        let values = metadata.fields().value_set(&values);
+        let unknown = tracing::Id::from_u64(u64::MAX);
+        tracing::dispatcher::get_default(|dispatch| {
+            dispatch.record(&unknown, &tracing::span::Record::new(&values));
+            dispatch.exit(&unknown);
+        });
+        tracing::event!(name: "empty", target: "app", Level::INFO, {});
+        tracing::info!("not empty");
+    };
+
+    let exporter = InMemorySpanExporter::new();
+    let strict = builder(&exporter).with_strict_mode(true).build().unwrap();
+    let handle = strict.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(strict), misuse);
+    assert_eq!(
+        handle.take_violations(),
+        [
+            Violation::UnknownSpan {
+                callback: "on_record",
+                id: u64::MAX
+            },
+            Violation::UnknownSpan {
+                callback: "on_exit",
+                id: u64::MAX
+            },
+            Violation::EmptyEvent {
+                name: "empty".to_string(),
+                target: "app".to_string()
+            },
+        ]
+    );
+    // Taking them drains them.
+    assert!(handle.take_violations().is_empty());
+
+    let lenient = sdk(&exporter);
+    let handle = lenient.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(lenient), misuse);
+    assert!(handle.take_violations().is_empty());
+}
+
+#[test]
+fn spans_export_how_many_children_they_had() {
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        let parent = tracing::info_span!("parent");
+        parent.in_scope(|| {
+            for _ in 0..3 {
+                tracing::info_span!("child")
+                    .in_scope(|| tracing::info_span!("grandchild").in_scope(|| {}));
+            }
+        });
+        assert_eq!(
+            parent.with_otel_span(|span| Some(Some(span.child_count()))),
+            Some(3)
+        );
+    });
+    let child_count = |name| {
+        exporter.spans_named(name)[0]
+            .attributes
+            .get("child_count")
+            .cloned()
+    };
+    // Grandchildren only count for their own parent.
+    assert_eq!(child_count("parent"), Some(3i64.into()));
+    assert_eq!(child_count("child"), Some(1i64.into()));
+    assert_eq!(child_count("grandchild"), None);
+}
+
+#[test]
+fn orphan_detection_flags_children_closing_after_their_parent() {
+    let exporter = InMemorySpanExporter::new();
+    let detecting = builder(&exporter).with_orphan_detection().build().unwrap();
+    let handle = detecting.handle();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(detecting), || {
+        let parent = tracing::info_span!("parent");
+        let orphan = tracing::info_span!(parent: None, "orphan");
+        orphan.set_parent_context(parent.span_context().unwrap());
+        parent.in_scope(|| tracing::info_span!("child").in_scope(|| {}));
+        drop(parent);
+        drop(orphan);
+
+        // A remote parent is never seen closing.
+        let remote = tracing::info_span!("remote_child");
+        remote.set_parent_context(SpanContext::new_remote(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+        ));
+        drop(remote);
+    });
+    let orphaned = |name| {
+        exporter.spans_named(name)[0]
+            .attributes
+            .get("otel.orphaned")
+            .cloned()
+    };
+    assert_eq!(orphaned("orphan"), Some(true.into()));
+    assert_eq!(orphaned("child"), None);
+    assert_eq!(orphaned("parent"), None);
+    assert_eq!(orphaned("remote_child"), None);
+    assert_eq!(handle.stats().spans_orphaned, 1);
+}
+
+#[test]
+fn attribute_processors_run_on_spans_and_events_before_export() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = builder(&exporter)
+        .with_attribute_processor(|attributes| {
+            if let Some(email) = attributes.get_mut("user_email") {
+                *email = "***".into();
+            }
+        })
+        // Runs after the first one.
+        .with_attribute_processor(|attributes| {
+            if let Some(email) = attributes.remove("user_email") {
+                attributes.insert("user.email".to_string(), email);
+            }
+            attributes.remove("password");
+        })
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("signup", user_email = "a@example.com", password = "pw");
+        tracing::info!(parent: &span, user_email = "a@example.com", "confirmation sent");
+    });
+    let span = &exporter.spans_named("signup")[0];
+    assert_eq!(span.attributes.get("user.email"), Some(&"***".into()));
+    assert_eq!(span.attributes.get("user_email"), None);
+    assert_eq!(span.attributes.get("password"), None);
+    let event = &span.events[0];
+    assert_eq!(event.attributes.get("user.email"), Some(&"***".into()));
+    assert!(!format!("{span:?}").contains("a@example.com"));
+}
+
+#[test]
+fn the_disabled_sdk_gives_spans_no_opentelemetry_span() {
+    tracing::subscriber::with_default(
+        tracing_subscriber::registry().with(DisabledSdk::new()),
+        || {
+            let span = tracing::info_span!("disabled", field = 1);
+            span.in_scope(|| tracing::info!("event"));
+            span.set_attribute("ignored", true);
+            assert_eq!(span.span_context(), None);
+            assert_eq!(
+                span.with_otel_span(|span| Some(Some(span.name.clone()))),
+                None
+            );
+            assert_eq!(span.elapsed(), None);
+        },
+    );
+}
+
+// An error with a source, as `?`-heavy code builds them.
+#[derive(Debug)]
+struct LoadingConfig(std::io::Error);
+
+impl std::fmt::Display for LoadingConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("loading config")
+    }
+}
+
+impl std::error::Error for LoadingConfig {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[test]
+fn record_error_sets_the_status_and_adds_an_exception_event() {
+    let exporter = InMemorySpanExporter::new();
+    let limits = SpanLimits {
+        max_events: 1,
+        ..SpanLimits::default()
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(builder(&exporter).with_span_limits(limits).build().unwrap());
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("startup");
+        let err = LoadingConfig(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "file not found",
+        ));
+        span.record_error(&err);
+        // Over the event limit, only the status changes.
+        span.record_error(&std::io::Error::other("retry failed"));
+    });
+    let span = &exporter.spans_named("startup")[0];
+    assert_eq!(
+        span.status,
+        SpanStatus::Error {
+            description: "retry failed".to_string()
+        }
+    );
+    assert_eq!(span.events.len(), 1);
+    assert_eq!(span.dropped_events_count, 1);
+    assert_eq!(span.events[0].name, "exception");
+    assert_eq!(
+        span.events[0].attributes.get("exception.message"),
+        Some(&"loading config: file not found".into())
+    );
+}
+
+#[test]
+fn thread_info_records_the_thread_spans_start_on() {
+    let exporter = InMemorySpanExporter::new();
+    let dispatch = tracing::Dispatch::new(
+        tracing_subscriber::registry()
+            .with(builder(&exporter).with_thread_info(true).build().unwrap()),
+    );
+    let spawn = |name: Option<&str>| {
+        let dispatch = dispatch.clone();
+        let mut builder = std::thread::Builder::new();
+        if let Some(name) = name {
+            builder = builder.name(name.to_string());
+        }
+        builder
+            .spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    tracing::info_span!("work").in_scope(|| {});
+                });
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    };
+    spawn(Some("worker-1"));
+    spawn(Some("worker-2"));
+    spawn(None);
+
+    let spans = exporter.spans_named("work");
+    let names: Vec<Option<&AttributeValue>> = spans
+        .iter()
+        .map(|span| span.attributes.get("thread.name"))
+        .collect();
+    assert_eq!(
+        names,
+        [Some(&"worker-1".into()), Some(&"worker-2".into()), None]
+    );
+    let ids: Vec<&AttributeValue> = spans
+        .iter()
+        .map(|span| &span.attributes["thread.id"])
+        .collect();
+    assert_ne!(ids[0], ids[1]);
+
+    // Off by default.
+    let exporter = InMemorySpanExporter::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk(&exporter)), || {
+        tracing::info_span!("work").in_scope(|| {});
+    });
+    assert_eq!(
+        exporter.spans_named("work")[0].attributes.get("thread.id"),
+        None
+    );
+}
+
+#[test]
+fn sampled_events_are_a_uniform_sample_of_the_span_events() {
+    let exporter = InMemorySpanExporter::new();
+    let limits = SpanLimits {
+        max_events: 100,
+        sample_events: true,
+        ..SpanLimits::default()
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(builder(&exporter).with_span_limits(limits).build().unwrap());
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("chatty");
+        for i in 0..10_000i64 {
+            tracing::info!(parent: &span, i, "tick");
+        }
+    });
+    let span = &exporter.spans_named("chatty")[0];
+    assert_eq!(span.events.len(), 100);
+    assert_eq!(span.dropped_events_count, 9_900);
+    let kept: Vec<i64> = span
+        .events
+        .iter()
+        .map(|event| match event.attributes["i"] {
+            AttributeValue::I64(i) => i,
+            ref other => panic!("{other:?}"),
+        })
+        .collect();
+    // Kept in the order they happened.
+    assert!(kept.windows(2).all(|pair| pair[0] < pair[1]));
+    // The first events would all be kept without sampling. Both bounds are over 6 standard
+    // deviations away from the expected 50 events from each half.
+    let first_half = kept.iter().filter(|i| **i < 5_000).count();
+    assert!((20..=80).contains(&first_half), "{first_half} of {kept:?}");
+    let mean = kept.iter().sum::<i64>() / 100;
+    assert!((3_000..=7_000).contains(&mean), "mean {mean}");
+}
+
+#[test]
+fn data_volume_attributes_are_integers() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(sdk(&exporter));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("query");
+        span.record_rows(42);
+        span.record_bytes_in(1_024);
+        span.record_bytes_out(2_048);
+    });
+    let span = &exporter.spans_named("query")[0];
+    assert_eq!(span.attributes[ROWS_PROCESSED], AttributeValue::I64(42));
+    assert_eq!(span.attributes[BYTES_IN], AttributeValue::I64(1_024));
+    assert_eq!(span.attributes[BYTES_OUT], AttributeValue::I64(2_048));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn entered_spans_record_their_context_switches() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(
+        builder(&exporter)
+            .with_context_switches(true)
+            .build()
+            .unwrap(),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        // Sleeping gives up the CPU at least once.
+        tracing::info_span!("sleep").in_scope(|| std::thread::sleep(Duration::from_millis(1)));
+    });
+    let span = &exporter.spans_named("sleep")[0];
+    let voluntary = &span.attributes["thread.context_switches.voluntary"];
+    assert!(matches!(voluntary, AttributeValue::I64(switches) if *switches >= 1));
+    assert!(span
+        .attributes
+        .contains_key("thread.context_switches.involuntary"));
+}
+
+#[test]
+fn propagate_into_continues_the_trace_on_another_thread() {
+    let exporter = InMemorySpanExporter::new();
+    let subscriber = tracing_subscriber::registry().with(sdk(&exporter));
+    let parent = tracing::subscriber::with_default(subscriber, || {
+        let parent = tracing::info_span!("parent");
+        let task = parent.propagate_into(|| tracing::info_span!("child").in_scope(|| {}));
+        std::thread::spawn(task).join().unwrap();
+        parent.span_context().unwrap()
+    });
+    let child = &exporter.spans_named("child")[0];
+    assert_eq!(child.trace_id, parent.trace_id);
+    assert_eq!(child.parent_span_id, Some(parent.span_id));
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "serde")]
+
+use opentelemetry_tracing::exporter::{InMemorySpanExporter, JsonFormatter, SpanFormatter};
+use opentelemetry_tracing::opentelemetry_sdk::{
+    AttributeValue, OTelSpanData, OpenTelemetrySdk, OtelSpanExt, Resource, SpanId, SpanKind,
+    SpanLink, SpanStatus, TraceId,
+};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use tracing_subscriber::prelude::*;
+
+// A closed span using every part of the data model.
+fn full_span() -> OTelSpanData {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        let span = tracing::info_span!("checkout", user = "alice", items = 3, vip = true);
+        span.set_span_kind(SpanKind::Server);
+        span.set_status(SpanStatus::Error {
+            description: "out of stock".to_string(),
+        });
+        span.add_link(SpanLink::new(TraceId::from(1), SpanId::from(2)));
+        span.add_event(
+            "retry",
+            vec![("attempt".to_string(), AttributeValue::F64(1.5))],
+        );
+    });
+    exporter.spans_named("checkout").remove(0)
+}
+
+#[test]
+fn spans_round_trip_through_json() {
+    let span = full_span();
+    let json = serde_json::to_string(&span).unwrap();
+    let decoded: OTelSpanData = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, span);
+}
+
+#[test]
+fn ids_are_fixed_width_hex() {
+    let span = full_span();
+    let json = serde_json::to_value(&span).unwrap();
+    assert_eq!(json["trace_id"], span.trace_id.to_string());
+    assert_eq!(json["span_id"].as_str().unwrap().len(), 16);
+    assert_eq!(
+        json["links"][0]["trace_id"],
+        "00000000000000000000000000000001"
+    );
+    assert_eq!(json["links"][0]["span_id"], "0000000000000002");
+    assert_eq!(json["kind"], "server");
+}
+
+#[test]
+fn stdout_json_uses_the_serde_encoding() {
+    let span = full_span();
+    let line = JsonFormatter.format(&span, &Resource::empty());
+    let mut line: serde_json::Value = serde_json::from_str(&line).unwrap();
+    line.as_object_mut().unwrap().remove("resource");
+    assert_eq!(line, serde_json::to_value(&span).unwrap());
+}
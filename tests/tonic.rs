@@ -0,0 +1,46 @@
+#![cfg(feature = "tonic")]
+
+use opentelemetry_tracing::exporter::InMemorySpanExporter;
+use opentelemetry_tracing::opentelemetry_sdk::{OpenTelemetrySdk, TraceId};
+use opentelemetry_tracing::processor::SimpleSpanProcessor;
+use opentelemetry_tracing::propagator::{Extractor, W3CTraceContextPropagator};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn traceparent_is_extracted_from_grpc_metadata() {
+    let mut metadata = MetadataMap::new();
+    metadata.insert(
+        "traceparent",
+        MetadataValue::from_static("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+    );
+    metadata.insert_bin("trace-bin", MetadataValue::from_bytes(b"\x00\x01"));
+
+    let context = W3CTraceContextPropagator::new().extract(&metadata).unwrap();
+    assert_eq!(
+        context.trace_id,
+        TraceId::from(0x0af7651916cd43dd8448eb211c80319c)
+    );
+    assert!(context.is_remote);
+    // Keys are matched case-insensitively and binary keys are left out.
+    assert!(Extractor::get(&metadata, "TraceParent").is_some());
+    assert_eq!(Extractor::keys(&metadata), ["traceparent"]);
+}
+
+#[test]
+fn span_context_is_injected_into_grpc_metadata() {
+    let exporter = InMemorySpanExporter::new();
+    let sdk = OpenTelemetrySdk::builder()
+        .with_span_processor(SimpleSpanProcessor::new(Box::new(exporter.clone())))
+        .build()
+        .unwrap();
+    let mut metadata = MetadataMap::new();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(sdk), || {
+        W3CTraceContextPropagator::new().inject(&tracing::info_span!("client"), &mut metadata);
+    });
+    let client = &exporter.spans_named("client")[0];
+    assert_eq!(
+        metadata.get("traceparent").unwrap().to_str().unwrap(),
+        format!("00-{}-{}-01", client.trace_id, client.span_id)
+    );
+}